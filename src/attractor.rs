@@ -0,0 +1,461 @@
+//! Strange attractors: density plots of the orbits of chaotic maps of the plane (Clifford,
+//! Peter de Jong, Ikeda, Hénon) and of projections of chaotic flows in space (Lorenz, Rössler).
+//!
+//! Orbits are traced from random starting points in parallel and every pixel counts how often
+//! an orbit visited it, like the chaos game of `ifs`. Flows are integrated with fourth order
+//! Runge-Kutta steps of length `dt`, which is their last parameter, and projected onto one of the
+//! coordinate planes.
+//!
+//! Points `(x, y)` are shown upright like the maps of `ifs`, at the complex number `-y + x i`.
+
+use crate::density::{self, DensityRenderer, Random, Screen};
+use crate::ifs;
+use crate::mandelbrot::Position;
+use crate::palette::Palette;
+use std::error::Error;
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+// Steps made before plotting, so that the orbit is on the attractor
+const WARM_UP: usize = 1000;
+
+// Points plotted by one parallel task with its own counts
+const POINTS_PER_TASK: u64 = 16384;
+
+// Points used to find the extent of the attractor
+const BOUNDS_POINTS: usize = 50_000;
+
+// Orbits that get this far from the origin diverged and start over
+const ESCAPE_RADIUS: f64 = 1e6;
+
+// Orbits that diverge this often in a row end, the parameters have no attractor
+const MAX_RESTARTS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttractorKind {
+    Clifford,
+    DeJong,
+    Ikeda,
+    Henon,
+    Lorenz,
+    Rossler,
+}
+
+impl AttractorKind {
+    pub const ALL: [AttractorKind; 6] = [
+        AttractorKind::Clifford,
+        AttractorKind::DeJong,
+        AttractorKind::Ikeda,
+        AttractorKind::Henon,
+        AttractorKind::Lorenz,
+        AttractorKind::Rossler,
+    ];
+
+    // Name used in the JavaScript API
+    pub fn name(&self) -> &'static str {
+        match self {
+            AttractorKind::Clifford => "clifford",
+            AttractorKind::DeJong => "de-jong",
+            AttractorKind::Ikeda => "ikeda",
+            AttractorKind::Henon => "henon",
+            AttractorKind::Lorenz => "lorenz",
+            AttractorKind::Rossler => "rossler",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AttractorKind> {
+        AttractorKind::ALL
+            .iter()
+            .find(|kind| kind.name() == name)
+            .cloned()
+    }
+
+    pub fn parameter_names(&self) -> &'static [&'static str] {
+        match self {
+            AttractorKind::Clifford | AttractorKind::DeJong => &["a", "b", "c", "d"],
+            AttractorKind::Ikeda => &["u"],
+            AttractorKind::Henon => &["a", "b"],
+            AttractorKind::Lorenz => &["sigma", "rho", "beta", "dt"],
+            AttractorKind::Rossler => &["a", "b", "c", "dt"],
+        }
+    }
+
+    // Classic chaotic parameters
+    pub fn default_parameters(&self) -> &'static [f64] {
+        match self {
+            AttractorKind::Clifford => &[-1.4, 1.6, 1.0, 0.7],
+            AttractorKind::DeJong => &[1.641, 1.902, 0.316, 1.525],
+            AttractorKind::Ikeda => &[0.918],
+            AttractorKind::Henon => &[1.4, 0.3],
+            AttractorKind::Lorenz => &[10.0, 28.0, 8.0 / 3.0, 0.005],
+            AttractorKind::Rossler => &[0.2, 0.2, 5.7, 0.01],
+        }
+    }
+
+    // Whether the attractor is a flow in space, which is projected onto a plane
+    pub fn is_flow(&self) -> bool {
+        matches!(self, AttractorKind::Lorenz | AttractorKind::Rossler)
+    }
+
+    // The projection showing the familiar shape of the attractor
+    pub fn default_projection(&self) -> Projection {
+        match self {
+            AttractorKind::Lorenz => Projection::XZ,
+            _ => Projection::XY,
+        }
+    }
+}
+
+// Coordinate plane onto which flows are projected, the first axis is shown horizontally
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    XY,
+    XZ,
+    YZ,
+}
+
+impl Projection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Projection::XY => "xy",
+            Projection::XZ => "xz",
+            Projection::YZ => "yz",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Projection> {
+        [Projection::XY, Projection::XZ, Projection::YZ]
+            .iter()
+            .find(|projection| projection.name() == name)
+            .cloned()
+    }
+
+    fn project(&self, [x, y, z]: [f64; 3]) -> (f64, f64) {
+        match self {
+            Projection::XY => (x, y),
+            Projection::XZ => (x, z),
+            Projection::YZ => (y, z),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttractorError {
+    UnknownAttractor(String),
+    UnknownProjection(String),
+    ParameterCount { expected: usize, found: usize },
+    InvalidParameter(&'static str, f64),
+}
+
+impl fmt::Display for AttractorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AttractorError::UnknownAttractor(name) => write!(f, "unknown attractor '{}'", name),
+            AttractorError::UnknownProjection(name) => {
+                write!(f, "unknown projection '{}', expected xy, xz or yz", name)
+            }
+            AttractorError::ParameterCount { expected, found } => write!(
+                f,
+                "the attractor has {} parameters, {} were given",
+                expected, found
+            ),
+            AttractorError::InvalidParameter(name, value) => {
+                write!(f, "invalid value {} for parameter '{}'", value, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AttractorError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attractor {
+    kind: AttractorKind,
+    parameters: Vec<f64>,
+    projection: Projection,
+}
+
+impl Attractor {
+    // Attractor with the parameters in the order of `parameter_names`
+    pub fn new(kind: AttractorKind, parameters: &[f64]) -> Result<Attractor, AttractorError> {
+        let names = kind.parameter_names();
+        if parameters.len() != names.len() {
+            return Err(AttractorError::ParameterCount {
+                expected: names.len(),
+                found: parameters.len(),
+            });
+        }
+        for (name, value) in names.iter().zip(parameters) {
+            let valid = if *name == "dt" {
+                *value > 0.0 && *value <= 0.1
+            } else {
+                value.is_finite()
+            };
+            if !valid {
+                return Err(AttractorError::InvalidParameter(name, *value));
+            }
+        }
+        Ok(Attractor {
+            kind,
+            parameters: parameters.to_vec(),
+            projection: kind.default_projection(),
+        })
+    }
+
+    // Attractor of the type with the name, with the default parameters if `parameters` is empty
+    pub fn from_name(name: &str, parameters: &[f64]) -> Result<Attractor, AttractorError> {
+        let kind = AttractorKind::from_name(name)
+            .ok_or_else(|| AttractorError::UnknownAttractor(name.to_string()))?;
+        if parameters.is_empty() {
+            return Attractor::new(kind, kind.default_parameters());
+        }
+        Attractor::new(kind, parameters)
+    }
+
+    // Projection of flows, maps of the plane are always shown as they are
+    pub fn with_projection(self, projection: Projection) -> Attractor {
+        if !self.kind.is_flow() {
+            return self;
+        }
+        Attractor { projection, ..self }
+    }
+
+    pub fn kind(&self) -> AttractorKind {
+        self.kind
+    }
+
+    pub fn parameters(&self) -> &[f64] {
+        &self.parameters
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    // Next point of the orbit. Points of maps of the plane have z = 0
+    pub fn step(&self, [x, y, z]: [f64; 3]) -> [f64; 3] {
+        let p = &self.parameters;
+        match self.kind {
+            AttractorKind::Clifford => [
+                (p[0] * y).sin() + p[2] * (p[0] * x).cos(),
+                (p[1] * x).sin() + p[3] * (p[1] * y).cos(),
+                0.0,
+            ],
+            AttractorKind::DeJong => [
+                (p[0] * y).sin() - (p[1] * x).cos(),
+                (p[2] * x).sin() - (p[3] * y).cos(),
+                0.0,
+            ],
+            AttractorKind::Ikeda => {
+                let t = 0.4 - 6.0 / (1.0 + x * x + y * y);
+                let (sin, cos) = t.sin_cos();
+                [
+                    1.0 + p[0] * (x * cos - y * sin),
+                    p[0] * (x * sin + y * cos),
+                    0.0,
+                ]
+            }
+            AttractorKind::Henon => [1.0 - p[0] * x * x + y, p[1] * x, 0.0],
+            AttractorKind::Lorenz | AttractorKind::Rossler => self.runge_kutta([x, y, z]),
+        }
+    }
+
+    // Velocity of the flow at a point
+    fn velocity(&self, [x, y, z]: [f64; 3]) -> [f64; 3] {
+        let p = &self.parameters;
+        match self.kind {
+            AttractorKind::Lorenz => [p[0] * (y - x), x * (p[1] - z) - y, x * y - p[2] * z],
+            _ => [-y - z, x + p[0] * y, p[1] + z * (x - p[2])],
+        }
+    }
+
+    fn runge_kutta(&self, point: [f64; 3]) -> [f64; 3] {
+        let dt = self.parameters[3];
+        let shifted = |k: [f64; 3], factor: f64| -> [f64; 3] {
+            [
+                point[0] + k[0] * factor,
+                point[1] + k[1] * factor,
+                point[2] + k[2] * factor,
+            ]
+        };
+        let k1 = self.velocity(point);
+        let k2 = self.velocity(shifted(k1, dt / 2.0));
+        let k3 = self.velocity(shifted(k2, dt / 2.0));
+        let k4 = self.velocity(shifted(k3, dt));
+        let mut next = point;
+        for idx in 0..3 {
+            next[idx] += dt / 6.0 * (k1[idx] + 2.0 * k2[idx] + 2.0 * k3[idx] + k4[idx]);
+        }
+        next
+    }
+
+    // Random point to start an orbit from, close to the attractor of the usual parameters
+    fn start(&self, random: &mut Random) -> [f64; 3] {
+        let mut coordinate = || 0.2 * random.next_f64() - 0.1;
+        let jitter = [coordinate(), coordinate(), coordinate()];
+        match self.kind {
+            AttractorKind::Lorenz | AttractorKind::Rossler => {
+                [1.0 + jitter[0], 1.0 + jitter[1], 1.0 + jitter[2]]
+            }
+            _ => [jitter[0], jitter[1], 0.0],
+        }
+    }
+
+    // Traces an orbit from a random start past the warm-up. Orbits that diverge start over, and
+    // end if they keep diverging
+    fn orbit(&self, mut random: Random) -> impl Iterator<Item = (f64, f64)> + '_ {
+        let mut point = self.start(&mut random);
+        let mut warm_up = WARM_UP;
+        let mut restarts = 0;
+        std::iter::from_fn(move || loop {
+            point = self.step(point);
+            if !point
+                .iter()
+                .all(|c| c.is_finite() && c.abs() < ESCAPE_RADIUS)
+            {
+                restarts += 1;
+                if restarts > MAX_RESTARTS {
+                    return None;
+                }
+                point = self.start(&mut random);
+                warm_up = WARM_UP;
+                continue;
+            }
+            if warm_up > 0 {
+                warm_up -= 1;
+                continue;
+            }
+            restarts = 0;
+            return Some(self.projection.project(point));
+        })
+    }
+
+    // Smallest and largest x and y of the points of a fixed orbit, None if the orbit diverges
+    pub fn bounds(&self) -> Option<((f64, f64), (f64, f64))> {
+        let mut x_range = (f64::INFINITY, f64::NEG_INFINITY);
+        let mut y_range = x_range;
+        for (x, y) in self.orbit(Random(0)).take(BOUNDS_POINTS) {
+            x_range = (x_range.0.min(x), x_range.1.max(x));
+            y_range = (y_range.0.min(y), y_range.1.max(y));
+        }
+        if x_range.0 > x_range.1 {
+            return None;
+        }
+        Some((x_range, y_range))
+    }
+
+    // Position of a `width` x `height` screen of a `Universe` that shows the whole attractor, or
+    // the square around the origin of side 4 if there is none
+    pub fn fitting_position(&self, width: u32, height: u32) -> Position {
+        let bounds = self.bounds().unwrap_or(((-2.0, 2.0), (-2.0, 2.0)));
+        density::fitting_position(bounds, ifs::to_complex, width, height)
+    }
+}
+
+// Names of the attractors for `Universe::start_attractor`
+#[wasm_bindgen]
+pub fn attractor_types() -> Vec<String> {
+    AttractorKind::ALL
+        .iter()
+        .map(|kind| kind.name().to_string())
+        .collect()
+}
+
+// Names of the parameters of an attractor, empty for unknown attractors
+#[wasm_bindgen]
+pub fn attractor_parameter_names(name: &str) -> Vec<String> {
+    AttractorKind::from_name(name).map_or(Vec::new(), |kind| {
+        kind.parameter_names()
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    })
+}
+
+#[wasm_bindgen]
+pub fn attractor_default_parameters(name: &str) -> Vec<f64> {
+    AttractorKind::from_name(name).map_or(Vec::new(), |kind| kind.default_parameters().to_vec())
+}
+
+// Density renderer of the orbits of an attractor
+#[derive(Debug, Clone)]
+pub struct AttractorRenderer {
+    screen: Screen,
+    attractor: Attractor,
+    palette: Palette,
+    counts: Vec<f32>,
+    samples: u64,
+}
+
+impl AttractorRenderer {
+    pub fn new(screen: Screen, attractor: Attractor, palette: Palette) -> AttractorRenderer {
+        let pixels = screen.pixels();
+        AttractorRenderer {
+            screen,
+            attractor,
+            palette,
+            counts: vec![0.0; pixels],
+            samples: 0,
+        }
+    }
+
+    pub fn attractor(&self) -> &Attractor {
+        &self.attractor
+    }
+
+    pub fn counts(&self) -> &[f32] {
+        &self.counts
+    }
+}
+
+impl DensityRenderer for AttractorRenderer {
+    fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    fn restart(
+        &self,
+        screen: Screen,
+    ) -> Result<Box<dyn DensityRenderer>, Box<dyn Error + Send + Sync>> {
+        Ok(Box::new(AttractorRenderer::new(
+            screen,
+            self.attractor.clone(),
+            self.palette.clone(),
+        )))
+    }
+
+    // Every sample is a plotted point, every task traces its own orbit
+    fn accumulate(&mut self, samples: u64) {
+        let pixels = self.counts.len();
+        let (attractor, screen) = (&self.attractor, &self.screen);
+        density::sample_parallel(
+            &mut self.counts,
+            self.samples..self.samples + samples,
+            POINTS_PER_TASK,
+            || vec![0.0; pixels],
+            |a: &mut Vec<f32>, b: &Vec<f32>| density::add_counts(a, b),
+            |random| {
+                // Orbits that keep diverging end and leave the rest of the task empty
+                let mut orbit = attractor.orbit(random).fuse();
+                move |counts: &mut Vec<f32>| {
+                    let pixel = orbit
+                        .next()
+                        .map(ifs::to_complex)
+                        .and_then(|z| screen.pixel(z));
+                    if let Some(idx) = pixel {
+                        counts[idx] += 1.0;
+                    }
+                }
+            },
+        );
+        self.samples += samples;
+    }
+
+    fn to_cells(&self) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        density::palette_cells(&self.counts, &self.palette)
+    }
+
+    fn samples(&self) -> u64 {
+        self.samples
+    }
+}
@@ -0,0 +1,42 @@
+use crate::options::Options;
+use fractal_rs::dzi::{self, DeepZoom};
+use fractal_rs::fractal::{Fractal, RenderSettings};
+use fractal_rs::mandelbrot;
+use fractal_rs::palette::Palette;
+use fractal_rs::viewport::Viewport;
+use num::complex::Complex;
+use std::error::Error;
+use std::path::PathBuf;
+
+// Renders a poster sized image of a location as a DZI pyramid and optionally as a single PNG
+pub fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let out: PathBuf = options.required("out")?;
+    let name = options.get_or("fractal", Fractal::Mandelbrot.name().to_string())?;
+    let fractal = Fractal::from_name(&name).ok_or(format!("unknown fractal '{}'", name))?;
+    let width = options.required("width")?;
+    let height = options.required("height")?;
+    let centre = Complex::new(options.get_or("re", -0.75)?, options.get_or("im", 0.0)?);
+    let viewport = Viewport::with_extent(centre, options.get_or("extent", 3.5)?, width, height);
+    let max_iterations = options.get_or("iterations", mandelbrot::DEFAULT_MAX_ITERATIONS)?;
+    let palette_name = options.get_or("palette", Palette::default().name().to_string())?;
+    let palette = Palette::from_name(&palette_name)
+        .ok_or(format!("unknown palette '{}'", palette_name))?;
+    let settings = RenderSettings::new(fractal, max_iterations, palette);
+    let deep_zoom = DeepZoom {
+        tile_size: options.get_or("tile-size", DeepZoom::default().tile_size)?,
+        overlap: options.get_or("overlap", DeepZoom::default().overlap)?,
+    };
+    if deep_zoom.tile_size == 0 {
+        return Err("--tile-size must be at least 1".into());
+    }
+
+    let max_level = DeepZoom::max_level(width, height);
+    deep_zoom.write(&out, &settings, &viewport, |level| {
+        println!("level {} of {} written", level, max_level);
+    })?;
+    if let Some(stitched) = options.get::<PathBuf>("stitch")? {
+        dzi::write_stitched_png(&stitched, &settings, &viewport, 64)?;
+        println!("stitched image written to {}", stitched.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use crate::options::Options;
+use fractal_rs::export;
+use fractal_rs::fractal::Fractal;
+use fractal_rs::iteration_data::IterationData;
+use fractal_rs::mandelbrot;
+use fractal_rs::palette::Palette;
+use fractal_rs::viewport::Viewport;
+use num::complex::Complex;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+// Renders the raw escape data of a viewport into an iteration data file
+pub fn render(options: &Options) -> Result<(), Box<dyn Error>> {
+    let out: PathBuf = options.required("out")?;
+    let name = options.get_or("fractal", Fractal::Mandelbrot.name().to_string())?;
+    let fractal = Fractal::from_name(&name).ok_or(format!("unknown fractal '{}'", name))?;
+    let centre = Complex::new(options.get_or("re", -0.75)?, options.get_or("im", 0.0)?);
+    let viewport = Viewport::with_extent(
+        centre,
+        options.get_or("extent", 3.5)?,
+        options.required("width")?,
+        options.required("height")?,
+    );
+    let max_iterations = options.get_or("iterations", mandelbrot::DEFAULT_MAX_ITERATIONS)?;
+    let with_distances = options.get_or("distances", false)?;
+
+    let data = IterationData::render(fractal, viewport, max_iterations, with_distances);
+    data.write_to(&mut BufWriter::new(File::create(&out)?))?;
+    println!("iteration data written to {}", out.display());
+    Ok(())
+}
+
+// Colours an iteration data file into a PNG
+pub fn recolour(options: &Options) -> Result<(), Box<dyn Error>> {
+    let input: PathBuf = options.required("in")?;
+    let out: PathBuf = options.required("out")?;
+    let palette_name = options.get_or("palette", Palette::default().name().to_string())?;
+    let palette = Palette::from_name(&palette_name)
+        .ok_or(format!("unknown palette '{}'", palette_name))?;
+    let distance_shading = options.get_or("distance-shading", false)?;
+
+    let data = IterationData::read_from(&mut BufReader::new(File::open(&input)?))?;
+    if distance_shading && data.distances.is_none() {
+        return Err(format!("{} contains no distance estimates", input.display()).into());
+    }
+    let rgba = data.to_rgba(&palette, distance_shading);
+    let (width, height) = (data.viewport.get_width(), data.viewport.get_height());
+    fs::write(&out, export::encode_png(width, height, &rgba)?)?;
+    println!("image written to {}", out.display());
+    Ok(())
+}
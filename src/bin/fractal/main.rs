@@ -0,0 +1,51 @@
+//! Command line tools for rendering fractals to disk.
+
+mod dzi;
+mod iterations;
+mod mesh;
+mod options;
+mod tiles;
+
+use std::env;
+use std::error::Error;
+use std::process;
+
+const USAGE: &str = "usage: fractal <command> [options]
+
+commands:
+  tiles     pre-generate a directory of XYZ map tiles
+            --out <dir> [--fractal <name>] [--min-zoom <z>] [--max-zoom <z>]
+            [--iterations <n>]
+  dzi       render a poster as a Deep Zoom Image pyramid
+            --out <base> --width <px> --height <px> [--re <x>] [--im <y>]
+            [--extent <width in the plane>] [--fractal <name>] [--iterations <n>]
+            [--palette <name>] [--tile-size <px>] [--overlap <px>] [--stitch <png>]
+  iterations  render raw escape data into an iteration data file
+            --out <file> --width <px> --height <px> [--re <x>] [--im <y>]
+            [--extent <width in the plane>] [--fractal <name>] [--iterations <n>]
+            [--distances <true|false>]
+  recolour  colour an iteration data file into a PNG
+            --in <file> --out <png> [--palette <name>] [--distance-shading <true|false>]
+  mesh      turn an iteration data file into a heightfield mesh for 3D printing
+            --in <file> --out <obj or stl> [--transfer <linear|sqrt|log|power>]
+            [--exponent <power>] [--smoothing <passes>] [--size <width>] [--relief <height>]
+            [--base <thickness>] [--decimation <tolerance>]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result: Result<(), Box<dyn Error>> = match args.first().map(String::as_str) {
+        Some(command) => options::Options::parse(&args[1..]).and_then(|options| match command {
+            "tiles" => tiles::run(&options),
+            "dzi" => dzi::run(&options),
+            "iterations" => iterations::render(&options),
+            "recolour" => iterations::recolour(&options),
+            "mesh" => mesh::run(&options),
+            _ => Err(USAGE.into()),
+        }),
+        None => Err(USAGE.into()),
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
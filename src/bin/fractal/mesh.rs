@@ -0,0 +1,56 @@
+use crate::options::Options;
+use fractal_rs::iteration_data::IterationData;
+use fractal_rs::mesh::{HeightTransfer, Mesh, MeshSettings};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+// Turns an iteration data file into a heightfield mesh, written as OBJ or binary STL by the
+// extension of the output file
+pub fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let input: PathBuf = options.required("in")?;
+    let out: PathBuf = options.required("out")?;
+    let extension = out
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+    let stl = match extension.as_deref() {
+        Some("stl") => true,
+        Some("obj") => false,
+        _ => return Err(format!("{} is neither an .obj nor an .stl file", out.display()).into()),
+    };
+    let defaults = MeshSettings::default();
+    let transfer = match options.get::<String>("transfer")?.as_deref() {
+        None => defaults.transfer,
+        Some("linear") => HeightTransfer::Linear,
+        Some("sqrt") => HeightTransfer::SquareRoot,
+        Some("log") => HeightTransfer::Logarithmic,
+        Some("power") => HeightTransfer::Power(options.required("exponent")?),
+        Some(name) => return Err(format!("unknown height transfer '{}'", name).into()),
+    };
+    let settings = MeshSettings {
+        transfer,
+        smoothing: options.get_or("smoothing", defaults.smoothing)?,
+        size: options.get_or("size", defaults.size)?,
+        relief: options.get_or("relief", defaults.relief)?,
+        base: options.get_or("base", defaults.base)?,
+        decimation: options.get("decimation")?,
+    };
+
+    let data = IterationData::read_from(&mut BufReader::new(File::open(&input)?))?;
+    let mesh = Mesh::from_iteration_data(&data, &settings)?;
+    let mut writer = BufWriter::new(File::create(&out)?);
+    if stl {
+        mesh.write_stl(&mut writer)?;
+    } else {
+        mesh.write_obj(&mut writer)?;
+    }
+    writer.flush()?;
+    println!(
+        "mesh of {} triangles written to {}",
+        mesh.triangles.len(),
+        out.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::str::FromStr;
+
+// Options of a subcommand, given as `--name value` pairs
+pub struct Options {
+    values: HashMap<String, String>,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Options, Box<dyn Error>> {
+        let mut values = HashMap::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument '{}'", arg))?;
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for --{}", name))?;
+            values.insert(name.to_string(), value.clone());
+        }
+        Ok(Options { values })
+    }
+
+    pub fn get<T: FromStr>(&self, name: &str) -> Result<Option<T>, Box<dyn Error>> {
+        match self.values.get(name) {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("invalid value '{}' for --{}", value, name).into()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_or<T: FromStr>(&self, name: &str, default: T) -> Result<T, Box<dyn Error>> {
+        Ok(self.get(name)?.unwrap_or(default))
+    }
+
+    pub fn required<T: FromStr>(&self, name: &str) -> Result<T, Box<dyn Error>> {
+        self.get(name)?
+            .ok_or_else(|| format!("missing required option --{}", name).into())
+    }
+}
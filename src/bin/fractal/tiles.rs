@@ -0,0 +1,59 @@
+use crate::options::Options;
+use fractal_rs::fractal::Fractal;
+use fractal_rs::{export, slippy};
+use rayon::prelude::*;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+// Deepest zoom level of a pre-generated pyramid, whose last level alone holds 4^12 (about 16.8
+// million) tiles. Deeper levels are better rendered on demand by the tile server
+const MAX_EXPORT_ZOOM: u32 = 12;
+
+// Writes the tiles of all requested zoom levels to `<out>/<z>/<x>/<y>.png`
+pub fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let out: PathBuf = options.required("out")?;
+    let name = options.get_or("fractal", Fractal::Mandelbrot.name().to_string())?;
+    let fractal =
+        Fractal::from_name(&name).ok_or(slippy::TileError::UnknownFractal(name))?;
+    let min_zoom = options.get_or("min-zoom", 0)?;
+    let max_zoom = options.get_or("max-zoom", 4)?;
+    let iterations: Option<u32> = options.get("iterations")?;
+    if max_zoom > MAX_EXPORT_ZOOM {
+        return Err(format!(
+            "--max-zoom {} exceeds the limit of {} for generated tile directories",
+            max_zoom, MAX_EXPORT_ZOOM
+        )
+        .into());
+    }
+    if min_zoom > max_zoom {
+        return Err(format!(
+            "--min-zoom {} is larger than --max-zoom {}",
+            min_zoom, max_zoom
+        )
+        .into());
+    }
+
+    for z in min_zoom..=max_zoom {
+        let max_iterations = iterations.unwrap_or_else(|| slippy::default_max_iterations(z));
+        // Fits into u32 coordinates below the export limit
+        let tiles = (1u64 << z) as u32;
+        for x in 0..tiles {
+            fs::create_dir_all(out.join(z.to_string()).join(x.to_string()))?;
+        }
+        let coordinates: Vec<(u32, u32)> =
+            (0..tiles).flat_map(|x| (0..tiles).map(move |y| (x, y))).collect();
+        coordinates
+            .par_iter()
+            .try_for_each(|&(x, y)| -> Result<(), Box<dyn Error + Send + Sync>> {
+                let rgba = slippy::render_tile(&fractal, z, x, y, max_iterations)?;
+                let png = export::encode_png(slippy::TILE_SIZE, slippy::TILE_SIZE, &rgba)?;
+                let path = out.join(z.to_string()).join(x.to_string()).join(format!("{}.png", y));
+                fs::write(path, png)?;
+                Ok(())
+            })
+            .map_err(|err| err as Box<dyn Error>)?;
+        println!("zoom level {}: {} tiles", z, coordinates.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,116 @@
+//! Serves XYZ map tiles at `/tiles/{fractal}/{z}/{x}/{y}.png` on localhost.
+//!
+//! Rendered tiles are kept in an on-disk cache and at most `--threads` tiles are rendered at the
+//! same time, further requests wait until a thread is free.
+
+// Only part of the option helpers are needed here
+#[allow(dead_code)]
+#[path = "../fractal/options.rs"]
+mod options;
+
+use fractal_rs::slippy::{TileError, TileRequest};
+use fractal_rs::{export, slippy};
+use options::Options;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use tiny_http::{Header, Request, Response, Server};
+
+const USAGE: &str = "usage: tile-server [--port <port>] [--cache-dir <dir>] [--threads <n>]";
+
+// File of the tile in the cache, which keeps the tiles of every iteration limit apart
+fn cache_path(tile: &TileRequest, cache_dir: &Path) -> PathBuf {
+    cache_dir
+        .join(tile.fractal.name())
+        .join(tile.max_iterations.to_string())
+        .join(tile.z.to_string())
+        .join(tile.x.to_string())
+        .join(format!("{}.png", tile.y))
+}
+
+// Returns the PNG of the tile from the cache, rendering and storing it first if necessary.
+// `worker` is the index of the thread handling the request
+fn load_tile(
+    tile: &TileRequest,
+    cache_dir: &Path,
+    worker: usize,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let path = cache_path(tile, cache_dir);
+    if let Ok(png) = fs::read(&path) {
+        return Ok(png);
+    }
+    let rgba = slippy::render_tile(&tile.fractal, tile.z, tile.x, tile.y, tile.max_iterations)?;
+    let png = export::encode_png(slippy::TILE_SIZE, slippy::TILE_SIZE, &rgba)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Write to a temporary file of the worker first so that concurrent requests never read a
+    // partial tile
+    let partial = path.with_extension(format!("png.{}.partial", worker));
+    fs::write(&partial, &png)?;
+    fs::rename(&partial, &path)?;
+    Ok(png)
+}
+
+fn respond(request: Request, cache_dir: &Path, worker: usize) {
+    let tile = match TileRequest::parse(request.url()) {
+        Ok(tile) => tile,
+        Err(err) => {
+            let status = match err {
+                TileError::InvalidQuery(_) => 400,
+                _ => 404,
+            };
+            let response = Response::from_string(err.to_string()).with_status_code(status);
+            let _ = request.respond(response);
+            return;
+        }
+    };
+    let response = match load_tile(&tile, cache_dir, worker) {
+        Ok(png) => Response::from_data(png)
+            .with_header(Header::from_bytes(&b"Content-Type"[..], &b"image/png"[..]).unwrap())
+            .with_header(
+                Header::from_bytes(&b"Access-Control-Allow-Origin"[..], &b"*"[..]).unwrap(),
+            ),
+        // The tile exists, so only the cache can have failed
+        Err(err) => Response::from_string(err.to_string()).with_status_code(500),
+    };
+    let _ = request.respond(response);
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let port: u16 = options.get_or("port", 8080)?;
+    let cache_dir: PathBuf = options.get_or("cache-dir", PathBuf::from("tile-cache"))?;
+    let default_threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let threads: usize = options.get_or("threads", default_threads)?;
+    let server = Arc::new(Server::http(("127.0.0.1", port)).map_err(|err| err.to_string())?);
+    println!("serving tiles at http://127.0.0.1:{}/tiles/{{fractal}}/{{z}}/{{x}}/{{y}}.png", port);
+
+    // Every thread handles one request at a time, which bounds the number of concurrent renders
+    let handles: Vec<_> = (0..threads.max(1))
+        .map(|worker| {
+            let server = server.clone();
+            let cache_dir = cache_dir.clone();
+            thread::spawn(move || {
+                while let Ok(request) = server.recv() {
+                    respond(request, &cache_dir, worker);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        let _ = handle.join();
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(err) = Options::parse(&args).and_then(|options| run(&options)) {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(1);
+    }
+}
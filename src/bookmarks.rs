@@ -0,0 +1,345 @@
+//! Named views with tags and thumbnails, and a catalogue of famous locations of the mandelbrot
+//! set.
+//!
+//! Bookmarks are exported as JSON of the form
+//!
+//! ```text
+//! {
+//!   "version": 1,
+//!   "bookmarks": [
+//!     { "name": "...", "tags": ["..."], "state": "...", "thumbnail": "..." }
+//!   ]
+//! }
+//! ```
+//!
+//! where `state` is the view serialised by `ViewState::encode` and `thumbnail` an optional PNG
+//! image encoded with the URL-safe base64 alphabet.
+
+use crate::export::ViewMetadata;
+use crate::fractal::{Fractal, RenderSettings};
+use crate::palette::Palette;
+use crate::state::{self, ViewState};
+use crate::Universe;
+use num::complex::Complex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+pub const VERSION: u32 = 1;
+
+// Width of bookmark thumbnails in pixels, the height follows the aspect ratio of the screen
+pub const THUMBNAIL_WIDTH: u32 = 96;
+
+#[derive(Debug)]
+pub enum BookmarkError {
+    Json(serde_json::Error),
+    UnsupportedVersion(u32),
+    InvalidState(String, state::StateError),
+    InvalidThumbnail(String),
+    UnknownBookmark(String),
+}
+
+impl fmt::Display for BookmarkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BookmarkError::Json(err) => write!(f, "invalid bookmark file: {}", err),
+            BookmarkError::UnsupportedVersion(version) => write!(
+                f,
+                "bookmark file version {} is not supported, expected {}",
+                version, VERSION
+            ),
+            BookmarkError::InvalidState(name, err) => {
+                write!(f, "invalid view of bookmark '{}': {}", name, err)
+            }
+            BookmarkError::InvalidThumbnail(name) => {
+                write!(f, "invalid thumbnail of bookmark '{}'", name)
+            }
+            BookmarkError::UnknownBookmark(name) => write!(f, "unknown bookmark '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for BookmarkError {}
+
+impl From<serde_json::Error> for BookmarkError {
+    fn from(err: serde_json::Error) -> BookmarkError {
+        BookmarkError::Json(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub name: String,
+    pub tags: Vec<String>,
+    // PNG image of the view
+    pub thumbnail: Option<Vec<u8>>,
+    pub state: ViewState,
+}
+
+// Layout of exported bookmark files
+#[derive(Serialize, Deserialize)]
+struct BookmarkFile {
+    version: u32,
+    bookmarks: Vec<BookmarkRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BookmarkRecord {
+    name: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    state: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    thumbnail: Option<String>,
+}
+
+// Bookmarks ordered by the time they were added. Names are unique, adding a bookmark with the
+// name of an existing one replaces it
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BookmarkStore {
+    bookmarks: Vec<Bookmark>,
+}
+
+// Internal functions NOT exposed to JS
+impl BookmarkStore {
+    pub fn bookmarks(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Bookmark> {
+        self.bookmarks.iter().find(|bookmark| bookmark.name == name)
+    }
+
+    pub fn insert(&mut self, bookmark: Bookmark) {
+        match self.bookmarks.iter_mut().find(|b| b.name == bookmark.name) {
+            Some(existing) => *existing = bookmark,
+            None => self.bookmarks.push(bookmark),
+        }
+    }
+
+    pub fn with_tag(&self, tag: &str) -> Vec<&Bookmark> {
+        self.bookmarks
+            .iter()
+            .filter(|bookmark| bookmark.tags.iter().any(|t| t == tag))
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Result<String, BookmarkError> {
+        let file = BookmarkFile {
+            version: VERSION,
+            bookmarks: self
+                .bookmarks
+                .iter()
+                .map(|bookmark| BookmarkRecord {
+                    name: bookmark.name.clone(),
+                    tags: bookmark.tags.clone(),
+                    state: bookmark.state.encode(),
+                    thumbnail: bookmark.thumbnail.as_deref().map(state::encode_base64),
+                })
+                .collect(),
+        };
+        Ok(serde_json::to_string_pretty(&file)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<BookmarkStore, BookmarkError> {
+        let file: BookmarkFile = serde_json::from_str(json)?;
+        if file.version != VERSION {
+            return Err(BookmarkError::UnsupportedVersion(file.version));
+        }
+        let mut store = BookmarkStore::default();
+        for record in file.bookmarks {
+            let state = ViewState::decode(&record.state)
+                .map_err(|err| BookmarkError::InvalidState(record.name.clone(), err))?;
+            let thumbnail = match &record.thumbnail {
+                Some(thumbnail) => Some(
+                    state::decode_base64(thumbnail)
+                        .ok_or_else(|| BookmarkError::InvalidThumbnail(record.name.clone()))?,
+                ),
+                None => None,
+            };
+            store.insert(Bookmark {
+                name: record.name,
+                tags: record.tags,
+                thumbnail,
+                state,
+            });
+        }
+        Ok(store)
+    }
+
+    // Adds all bookmarks of the other store, replacing the ones with the same name
+    pub fn merge(&mut self, other: BookmarkStore) {
+        for bookmark in other.bookmarks {
+            self.insert(bookmark);
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl BookmarkStore {
+    pub fn new() -> BookmarkStore {
+        BookmarkStore::default()
+    }
+
+    // Parses bookmarks exported by `export_json`
+    pub fn import_json(json: &str) -> Result<BookmarkStore, JsValue> {
+        BookmarkStore::from_json(json).map_err(|err| JsValue::from(err.to_string()))
+    }
+
+    pub fn export_json(&self) -> Result<String, JsValue> {
+        self.to_json().map_err(|err| JsValue::from(err.to_string()))
+    }
+
+    // Adds the bookmarks of another export, replacing the ones with the same name
+    pub fn merge_json(&mut self, json: &str) -> Result<(), JsValue> {
+        let other = BookmarkStore::import_json(json)?;
+        self.merge(other);
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.bookmarks
+            .iter()
+            .map(|bookmark| bookmark.name.clone())
+            .collect()
+    }
+
+    pub fn names_with_tag(&self, tag: &str) -> Vec<String> {
+        self.with_tag(tag)
+            .iter()
+            .map(|bookmark| bookmark.name.clone())
+            .collect()
+    }
+
+    pub fn tags(&self, name: &str) -> Vec<String> {
+        self.get(name)
+            .map(|bookmark| bookmark.tags.clone())
+            .unwrap_or_default()
+    }
+
+    // PNG thumbnail of the bookmark, e.g. for creating a Blob
+    pub fn thumbnail(&self, name: &str) -> Option<Vec<u8>> {
+        self.get(name)
+            .and_then(|bookmark| bookmark.thumbnail.clone())
+    }
+
+    // Bookmarks the current view of the universe together with a thumbnail of it
+    pub fn add(
+        &mut self,
+        universe: &Universe,
+        name: &str,
+        tags: Vec<String>,
+    ) -> Result<(), JsValue> {
+        let bookmark = universe
+            .bookmark(name, tags)
+            .map_err(|err| JsValue::from(err.to_string()))?;
+        self.insert(bookmark);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.bookmarks.len();
+        self.bookmarks.retain(|bookmark| bookmark.name != name);
+        self.bookmarks.len() != len
+    }
+
+    pub fn go_to(&self, universe: &Universe, name: &str) -> Result<(), JsValue> {
+        let bookmark = self
+            .get(name)
+            .ok_or_else(|| BookmarkError::UnknownBookmark(name.to_string()))
+            .map_err(|err| JsValue::from(err.to_string()))?;
+        universe.restore_state(&bookmark.state);
+        Ok(())
+    }
+}
+
+// Well known location of the mandelbrot set. `extent` is the size of the complex plane shown
+// along the real axis
+#[derive(Debug, Clone, PartialEq)]
+pub struct FamousLocation {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub centre: Complex<f64>,
+    pub extent: f64,
+    pub max_iterations: u32,
+}
+
+pub const FAMOUS_LOCATIONS: [FamousLocation; 6] = [
+    FamousLocation {
+        name: "Whole set",
+        description: "The complete mandelbrot set",
+        centre: Complex::new(-0.75, 0.0),
+        extent: 3.0,
+        max_iterations: 100,
+    },
+    FamousLocation {
+        name: "Seahorse Valley",
+        description: "Between the main cardioid and the period 2 bulb",
+        centre: Complex::new(-0.745, 0.105),
+        extent: 0.03,
+        max_iterations: 500,
+    },
+    FamousLocation {
+        name: "Elephant Valley",
+        description: "Between the main cardioid and its cusp on the positive real axis",
+        centre: Complex::new(0.2925, 0.0149),
+        extent: 0.03,
+        max_iterations: 500,
+    },
+    FamousLocation {
+        name: "Misiurewicz point i",
+        description: "c = i, whose orbit 0, i, -1 + i, -i, -1 + i, ... is preperiodic",
+        centre: Complex::new(0.0, 1.0),
+        extent: 0.05,
+        max_iterations: 500,
+    },
+    FamousLocation {
+        name: "Misiurewicz point -2",
+        description: "c = -2, the tip of the antenna with orbit 0, -2, 2, 2, ...",
+        centre: Complex::new(-2.0, 0.0),
+        extent: 0.05,
+        max_iterations: 500,
+    },
+    FamousLocation {
+        name: "Misiurewicz point M4,1",
+        description: "c = -0.10109636 + 0.95628651i, preperiodic with preperiod 4 and period 1",
+        centre: Complex::new(-0.10109636384562, 0.95628651080914),
+        extent: 0.005,
+        max_iterations: 1000,
+    },
+];
+
+// Looks up a famous location by its name, ignoring case
+pub fn famous_location(name: &str) -> Option<&'static FamousLocation> {
+    FAMOUS_LOCATIONS
+        .iter()
+        .find(|location| location.name.eq_ignore_ascii_case(name))
+}
+
+impl FamousLocation {
+    // View of a `width` x `height` screen of a `Universe`, whose rows run along the real axis
+    pub fn view_metadata(&self, width: u32, height: u32) -> ViewMetadata {
+        let pixel_size = self.extent / height as f64;
+        ViewMetadata {
+            settings: RenderSettings::new(
+                Fractal::Mandelbrot,
+                self.max_iterations,
+                Palette::default(),
+            ),
+            centre: self.centre,
+            zoom_factor: 1.0 / (width as f64 * pixel_size),
+            width,
+            height,
+        }
+    }
+}
+
+// Names of the built-in famous locations for `Universe::go_to_location`
+#[wasm_bindgen]
+pub fn famous_locations() -> Vec<String> {
+    FAMOUS_LOCATIONS
+        .iter()
+        .map(|location| location.name.to_string())
+        .collect()
+}
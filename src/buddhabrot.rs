@@ -0,0 +1,278 @@
+//! Buddhabrot and Nebulabrot images: the density of the orbits of points that escape the
+//! mandelbrot set.
+//!
+//! Unlike the escape time kernels, which colour every pixel on its own, the renderer samples
+//! random points `c`, traces the orbits of the ones that escape and counts every visited pixel.
+//! Each colour channel counts the orbits whose escape iteration falls into its band, equal bands
+//! give the grey Buddhabrot and bands of increasing length the Nebulabrot.
+//!
+//! Sampling is progressive: `accumulate` adds samples to the histograms, which can be displayed at
+//! any time. Samples are drawn where an initial probe found orbits that cross the view, and are
+//! weighted by the inverse of their probability so that the image matches uniform sampling.
+
+use crate::density::{self, DensityRenderer, Random, Screen};
+use num::complex::Complex;
+use rayon::prelude::*;
+use std::error::Error;
+
+// Points are sampled from the square of the plane that contains the mandelbrot set
+const SAMPLE_MIN: f64 = -2.0;
+const SAMPLE_SIZE: f64 = 4.0;
+
+// Cells per side of the grid used for importance sampling, and probes per cell
+const GRID_SIZE: usize = 64;
+const PROBES_PER_CELL: usize = 4;
+
+// Share of the uniform distribution mixed into the importance, so that no region is skipped
+const UNIFORM_SHARE: f64 = 0.1;
+
+// Samples traced by one parallel task with its own histogram
+const SAMPLES_PER_TASK: u64 = 4096;
+
+// Escape iterations `min_iterations..=max_iterations` counted by a colour channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Band {
+    pub min_iterations: u32,
+    pub max_iterations: u32,
+}
+
+impl Band {
+    pub fn new(min_iterations: u32, max_iterations: u32) -> Band {
+        Band {
+            min_iterations,
+            max_iterations,
+        }
+    }
+
+    fn contains(&self, iterations: u32) -> bool {
+        (self.min_iterations..=self.max_iterations).contains(&iterations)
+    }
+}
+
+// Bands of the red, green and blue channels
+pub type Bands = [Band; 3];
+
+// The same band in all channels
+pub fn buddhabrot_bands(max_iterations: u32) -> Bands {
+    [Band::new(0, max_iterations); 3]
+}
+
+// The classic Nebulabrot with long orbits in red and short ones in blue
+pub fn nebulabrot_bands() -> Bands {
+    [Band::new(0, 2000), Band::new(0, 200), Band::new(0, 20)]
+}
+
+#[derive(Debug, Clone)]
+pub struct Buddhabrot {
+    screen: Screen,
+    bands: Bands,
+    // Counts of the red, green and blue channel in the pixel order of `Universe`
+    histograms: [Vec<f32>; 3],
+    samples: u64,
+    // Cumulative probability of the cells of the sampling grid
+    cumulative: Vec<f64>,
+}
+
+impl Buddhabrot {
+    // Renderer of the view of the screen. The plane is probed for importance sampling by the
+    // first call of `accumulate`
+    pub fn new(screen: Screen, bands: Bands) -> Buddhabrot {
+        let pixels = screen.pixels();
+        Buddhabrot {
+            screen,
+            bands,
+            histograms: [vec![0.0; pixels], vec![0.0; pixels], vec![0.0; pixels]],
+            samples: 0,
+            cumulative: Vec::new(),
+        }
+    }
+
+    pub fn bands(&self) -> Bands {
+        self.bands
+    }
+
+    pub fn histograms(&self) -> &[Vec<f32>; 3] {
+        &self.histograms
+    }
+
+    fn max_iterations(&self) -> u32 {
+        self.bands
+            .iter()
+            .map(|band| band.max_iterations)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Iterates z^2 + c, keeping the orbit. Returns the escape iteration, None if the point does
+    // not escape within the longest band
+    fn orbit(&self, c: Complex<f64>, orbit: &mut Vec<Complex<f64>>) -> Option<u32> {
+        orbit.clear();
+        if in_main_components(c) {
+            return None;
+        }
+        let mut z = Complex::new(0.0, 0.0);
+        for iter in 0..self.max_iterations() {
+            z = z * z + c;
+            orbit.push(z);
+            if z.norm_sqr() > 4.0 {
+                return Some(iter + 1);
+            }
+        }
+        None
+    }
+
+    fn trace_sample(
+        &self,
+        random: &mut Random,
+        orbit: &mut Vec<Complex<f64>>,
+        histograms: &mut [Vec<f32>; 3],
+    ) {
+        let (c, weight) = self.draw(random);
+        let iterations = match self.orbit(c, orbit) {
+            Some(iterations) => iterations,
+            None => return,
+        };
+        for (band, histogram) in self.bands.iter().zip(histograms.iter_mut()) {
+            if !band.contains(iterations) {
+                continue;
+            }
+            for z in orbit.iter() {
+                if let Some(idx) = self.screen.pixel(*z) {
+                    histogram[idx] += weight as f32;
+                }
+            }
+        }
+    }
+
+    // Draws a point from the importance distribution, with the weight that corrects for it
+    fn draw(&self, random: &mut Random) -> (Complex<f64>, f64) {
+        let u = random.next_f64();
+        let cell = self
+            .cumulative
+            .partition_point(|p| *p <= u)
+            .min(self.cumulative.len() - 1);
+        let probability = self.cumulative[cell]
+            - if cell > 0 {
+                self.cumulative[cell - 1]
+            } else {
+                0.0
+            };
+        let cell_size = SAMPLE_SIZE / GRID_SIZE as f64;
+        let c = Complex::new(
+            SAMPLE_MIN + ((cell % GRID_SIZE) as f64 + random.next_f64()) * cell_size,
+            SAMPLE_MIN + ((cell / GRID_SIZE) as f64 + random.next_f64()) * cell_size,
+        );
+        let uniform = 1.0 / (GRID_SIZE * GRID_SIZE) as f64;
+        (c, uniform / probability)
+    }
+
+    // Probes every cell of the sampling grid and returns the cumulative distribution of cells,
+    // proportional to the number of orbit points the probes left in the view
+    fn probe(&self) -> Vec<f64> {
+        let cell_size = SAMPLE_SIZE / GRID_SIZE as f64;
+        let hits: Vec<f64> = (0..GRID_SIZE * GRID_SIZE)
+            .into_par_iter()
+            .map(|cell| {
+                let mut random = Random(cell as u64);
+                let mut orbit = Vec::new();
+                let mut hits = 0;
+                for _ in 0..PROBES_PER_CELL {
+                    let c = Complex::new(
+                        SAMPLE_MIN + ((cell % GRID_SIZE) as f64 + random.next_f64()) * cell_size,
+                        SAMPLE_MIN + ((cell / GRID_SIZE) as f64 + random.next_f64()) * cell_size,
+                    );
+                    if self.orbit(c, &mut orbit).is_some() {
+                        hits += orbit
+                            .iter()
+                            .filter(|z| self.screen.pixel(**z).is_some())
+                            .count();
+                    }
+                }
+                hits as f64
+            })
+            .collect();
+        let total: f64 = hits.iter().sum();
+        let uniform = 1.0 / hits.len() as f64;
+        let mut sum = 0.0;
+        hits.iter()
+            .map(|hits| {
+                let importance = if total > 0.0 { hits / total } else { uniform };
+                sum += UNIFORM_SHARE * uniform + (1.0 - UNIFORM_SHARE) * importance;
+                sum
+            })
+            .collect()
+    }
+}
+
+impl DensityRenderer for Buddhabrot {
+    fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    fn restart(
+        &self,
+        screen: Screen,
+    ) -> Result<Box<dyn DensityRenderer>, Box<dyn Error + Send + Sync>> {
+        Ok(Box::new(Buddhabrot::new(screen, self.bands)))
+    }
+
+    fn accumulate(&mut self, samples: u64) {
+        if self.cumulative.is_empty() {
+            self.cumulative = self.probe();
+        }
+        let pixels = self.histograms[0].len();
+        let mut histograms = std::mem::take(&mut self.histograms);
+        let this = &*self;
+        density::sample_parallel(
+            &mut histograms,
+            self.samples..self.samples + samples,
+            SAMPLES_PER_TASK,
+            || [vec![0.0; pixels], vec![0.0; pixels], vec![0.0; pixels]],
+            |a: &mut [Vec<f32>; 3], b: &[Vec<f32>; 3]| {
+                for (a, b) in a.iter_mut().zip(b.iter()) {
+                    density::add_counts(a, b);
+                }
+            },
+            |mut random| {
+                let mut orbit = Vec::new();
+                move |histograms: &mut [Vec<f32>; 3]| {
+                    this.trace_sample(&mut random, &mut orbit, histograms)
+                }
+            },
+        );
+        self.histograms = histograms;
+        self.samples += samples;
+    }
+
+    // Tone maps the histograms to colour channels, scaling the square root of every channel to
+    // its largest count
+    fn to_cells(&self) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let channel = |histogram: &Vec<f32>| -> Vec<u8> {
+            let max = histogram.iter().cloned().fold(0.0f32, f32::max);
+            if max <= 0.0 {
+                return vec![0; histogram.len()];
+            }
+            histogram
+                .iter()
+                .map(|count| ((count / max).sqrt() * 255.0) as u8)
+                .collect()
+        };
+        (
+            channel(&self.histograms[0]),
+            channel(&self.histograms[1]),
+            channel(&self.histograms[2]),
+        )
+    }
+
+    fn samples(&self) -> u64 {
+        self.samples
+    }
+}
+
+// Whether the point lies in the main cardioid or the period 2 bulb, whose points never escape
+fn in_main_components(c: Complex<f64>) -> bool {
+    let q = (c.re - 0.25).powi(2) + c.im * c.im;
+    let in_cardioid = q * (q + (c.re - 0.25)) <= 0.25 * c.im * c.im;
+    let in_bulb = (c.re + 1.0).powi(2) + c.im * c.im <= 1.0 / 16.0;
+    in_cardioid || in_bulb
+}
@@ -0,0 +1,183 @@
+//! Shared parts of the renderers that plot points onto the screen and show how often every pixel
+//! was hit, instead of colouring every pixel on its own like the escape time kernels.
+//!
+//! Renderers accumulate samples progressively in parallel with `sample_parallel`: every rayon
+//! task counts into its own buffer, which are summed at the end, and the image can be drawn after
+//! every step.
+
+use crate::mandelbrot::Position;
+use crate::palette::Palette;
+use num::complex::Complex;
+use rayon::prelude::*;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+// Share of the screen left empty around a fitted attractor
+pub const MARGIN: f64 = 0.05;
+
+// A `width` x `height` screen of a `Universe` at a position
+#[derive(Debug, Clone, PartialEq)]
+pub struct Screen {
+    pub position: Position,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Screen {
+    pub fn new(position: Position, width: u32, height: u32) -> Screen {
+        Screen {
+            position,
+            width,
+            height,
+        }
+    }
+
+    pub fn pixels(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    // Index of the pixel showing the point in the order of the cells of a `Universe`, None if it
+    // is outside of the screen
+    pub fn pixel(&self, point: Complex<f64>) -> Option<usize> {
+        let (row, column) = self
+            .position
+            .complex_to_pixel(point, self.width, self.height);
+        // Pixels are centred on their integer coordinates
+        let (row, column) = ((row + 0.5).floor(), (column + 0.5).floor());
+        // Also rejects points that are not finite
+        if !(0.0..self.height as f64).contains(&row) || !(0.0..self.width as f64).contains(&column)
+        {
+            return None;
+        }
+        Some(row as usize * self.width as usize + column as usize)
+    }
+}
+
+// Renderer accumulating a density image of a screen
+pub trait DensityRenderer: fmt::Debug + Send + Sync {
+    fn screen(&self) -> &Screen;
+
+    // The same renderer for another screen, with nothing accumulated yet
+    fn restart(
+        &self,
+        screen: Screen,
+    ) -> Result<Box<dyn DensityRenderer>, Box<dyn Error + Send + Sync>>;
+
+    // Adds `samples` samples in parallel on the current rayon thread pool
+    fn accumulate(&mut self, samples: u64);
+
+    // Number of samples accumulated so far
+    fn samples(&self) -> u64;
+
+    // Red, green and blue cells of the image of all samples so far
+    fn to_cells(&self) -> (Vec<u8>, Vec<u8>, Vec<u8>);
+}
+
+// Runs the samples with the given indices in parallel tasks of `per_task` samples and adds the
+// counts of all tasks to `total`. Every task counts into its own buffer made by `empty`, calling
+// the function that `task` makes from a `Random` seeded with the index of its first sample once
+// per sample. `add` adds the counts of a buffer to another
+pub fn sample_parallel<C, T, S>(
+    total: &mut C,
+    samples: Range<u64>,
+    per_task: u64,
+    empty: impl Fn() -> C + Sync + Send,
+    add: impl Fn(&mut C, &C) + Sync + Send,
+    task: T,
+) where
+    C: Send,
+    T: Fn(Random) -> S + Sync + Send,
+    S: FnMut(&mut C),
+{
+    let count = samples.end.saturating_sub(samples.start);
+    let merged = (0..count.div_ceil(per_task))
+        .into_par_iter()
+        .fold(&empty, |mut counts, idx| {
+            let start = idx * per_task;
+            let mut sample = task(Random(samples.start + start));
+            for _ in 0..per_task.min(count - start) {
+                sample(&mut counts);
+            }
+            counts
+        })
+        .reduce_with(|mut a, b| {
+            add(&mut a, &b);
+            a
+        });
+    if let Some(merged) = merged {
+        add(total, &merged);
+    }
+}
+
+// Adds the counts of another buffer
+pub fn add_counts(counts: &mut [f32], other: &[f32]) {
+    counts.iter_mut().zip(other).for_each(|(a, b)| *a += b);
+}
+
+// Position of a `width` x `height` screen of a `Universe` that shows the points within the x and y
+// ranges with a margin. `to_complex` maps the points to the complex plane with x along the
+// imaginary axis, like `ifs::to_complex`
+pub fn fitting_position(
+    ((x_min, x_max), (y_min, y_max)): ((f64, f64), (f64, f64)),
+    to_complex: fn((f64, f64)) -> Complex<f64>,
+    width: u32,
+    height: u32,
+) -> Position {
+    let extent = |min: f64, max: f64| ((max - min) * (1.0 + 2.0 * MARGIN)).max(1e-9);
+    let zoom_factor =
+        Position::zoom_factor_fitting(extent(y_min, y_max), extent(x_min, x_max), width, height);
+    let centre = to_complex(((x_min + x_max) / 2.0, (y_min + y_max) / 2.0));
+    Position::from_centre(centre, zoom_factor, width, height)
+}
+
+// Logarithm of the count relative to the largest count, between 0 for empty pixels and 1
+pub fn log_density(count: f32, max: f32) -> f64 {
+    if max <= 0.0 {
+        return 0.0;
+    }
+    (count as f64).ln_1p() / (max as f64).ln_1p()
+}
+
+// Colours the logarithm of the counts relative to the largest one with the palette, pixels that
+// were never hit stay black
+pub fn palette_cells(counts: &[f32], palette: &Palette) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    let max = counts.iter().cloned().fold(0.0f32, f32::max);
+    let (mut r, mut g, mut b) = (
+        Vec::with_capacity(counts.len()),
+        Vec::with_capacity(counts.len()),
+        Vec::with_capacity(counts.len()),
+    );
+    for count in counts {
+        let rgb = if *count > 0.0 {
+            // Quotient 1.0 is black like the inside of the mandelbrot set
+            palette.rgb_value(log_density(*count, max).min(0.999))
+        } else {
+            (0, 0, 0)
+        };
+        r.push(rgb.0);
+        g.push(rgb.1);
+        b.push(rgb.2);
+    }
+    (r, g, b)
+}
+
+// Generator of uniform random numbers, SplitMix64. Seeding it with the index of the first sample
+// of a task makes images independent of how tasks are spread over threads
+#[derive(Debug, Clone)]
+pub struct Random(pub u64);
+
+impl Random {
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,183 @@
+//! Forward-mode automatic differentiation with complex dual numbers.
+//!
+//! A `Dual` carries a value together with its derivative with respect to a single complex
+//! variable, e.g. dz/dc of an iterated formula. Every operation applies the chain rule, so
+//! evaluating any expression on duals yields its derivative without further work.
+
+use num::complex::Complex;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Dual {
+    pub value: Complex<f64>,
+    pub derivative: Complex<f64>,
+}
+
+impl Dual {
+    pub fn new(value: Complex<f64>, derivative: Complex<f64>) -> Dual {
+        Dual { value, derivative }
+    }
+
+    // A value that does not depend on the variable
+    pub fn constant(value: Complex<f64>) -> Dual {
+        Dual::new(value, Complex::new(0.0, 0.0))
+    }
+
+    // The variable itself, whose derivative is 1
+    pub fn variable(value: Complex<f64>) -> Dual {
+        Dual::new(value, Complex::new(1.0, 0.0))
+    }
+
+    // Applies a holomorphic function given its value and derivative at `self.value`
+    fn chain(self, value: Complex<f64>, derivative: Complex<f64>) -> Dual {
+        Dual::new(value, derivative * self.derivative)
+    }
+
+    pub fn powi(self, exponent: i32) -> Dual {
+        if exponent == 0 {
+            return Dual::constant(Complex::new(1.0, 0.0));
+        }
+        let power = self.value.powi(exponent - 1);
+        self.chain(power * self.value, power * exponent as f64)
+    }
+
+    // a^b = exp(b ln a), whose derivative is b a^(b-1) a' + ln(a) a^b b'. The logarithm is only
+    // needed for exponents that depend on the variable, which avoids NaN for a = 0
+    pub fn powc(self, exponent: Dual) -> Dual {
+        let value = self.value.powc(exponent.value);
+        let mut derivative =
+            exponent.value * self.value.powc(exponent.value - 1.0) * self.derivative;
+        if exponent.derivative != Complex::new(0.0, 0.0) {
+            derivative += self.value.ln() * value * exponent.derivative;
+        }
+        Dual::new(value, derivative)
+    }
+
+    pub fn sqrt(self) -> Dual {
+        let root = self.value.sqrt();
+        self.chain(root, 0.5 / root)
+    }
+
+    pub fn exp(self) -> Dual {
+        let exp = self.value.exp();
+        self.chain(exp, exp)
+    }
+
+    pub fn ln(self) -> Dual {
+        self.chain(self.value.ln(), 1.0 / self.value)
+    }
+
+    pub fn sin(self) -> Dual {
+        self.chain(self.value.sin(), self.value.cos())
+    }
+
+    pub fn cos(self) -> Dual {
+        self.chain(self.value.cos(), -self.value.sin())
+    }
+
+    pub fn tan(self) -> Dual {
+        let cos = self.value.cos();
+        self.chain(self.value.tan(), 1.0 / (cos * cos))
+    }
+
+    pub fn sinh(self) -> Dual {
+        self.chain(self.value.sinh(), self.value.cosh())
+    }
+
+    pub fn cosh(self) -> Dual {
+        self.chain(self.value.cosh(), self.value.sinh())
+    }
+
+    // The functions below are not holomorphic. Their derivatives are taken along the real axis
+    // of the variable, which keeps distance estimates usable for folding formulas like the
+    // burning ship
+
+    pub fn conj(self) -> Dual {
+        Dual::new(self.value.conj(), self.derivative.conj())
+    }
+
+    pub fn abs(self) -> Dual {
+        let norm = self.value.norm();
+        // At 0 the one-sided derivative, |x| grows with the speed of x in any direction
+        let derivative = if norm == 0.0 {
+            self.derivative.norm()
+        } else {
+            (self.value.conj() * self.derivative).re / norm
+        };
+        Dual::new(Complex::new(norm, 0.0), Complex::new(derivative, 0.0))
+    }
+
+    pub fn re(self) -> Dual {
+        Dual::new(
+            Complex::new(self.value.re, 0.0),
+            Complex::new(self.derivative.re, 0.0),
+        )
+    }
+
+    pub fn im(self) -> Dual {
+        Dual::new(
+            Complex::new(self.value.im, 0.0),
+            Complex::new(self.derivative.im, 0.0),
+        )
+    }
+
+    pub fn arg(self) -> Dual {
+        // At 0 the one-sided derivative, the argument is constant along rays from 0
+        let derivative = if self.value == Complex::new(0.0, 0.0) {
+            0.0
+        } else {
+            (self.derivative / self.value).im
+        };
+        Dual::new(
+            Complex::new(self.value.arg(), 0.0),
+            Complex::new(derivative, 0.0),
+        )
+    }
+}
+
+impl Add for Dual {
+    type Output = Dual;
+
+    fn add(self, other: Dual) -> Dual {
+        Dual::new(self.value + other.value, self.derivative + other.derivative)
+    }
+}
+
+impl Sub for Dual {
+    type Output = Dual;
+
+    fn sub(self, other: Dual) -> Dual {
+        Dual::new(self.value - other.value, self.derivative - other.derivative)
+    }
+}
+
+impl Mul for Dual {
+    type Output = Dual;
+
+    fn mul(self, other: Dual) -> Dual {
+        Dual::new(
+            self.value * other.value,
+            self.derivative * other.value + self.value * other.derivative,
+        )
+    }
+}
+
+impl Div for Dual {
+    type Output = Dual;
+
+    fn div(self, other: Dual) -> Dual {
+        let value = self.value / other.value;
+        Dual::new(
+            value,
+            (self.derivative - value * other.derivative) / other.value,
+        )
+    }
+}
+
+impl Neg for Dual {
+    type Output = Dual;
+
+    fn neg(self) -> Dual {
+        Dual::new(-self.value, -self.derivative)
+    }
+}
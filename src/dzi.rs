@@ -0,0 +1,138 @@
+//! Export of gigapixel images as Deep Zoom Image (DZI) pyramids.
+//!
+//! Every level of the pyramid is rendered tile by tile straight from the fractal, so memory use
+//! only depends on the tile size and not on the size of the image.
+
+use crate::export;
+use crate::fractal::RenderSettings;
+use crate::viewport::Viewport;
+use png::{BitDepth, ColorType, Encoder};
+use rayon::prelude::*;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+// Tile layout of a DZI pyramid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeepZoom {
+    pub tile_size: u32,
+    pub overlap: u32,
+}
+
+impl Default for DeepZoom {
+    fn default() -> DeepZoom {
+        DeepZoom {
+            tile_size: 254,
+            overlap: 1,
+        }
+    }
+}
+
+impl DeepZoom {
+    // Index of the full resolution level, level 0 is a single pixel
+    pub fn max_level(width: u32, height: u32) -> u32 {
+        let size = width.max(height).max(1);
+        32 - (size - 1).leading_zeros()
+    }
+
+    // Size of the image at the given level, every level halves the one above it
+    pub fn level_size(width: u32, height: u32, level: u32) -> (u32, u32) {
+        let scale = 1u64 << (DeepZoom::max_level(width, height) - level);
+        let shrink = |size: u32| (size as u64).div_ceil(scale).max(1) as u32;
+        (shrink(width), shrink(height))
+    }
+
+    // Pixel range covered by tile `index` along an axis of `size` pixels, including the overlap
+    fn tile_span(&self, index: u32, size: u32) -> (u32, u32) {
+        let start = (index * self.tile_size).saturating_sub(self.overlap);
+        let end = ((index + 1) * self.tile_size + self.overlap).min(size);
+        (start, end - start)
+    }
+
+    fn descriptor(&self, width: u32, height: u32) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" TileSize=\"{}\" \
+             Overlap=\"{}\" Format=\"png\">\n  <Size Width=\"{}\" Height=\"{}\"/>\n</Image>\n",
+            self.tile_size, self.overlap, width, height
+        )
+    }
+
+    // Writes `<base>.dzi` and the tiles in `<base>_files/<level>/<column>_<row>.png`, calling
+    // `progress` with the number of each level once it is complete. Fails for a tile size of 0
+    pub fn write(
+        &self,
+        base: &Path,
+        settings: &RenderSettings,
+        viewport: &Viewport,
+        progress: impl Fn(u32),
+    ) -> Result<(), Box<dyn Error>> {
+        if self.tile_size == 0 {
+            return Err("the tile size of a DZI pyramid must be at least 1".into());
+        }
+        let (width, height) = (viewport.get_width(), viewport.get_height());
+        if let Some(dir) = base.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(base.with_extension("dzi"), self.descriptor(width, height))?;
+        let mut files = base.as_os_str().to_owned();
+        files.push("_files");
+        let files = PathBuf::from(files);
+
+        for level in 0..=DeepZoom::max_level(width, height) {
+            let (level_width, level_height) = DeepZoom::level_size(width, height, level);
+            let level_viewport = viewport.resized(level_width, level_height);
+            let level_dir = files.join(level.to_string());
+            fs::create_dir_all(&level_dir)?;
+            let columns = level_width.div_ceil(self.tile_size);
+            let rows = level_height.div_ceil(self.tile_size);
+            let tiles: Vec<(u32, u32)> =
+                (0..columns).flat_map(|col| (0..rows).map(move |row| (col, row))).collect();
+            tiles
+                .par_iter()
+                .try_for_each(|&(col, row)| -> Result<(), Box<dyn Error + Send + Sync>> {
+                    let (x, tile_width) = self.tile_span(col, level_width);
+                    let (y, tile_height) = self.tile_span(row, level_height);
+                    let rgba =
+                        level_viewport.render_rgba(settings, (x, y), (tile_width, tile_height));
+                    let png = export::encode_png(tile_width, tile_height, &rgba)?;
+                    fs::write(level_dir.join(format!("{}_{}.png", col, row)), png)?;
+                    Ok(())
+                })
+                .map_err(|err| err as Box<dyn Error>)?;
+            progress(level);
+        }
+        Ok(())
+    }
+}
+
+// Writes the whole viewport into a single PNG, rendering and streaming `band_height` rows at a
+// time to keep memory bounded
+pub fn write_stitched_png(
+    path: &Path,
+    settings: &RenderSettings,
+    viewport: &Viewport,
+    band_height: u32,
+) -> Result<(), Box<dyn Error>> {
+    let (width, height) = (viewport.get_width(), viewport.get_height());
+    let mut encoder = Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    let mut stream = writer.stream_writer()?;
+    let band_height = band_height.max(1);
+    for y in (0..height).step_by(band_height as usize) {
+        let band_height = band_height.min(height - y);
+        // Render the rows of the band in parallel
+        let rows: Vec<Vec<u8>> = (y..y + band_height)
+            .into_par_iter()
+            .map(|row| viewport.render_rgba(settings, (0, row), (width, 1)))
+            .collect();
+        for row in rows {
+            std::io::Write::write_all(&mut stream, &row)?;
+        }
+    }
+    stream.finish()?;
+    Ok(())
+}
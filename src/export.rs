@@ -0,0 +1,137 @@
+use crate::fractal::{Fractal, RenderSettings};
+use crate::mandelbrot::Position;
+use crate::palette::Palette;
+use num::complex::Complex;
+use png::{BitDepth, ColorType, Decoder, Encoder, EncodingError};
+use std::error::Error;
+use std::fmt;
+
+// Encodes RGBA bytes in row major order as a PNG image
+pub fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>, EncodingError> {
+    encode_png_with_text(width, height, rgba, &[])
+}
+
+// Encodes RGBA bytes as a PNG image with a tEXt chunk for every (keyword, text) pair
+pub fn encode_png_with_text(
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    text: &[(String, String)],
+) -> Result<Vec<u8>, EncodingError> {
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut bytes, width, height);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        for (keyword, text) in text {
+            encoder.add_text_chunk(keyword.clone(), text.clone())?;
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(rgba)?;
+    }
+    Ok(bytes)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetadataError {
+    Missing(&'static str),
+    Invalid(&'static str, String),
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetadataError::Missing(keyword) => write!(f, "missing '{}' metadata", keyword),
+            MetadataError::Invalid(keyword, text) => {
+                write!(f, "invalid '{}' metadata: '{}'", keyword, text)
+            }
+        }
+    }
+}
+
+impl Error for MetadataError {}
+
+// Location of a view as stored in exported images, enough to render it again exactly
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewMetadata {
+    pub settings: RenderSettings,
+    pub centre: Complex<f64>,
+    pub zoom_factor: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ViewMetadata {
+    // f64 values are formatted with the shortest representation that parses back to the same value
+    pub fn to_text(&self) -> Vec<(String, String)> {
+        vec![
+            ("Software".to_string(), "fractal-rs".to_string()),
+            ("Fractal".to_string(), self.settings.fractal.spec()),
+            ("Centre".to_string(), format!("{},{}", self.centre.re, self.centre.im)),
+            ("Zoom".to_string(), self.zoom_factor.to_string()),
+            ("Iterations".to_string(), self.settings.max_iterations.to_string()),
+            ("Palette".to_string(), self.settings.palette.name().to_string()),
+        ]
+    }
+
+    pub fn from_text(
+        text: &[(String, String)],
+        width: u32,
+        height: u32,
+    ) -> Result<ViewMetadata, MetadataError> {
+        let value = |keyword: &'static str| {
+            text.iter()
+                .find(|(key, _)| key == keyword)
+                .map(|(_, value)| value.as_str())
+                .ok_or(MetadataError::Missing(keyword))
+        };
+        let invalid = |keyword: &'static str, value: &str| {
+            MetadataError::Invalid(keyword, value.to_string())
+        };
+
+        let fractal = value("Fractal")?;
+        let fractal = Fractal::from_spec(fractal).ok_or_else(|| invalid("Fractal", fractal))?;
+        let centre = value("Centre")?;
+        let (re, im) = centre
+            .split_once(',')
+            .and_then(|(re, im)| Some((re.trim().parse().ok()?, im.trim().parse().ok()?)))
+            .ok_or_else(|| invalid("Centre", centre))?;
+        let zoom = value("Zoom")?;
+        let zoom_factor = zoom
+            .parse()
+            .ok()
+            .filter(|zoom_factor| Position::is_valid_zoom_factor(*zoom_factor))
+            .ok_or_else(|| invalid("Zoom", zoom))?;
+        let iterations = value("Iterations")?;
+        let max_iterations = iterations
+            .parse()
+            .map_err(|_| invalid("Iterations", iterations))?;
+        let palette = value("Palette")?;
+        let palette = Palette::from_name(palette).ok_or_else(|| invalid("Palette", palette))?;
+
+        Ok(ViewMetadata {
+            settings: RenderSettings::new(fractal, max_iterations, palette),
+            centre: Complex::new(re, im),
+            zoom_factor,
+            width,
+            height,
+        })
+    }
+}
+
+// Encodes RGBA bytes as a PNG image recording the view they show
+pub fn encode_view_png(rgba: &[u8], metadata: &ViewMetadata) -> Result<Vec<u8>, EncodingError> {
+    encode_png_with_text(metadata.width, metadata.height, rgba, &metadata.to_text())
+}
+
+// Reads the view recorded in the tEXt chunks of a PNG image written by `encode_view_png`
+pub fn read_view_png(bytes: &[u8]) -> Result<ViewMetadata, Box<dyn Error>> {
+    let reader = Decoder::new(bytes).read_info()?;
+    let info = reader.info();
+    let text: Vec<(String, String)> = info
+        .uncompressed_latin1_text
+        .iter()
+        .map(|chunk| (chunk.keyword.clone(), chunk.text.clone()))
+        .collect();
+    Ok(ViewMetadata::from_text(&text, info.width, info.height)?)
+}
@@ -0,0 +1,435 @@
+//! Tokenizer and recursive descent parser of the expressions of formulas, Fractint `.frm` files
+//! and L-system rules.
+//!
+//! Expressions are made of numbers like `2.5e-3`, variables, calls `name(arguments)` and, from
+//! the loosest to the tightest binding, the operators `||`, `&&`, the comparisons
+//! `< <= > >= == !=`, `+ -`, `* /`, the unary `-` and `!` and the right associative `^`, so
+//! `-z^2` is `-(z^2)`. Fractint formulas also write the squared modulus as `|x|` and complex
+//! constants as `(re, im)`.
+//!
+//! New lines and `;` end statements. The users of the parser read statements themselves with
+//! `Parser::assignment` and the tokens around them, only expressions are parsed here.
+
+use num::complex::Complex;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl SyntaxError {
+    pub fn new((line, column): (usize, usize), message: impl Into<String>) -> SyntaxError {
+        SyntaxError {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(Complex<f64>),
+    Variable(String),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    // `|x|`, the squared modulus
+    Modulus(Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Standard,
+    // Adds `|x|` and `(re, im)`
+    Fractint,
+}
+
+// Names an expression may refer to, others are syntax errors
+pub trait Scope {
+    fn is_variable(&self, name: &str) -> bool;
+
+    // Number of arguments of the function, None if there is no such function
+    fn arity(&self, name: &str) -> Option<usize>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(&'static str),
+    Separator,
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "number {}", value),
+            Token::Name(name) => write!(f, "'{}'", name),
+            Token::Symbol(symbol) => write!(f, "'{}'", symbol),
+            Token::Separator => write!(f, "end of statement"),
+            Token::End => write!(f, "end of formula"),
+        }
+    }
+}
+
+// Longer symbols first, so that `<=` is not read as `<`
+const SYMBOLS: [&str; 21] = [
+    "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "^", "(", ")", ",", "=", "<", ">", "!",
+    "|", ":", ";",
+];
+
+// Binary operators of each precedence level, from the loosest to the tightest binding
+const LEVELS: [&[(&str, BinaryOp)]; 5] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[
+        ("<=", BinaryOp::LessEqual),
+        (">=", BinaryOp::GreaterEqual),
+        ("==", BinaryOp::Equal),
+        ("!=", BinaryOp::NotEqual),
+        ("<", BinaryOp::Less),
+        (">", BinaryOp::Greater),
+    ],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[("*", BinaryOp::Mul), ("/", BinaryOp::Div)],
+];
+
+// Level of `+` and `-`, the loosest binding operators within `|x|`
+const SUM_LEVEL: usize = 3;
+
+// Deepest nesting of parentheses, calls, moduli, unary operators and powers, so that crafted
+// input fails with a syntax error before the recursion overflows the stack. A level takes about
+// 11 KB of stack in debug builds, well within the 1 MB of a wasm module.
+pub const MAX_DEPTH: usize = 64;
+
+// Tokens with their line and column
+fn tokenize(source: &str, first_line: usize) -> Result<Vec<(Token, usize, usize)>, SyntaxError> {
+    let mut tokens = Vec::new();
+    for (line_idx, line) in source.lines().enumerate() {
+        let line_number = first_line + line_idx;
+        let mut idx = 0;
+        while idx < line.len() {
+            let rest = &line[idx..];
+            let position = (line_number, line[..idx].chars().count() + 1);
+            let c = rest.chars().next().unwrap_or(' ');
+            if c.is_whitespace() {
+                idx += c.len_utf8();
+            } else if c.is_ascii_digit() || c == '.' {
+                let end = number_end(rest);
+                let value = rest[..end].parse().map_err(|_| {
+                    SyntaxError::new(position, format!("invalid number '{}'", &rest[..end]))
+                })?;
+                tokens.push((Token::Number(value), position.0, position.1));
+                idx += end;
+            } else if c.is_alphabetic() || c == '_' {
+                let end = rest
+                    .find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                tokens.push((Token::Name(rest[..end].to_string()), position.0, position.1));
+                idx += end;
+            } else {
+                let symbol = SYMBOLS
+                    .iter()
+                    .find(|symbol| rest.starts_with(*symbol))
+                    .ok_or_else(|| {
+                        SyntaxError::new(position, format!("unexpected character '{}'", c))
+                    })?;
+                let token = if *symbol == ";" {
+                    Token::Separator
+                } else {
+                    Token::Symbol(symbol)
+                };
+                tokens.push((token, position.0, position.1));
+                idx += symbol.len();
+            }
+        }
+        tokens.push((Token::Separator, line_number, line.chars().count() + 1));
+    }
+    let (line, column) = match tokens.last() {
+        Some((_, line, column)) => (*line, *column),
+        None => (first_line, 1),
+    };
+    tokens.push((Token::End, line, column));
+    Ok(tokens)
+}
+
+// Length of the number at the start of the text, including an exponent like in `1e-3`
+fn number_end(text: &str) -> usize {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    match text[end..].strip_prefix(['e', 'E']) {
+        Some(exponent) => {
+            let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+            let digits = exponent
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(exponent.len());
+            if digits > 0 {
+                text.len() - exponent.len() + digits
+            } else {
+                end
+            }
+        }
+        None => end,
+    }
+}
+
+// Value of a real number, possibly negated like the parts of `(-0.5, -0.3)`
+fn real_constant(expr: &Expr) -> Option<f64> {
+    match expr {
+        Expr::Number(value) if value.im == 0.0 => Some(value.re),
+        Expr::Negate(value) => real_constant(value).map(|value| -value),
+        _ => None,
+    }
+}
+
+pub struct Parser {
+    tokens: Vec<(Token, usize, usize)>,
+    next: usize,
+    dialect: Dialect,
+    depth: usize,
+}
+
+impl Parser {
+    // Splits the source into tokens, `first_line` is the number of its first line in errors
+    pub fn new(source: &str, first_line: usize, dialect: Dialect) -> Result<Parser, SyntaxError> {
+        Ok(Parser {
+            tokens: tokenize(source, first_line)?,
+            next: 0,
+            dialect,
+            depth: 0,
+        })
+    }
+
+    pub fn peek(&self) -> &Token {
+        &self.tokens[self.next].0
+    }
+
+    // Line and column of the next token
+    pub fn position(&self) -> (usize, usize) {
+        let (_, line, column) = self.tokens[self.next];
+        (line, column)
+    }
+
+    pub fn error<T>(&self, message: impl Into<String>) -> Result<T, SyntaxError> {
+        Err(SyntaxError::new(self.position(), message))
+    }
+
+    // Consumes the next token, the end is never passed
+    pub fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].0.clone();
+        if token != Token::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    // Consumes the symbol if it comes next
+    pub fn eat(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Token::Symbol(next) if *next == symbol) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn expect(&mut self, symbol: &str, context: &str) -> Result<(), SyntaxError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            self.error(format!(
+                "expected '{}' {}, found {}",
+                symbol,
+                context,
+                self.peek()
+            ))
+        }
+    }
+
+    // Consumes `name =` if an assignment comes next and returns the name
+    pub fn assignment(&mut self) -> Option<String> {
+        match (&self.tokens[self.next].0, self.tokens.get(self.next + 1)) {
+            (Token::Name(name), Some((Token::Symbol("="), _, _))) => {
+                let name = name.clone();
+                self.next += 2;
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn expression(&mut self, scope: &dyn Scope) -> Result<Expr, SyntaxError> {
+        self.binary(0, scope)
+    }
+
+    fn binary(&mut self, level: usize, scope: &dyn Scope) -> Result<Expr, SyntaxError> {
+        if level == LEVELS.len() {
+            return self.unary(scope);
+        }
+        let mut left = self.binary(level + 1, scope)?;
+        'operators: loop {
+            for (symbol, op) in LEVELS[level] {
+                if self.eat(symbol) {
+                    let right = self.binary(level + 1, scope)?;
+                    left = Expr::Binary(*op, Box::new(left), Box::new(right));
+                    continue 'operators;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    // Every nested expression passes through here, which bounds the depth of the recursion
+    fn unary(&mut self, scope: &dyn Scope) -> Result<Expr, SyntaxError> {
+        if self.depth == MAX_DEPTH {
+            return self.error(format!(
+                "expression nested deeper than {} levels",
+                MAX_DEPTH
+            ));
+        }
+        self.depth += 1;
+        let result = self.nested_unary(scope);
+        self.depth -= 1;
+        result
+    }
+
+    fn nested_unary(&mut self, scope: &dyn Scope) -> Result<Expr, SyntaxError> {
+        if self.eat("-") {
+            return Ok(Expr::Negate(Box::new(self.unary(scope)?)));
+        }
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary(scope)?)));
+        }
+        let base = self.primary(scope)?;
+        if self.eat("^") {
+            let exponent = self.unary(scope)?;
+            return Ok(Expr::Binary(
+                BinaryOp::Pow,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self, scope: &dyn Scope) -> Result<Expr, SyntaxError> {
+        let position = self.position();
+        match self.peek().clone() {
+            Token::Number(value) => {
+                self.next += 1;
+                Ok(Expr::Number(Complex::new(value, 0.0)))
+            }
+            Token::Name(name) => {
+                self.next += 1;
+                if self.eat("(") {
+                    self.call(name, position, scope)
+                } else if scope.is_variable(&name) {
+                    Ok(Expr::Variable(name))
+                } else {
+                    Err(SyntaxError::new(
+                        position,
+                        format!("unknown variable '{}'", name),
+                    ))
+                }
+            }
+            Token::Symbol("(") => {
+                self.next += 1;
+                let value = self.expression(scope)?;
+                if self.dialect == Dialect::Fractint && self.eat(",") {
+                    let im = self.expression(scope)?;
+                    self.expect(")", "to close the complex constant")?;
+                    return match (real_constant(&value), real_constant(&im)) {
+                        (Some(re), Some(im)) => Ok(Expr::Number(Complex::new(re, im))),
+                        _ => Err(SyntaxError::new(
+                            position,
+                            "complex constants need numeric parts",
+                        )),
+                    };
+                }
+                self.expect(")", "to close the parenthesis")?;
+                Ok(value)
+            }
+            Token::Symbol("|") if self.dialect == Dialect::Fractint => {
+                self.next += 1;
+                let value = self.binary(SUM_LEVEL, scope)?;
+                self.expect("|", "to close the modulus")?;
+                Ok(Expr::Modulus(Box::new(value)))
+            }
+            token => self.error(format!(
+                "expected a number, variable or function, found {}",
+                token
+            )),
+        }
+    }
+
+    // Arguments of a function call, the opening parenthesis has been consumed
+    fn call(
+        &mut self,
+        name: String,
+        position: (usize, usize),
+        scope: &dyn Scope,
+    ) -> Result<Expr, SyntaxError> {
+        let arity = match scope.arity(&name) {
+            Some(arity) => arity,
+            None => {
+                return Err(SyntaxError::new(
+                    position,
+                    format!("unknown function '{}'", name),
+                ))
+            }
+        };
+        let mut arguments = Vec::with_capacity(arity);
+        for argument in 0..arity {
+            if argument > 0 && !self.eat(",") {
+                return self.error(format!(
+                    "'{}' takes {} arguments, expected ',' but found {}",
+                    name,
+                    arity,
+                    self.peek()
+                ));
+            }
+            arguments.push(self.expression(scope)?);
+        }
+        if matches!(self.peek(), Token::Symbol(",")) {
+            return self.error(format!("'{}' takes {} argument(s)", name, arity));
+        }
+        self.expect(")", &format!("after the arguments of '{}'", name))?;
+        Ok(Expr::Call(name, arguments))
+    }
+}
@@ -0,0 +1,936 @@
+//! Fractal flames in the style of Scott Draves' flam3: iterated function systems whose transforms
+//! follow the affine map by a weighted sum of nonlinear variations, coloured by the transforms
+//! that led to every point.
+//!
+//! Every transform has a colour coordinate. While the chaos game runs, the colour coordinate of
+//! the point moves towards the one of the chosen transform, and every plotted point adds the
+//! palette colour at its coordinate to its pixel. The image is tone mapped like flam3: the
+//! brightness of a pixel is the logarithm of its hit count, corrected by `gamma`, and `vibrancy`
+//! blends between applying gamma to the brightness only (1.0, saturated colours) and to every
+//! colour channel (0.0).
+//!
+//! Points are counted on a grid `supersample` times finer than the screen. Before the grid is
+//! reduced to the screen, sparse cells are spread over a radius that shrinks with their count,
+//! the density estimation of flam3, which smooths the noise of rare points while dense regions
+//! stay sharp.
+//!
+//! Genomes can be imported from the `.flame` XML files of flam3 and Apophysis. Their images use
+//! the image orientation of flam3, with `y` growing downwards.
+
+use crate::density::{self, DensityRenderer, Random, Screen};
+use crate::ifs::{AffineMap, Ifs, IfsError};
+use crate::mandelbrot::Position;
+use crate::palette::Palette;
+use num::complex::Complex;
+use rayon::prelude::*;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fmt;
+
+// Finest supported supersampling
+pub const MAX_SUPERSAMPLE: u32 = 4;
+
+// Largest radius of the density estimation, every cell of the estimate gathers the cells within
+// it, like the practical limit of flam3
+pub const MAX_ESTIMATOR_RADIUS: f64 = 32.0;
+
+// Colours of a flame palette
+pub const PALETTE_SIZE: usize = 256;
+
+// Iterations made before plotting, so that the point is on the attractor
+const FUSE: usize = 20;
+
+// Points plotted by one parallel task with its own histogram
+const POINTS_PER_TASK: u64 = 16384;
+
+// Points used to find the extent of flames without a camera
+const BOUNDS_POINTS: usize = 20_000;
+
+// Share of the attractor's extent that may be cut off on each side when fitting the view, since
+// variations like spherical throw rare points far away
+const BOUNDS_QUANTILE: f64 = 0.005;
+
+// Keeps variations finite at the origin
+const EPSILON: f64 = 1e-10;
+
+// Attributes of `.flame` transforms that are not variations and do not change the image enough
+// to be rejected
+const IGNORED_XFORM_ATTRIBUTES: [&str; 6] = [
+    "opacity",
+    "animate",
+    "name",
+    "plotmode",
+    "var_color",
+    "chaos",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variation {
+    Linear,
+    Sinusoidal,
+    Spherical,
+    Swirl,
+    Horseshoe,
+    Polar,
+    Handkerchief,
+    Heart,
+    Disc,
+    Spiral,
+    Hyperbolic,
+    Diamond,
+    Ex,
+    Julia,
+    Bent,
+    Fisheye,
+    Exponential,
+    Power,
+    Cosine,
+    Bubble,
+    Cylinder,
+    Eyefish,
+    Tangent,
+}
+
+impl Variation {
+    pub const ALL: [Variation; 23] = [
+        Variation::Linear,
+        Variation::Sinusoidal,
+        Variation::Spherical,
+        Variation::Swirl,
+        Variation::Horseshoe,
+        Variation::Polar,
+        Variation::Handkerchief,
+        Variation::Heart,
+        Variation::Disc,
+        Variation::Spiral,
+        Variation::Hyperbolic,
+        Variation::Diamond,
+        Variation::Ex,
+        Variation::Julia,
+        Variation::Bent,
+        Variation::Fisheye,
+        Variation::Exponential,
+        Variation::Power,
+        Variation::Cosine,
+        Variation::Bubble,
+        Variation::Cylinder,
+        Variation::Eyefish,
+        Variation::Tangent,
+    ];
+
+    // Name of the variation in `.flame` files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variation::Linear => "linear",
+            Variation::Sinusoidal => "sinusoidal",
+            Variation::Spherical => "spherical",
+            Variation::Swirl => "swirl",
+            Variation::Horseshoe => "horseshoe",
+            Variation::Polar => "polar",
+            Variation::Handkerchief => "handkerchief",
+            Variation::Heart => "heart",
+            Variation::Disc => "disc",
+            Variation::Spiral => "spiral",
+            Variation::Hyperbolic => "hyperbolic",
+            Variation::Diamond => "diamond",
+            Variation::Ex => "ex",
+            Variation::Julia => "julia",
+            Variation::Bent => "bent",
+            Variation::Fisheye => "fisheye",
+            Variation::Exponential => "exponential",
+            Variation::Power => "power",
+            Variation::Cosine => "cosine",
+            Variation::Bubble => "bubble",
+            Variation::Cylinder => "cylinder",
+            Variation::Eyefish => "eyefish",
+            Variation::Tangent => "tangent",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Variation> {
+        Variation::ALL
+            .iter()
+            .find(|variation| variation.name() == name)
+            .cloned()
+    }
+
+    // The variation at a point, as defined by flam3. The angle θ is measured from the y axis
+    // like in flam3, so that `sin θ = x / r` and `cos θ = y / r`
+    pub fn apply(&self, (x, y): (f64, f64), random: &mut Random) -> (f64, f64) {
+        let r2 = x * x + y * y;
+        let r = r2.sqrt();
+        let theta = x.atan2(y);
+        let (sin_theta, cos_theta) = (x / (r + EPSILON), y / (r + EPSILON));
+        match self {
+            Variation::Linear => (x, y),
+            Variation::Sinusoidal => (x.sin(), y.sin()),
+            Variation::Spherical => (x / (r2 + EPSILON), y / (r2 + EPSILON)),
+            Variation::Swirl => {
+                let (sin, cos) = r2.sin_cos();
+                (x * sin - y * cos, x * cos + y * sin)
+            }
+            Variation::Horseshoe => {
+                let r = r + EPSILON;
+                ((x - y) * (x + y) / r, 2.0 * x * y / r)
+            }
+            Variation::Polar => (theta / PI, r - 1.0),
+            Variation::Handkerchief => (r * (theta + r).sin(), r * (theta - r).cos()),
+            Variation::Heart => (r * (theta * r).sin(), -r * (theta * r).cos()),
+            Variation::Disc => {
+                let (sin, cos) = (PI * r).sin_cos();
+                (theta / PI * sin, theta / PI * cos)
+            }
+            Variation::Spiral => {
+                let r = r + EPSILON;
+                ((cos_theta + r.sin()) / r, (sin_theta - r.cos()) / r)
+            }
+            Variation::Hyperbolic => (sin_theta / (r + EPSILON), cos_theta * r),
+            Variation::Diamond => (sin_theta * r.cos(), cos_theta * r.sin()),
+            Variation::Ex => {
+                let n0 = (theta + r).sin().powi(3);
+                let n1 = (theta - r).cos().powi(3);
+                (r * (n0 + n1), r * (n0 - n1))
+            }
+            Variation::Julia => {
+                let omega = if random.next_u64() & 1 == 0 { 0.0 } else { PI };
+                let (sin, cos) = (theta / 2.0 + omega).sin_cos();
+                (r.sqrt() * cos, r.sqrt() * sin)
+            }
+            Variation::Bent => (
+                if x < 0.0 { 2.0 * x } else { x },
+                if y < 0.0 { y / 2.0 } else { y },
+            ),
+            Variation::Fisheye => {
+                let factor = 2.0 / (r + 1.0);
+                (factor * y, factor * x)
+            }
+            Variation::Exponential => {
+                let factor = (x - 1.0).exp();
+                let (sin, cos) = (PI * y).sin_cos();
+                (factor * cos, factor * sin)
+            }
+            Variation::Power => {
+                let factor = r.powf(sin_theta);
+                (factor * cos_theta, factor * sin_theta)
+            }
+            Variation::Cosine => {
+                let (sin, cos) = (PI * x).sin_cos();
+                (cos * y.cosh(), -sin * y.sinh())
+            }
+            Variation::Bubble => {
+                let factor = 4.0 / (r2 + 4.0);
+                (factor * x, factor * y)
+            }
+            Variation::Cylinder => (x.sin(), y),
+            Variation::Eyefish => {
+                let factor = 2.0 / (r + 1.0);
+                (factor * x, factor * y)
+            }
+            Variation::Tangent => (x.sin() / y.cos(), y.tan()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlameError {
+    Xml { line: usize, message: String },
+    MissingFlame(String),
+    NoTransforms,
+    Invalid(String, String),
+    UnsupportedVariation(String),
+    InvalidPalette(String),
+    Transform(String),
+}
+
+impl fmt::Display for FlameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlameError::Xml { line, message } => write!(f, "line {}: {}", line, message),
+            FlameError::MissingFlame(name) if name.is_empty() => {
+                write!(f, "the file contains no flame")
+            }
+            FlameError::MissingFlame(name) => write!(f, "no flame '{}' in the file", name),
+            FlameError::NoTransforms => write!(f, "the flame has no transforms"),
+            FlameError::Invalid(name, value) => {
+                write!(f, "invalid value '{}' for '{}'", value, name)
+            }
+            FlameError::UnsupportedVariation(name) => {
+                write!(f, "the variation '{}' is not supported", name)
+            }
+            FlameError::InvalidPalette(reason) => write!(f, "invalid palette: {}", reason),
+            FlameError::Transform(err) => write!(f, "invalid transform: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for FlameError {}
+
+impl From<IfsError> for FlameError {
+    fn from(err: IfsError) -> FlameError {
+        FlameError::Transform(err.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    // Applied before the variations. Its weight is the weight of the transform
+    pub affine: AffineMap,
+    pub variations: Vec<(Variation, f64)>,
+    // Applied after the variations
+    pub post: Option<AffineMap>,
+    // Position of the transform in the palette, between 0 and 1
+    pub color: f64,
+    // How far the colour of a point moves towards `color` when the transform is applied
+    pub color_speed: f64,
+}
+
+impl Transform {
+    pub fn new(affine: AffineMap, variations: Vec<(Variation, f64)>, color: f64) -> Transform {
+        Transform {
+            affine,
+            variations,
+            post: None,
+            color,
+            color_speed: 0.5,
+        }
+    }
+
+    pub fn apply(&self, point: (f64, f64), random: &mut Random) -> (f64, f64) {
+        let point = self.affine.apply(point);
+        let mut sum = (0.0, 0.0);
+        for (variation, weight) in &self.variations {
+            let (x, y) = variation.apply(point, random);
+            sum = (sum.0 + weight * x, sum.1 + weight * y);
+        }
+        match &self.post {
+            Some(post) => post.apply(sum),
+            None => sum,
+        }
+    }
+
+    fn blend_color(&self, color: f64) -> f64 {
+        color + (self.color - color) * self.color_speed
+    }
+}
+
+// Part of the plane shown by a flame, in the orientation of its images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub centre: (f64, f64),
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flame {
+    pub name: String,
+    pub transforms: Vec<Transform>,
+    // Applied to plotted points only, not to the orbit
+    pub final_transform: Option<Transform>,
+    pub palette: Vec<(u8, u8, u8)>,
+    // The attractor is fitted to the screen if there is no camera
+    pub camera: Option<Camera>,
+    pub brightness: f64,
+    pub gamma: f64,
+    pub vibrancy: f64,
+    pub supersample: u32,
+    // Largest and smallest radius of the density estimation in cells of the supersampled grid,
+    // and how fast the radius shrinks with the count
+    pub estimator_radius: f64,
+    pub estimator_minimum: f64,
+    pub estimator_curve: f64,
+}
+
+impl Flame {
+    // Flame with the default tone mapping, coloured with the palette
+    pub fn new(transforms: Vec<Transform>, palette: &Palette) -> Flame {
+        Flame {
+            name: String::new(),
+            transforms,
+            final_transform: None,
+            palette: palette.sample(PALETTE_SIZE),
+            camera: None,
+            brightness: 1.0,
+            gamma: 4.0,
+            vibrancy: 1.0,
+            supersample: 1,
+            estimator_radius: 9.0,
+            estimator_minimum: 0.0,
+            estimator_curve: 0.4,
+        }
+    }
+
+    // Checks the flame and returns the transforms as a system for choosing them by weight
+    pub fn validate(&self) -> Result<Ifs, FlameError> {
+        if self.transforms.is_empty() {
+            return Err(FlameError::NoTransforms);
+        }
+        if self.palette.is_empty() {
+            return Err(FlameError::InvalidPalette("no colours".to_string()));
+        }
+        let invalid =
+            |name: &str, value: f64| Err(FlameError::Invalid(name.to_string(), value.to_string()));
+        if !(self.gamma.is_finite() && self.gamma > 0.0) {
+            return invalid("gamma", self.gamma);
+        }
+        if !(self.brightness.is_finite() && self.brightness > 0.0) {
+            return invalid("brightness", self.brightness);
+        }
+        if !(0.0..=1.0).contains(&self.vibrancy) {
+            return invalid("vibrancy", self.vibrancy);
+        }
+        if !(1..=MAX_SUPERSAMPLE).contains(&self.supersample) {
+            return invalid("supersample", self.supersample as f64);
+        }
+        if !(0.0..=MAX_ESTIMATOR_RADIUS).contains(&self.estimator_radius) {
+            return invalid("estimator_radius", self.estimator_radius);
+        }
+        if !(0.0..=MAX_ESTIMATOR_RADIUS).contains(&self.estimator_minimum) {
+            return invalid("estimator_minimum", self.estimator_minimum);
+        }
+        // A negative curve would grow the radius with the count
+        if !(self.estimator_curve.is_finite() && self.estimator_curve >= 0.0) {
+            return invalid("estimator_curve", self.estimator_curve);
+        }
+        Ok(Ifs::new(
+            self.transforms.iter().map(|t| t.affine).collect(),
+        )?)
+    }
+
+    // Parses the first flame of a `.flame` file, or the one with the given name
+    pub fn parse(xml: &str, name: &str) -> Result<Flame, FlameError> {
+        let document = roxmltree::Document::parse(xml).map_err(|err| FlameError::Xml {
+            line: err.pos().row as usize,
+            message: err.to_string(),
+        })?;
+        let node = document
+            .descendants()
+            .filter(|node| node.has_tag_name("flame"))
+            .find(|node| name.is_empty() || node.attribute("name") == Some(name))
+            .ok_or_else(|| FlameError::MissingFlame(name.to_string()))?;
+        let mut flame = Flame::from_element(&Element(node))?;
+        for node in node.descendants().skip(1).filter(|node| node.is_element()) {
+            let element = Element(node);
+            match node.tag_name().name() {
+                "xform" => flame.transforms.push(parse_transform(&element)?),
+                "finalxform" => flame.final_transform = Some(parse_transform(&element)?),
+                "color" => {
+                    let index: usize = element.number("index", 0.0)? as usize;
+                    let rgb = element.numbers("rgb")?;
+                    // An earlier `palette` element may have fewer colours
+                    if index >= flame.palette.len() || rgb.len() != 3 {
+                        return Err(FlameError::InvalidPalette(format!(
+                            "invalid colour {}",
+                            index
+                        )));
+                    }
+                    let channel = |value: f64| value.clamp(0.0, 255.0) as u8;
+                    flame.palette[index] = (channel(rgb[0]), channel(rgb[1]), channel(rgb[2]));
+                }
+                "palette" => flame.palette = parse_hex_palette(element.text())?,
+                _ => {}
+            }
+        }
+        flame.validate()?;
+        Ok(flame)
+    }
+
+    fn from_element(element: &Element) -> Result<Flame, FlameError> {
+        let mut flame = Flame::new(Vec::new(), &Palette::default());
+        flame.name = element.attribute("name").unwrap_or("").to_string();
+        // flam3 scales brightness so that 4 is the usual value
+        flame.brightness = element.number("brightness", 4.0)? / 4.0;
+        flame.gamma = element.number("gamma", flame.gamma)?;
+        flame.vibrancy = element.number("vibrancy", flame.vibrancy)?;
+        let supersample = element.number("supersample", element.number("oversample", 1.0)?)?;
+        flame.supersample = (supersample.round() as u32).clamp(1, MAX_SUPERSAMPLE);
+        flame.estimator_radius = element.number("estimator_radius", flame.estimator_radius)?;
+        flame.estimator_minimum = element.number("estimator_minimum", flame.estimator_minimum)?;
+        flame.estimator_curve = element.number("estimator_curve", flame.estimator_curve)?;
+        if element.attribute("size").is_some() {
+            let size = element.numbers("size")?;
+            let centre = element.numbers("center")?;
+            if size.len() != 2 {
+                return Err(FlameError::Invalid("size".to_string(), size_text(&size)));
+            }
+            if centre.len() != 2 {
+                return Err(FlameError::Invalid(
+                    "center".to_string(),
+                    size_text(&centre),
+                ));
+            }
+            let scale = element.number("scale", 1.0)? * 2f64.powf(element.number("zoom", 0.0)?);
+            if !(scale.is_finite() && scale > 0.0) {
+                return Err(FlameError::Invalid("scale".to_string(), scale.to_string()));
+            }
+            flame.camera = Some(Camera {
+                centre: (centre[0], centre[1]),
+                width: size[0] / scale,
+                height: size[1] / scale,
+            });
+        }
+        Ok(flame)
+    }
+
+    // Plays a fixed chaos game and returns the range of x and y of most of its points
+    fn bounds(&self, choice: &Ifs) -> ((f64, f64), (f64, f64)) {
+        let mut random = Random(0);
+        let mut game = Game::new(&mut random);
+        let mut points = Vec::with_capacity(BOUNDS_POINTS);
+        for idx in 0..FUSE + BOUNDS_POINTS {
+            if let Some((point, _)) = game.step(self, choice, &mut random) {
+                if idx >= FUSE {
+                    points.push(point);
+                }
+            }
+        }
+        let range = |mut values: Vec<f64>| -> (f64, f64) {
+            if values.is_empty() {
+                return (-1.0, 1.0);
+            }
+            values.sort_by(|a, b| a.total_cmp(b));
+            let cut = (values.len() as f64 * BOUNDS_QUANTILE) as usize;
+            (values[cut], values[values.len() - 1 - cut])
+        };
+        (
+            range(points.iter().map(|p| p.0).collect()),
+            range(points.iter().map(|p| p.1).collect()),
+        )
+    }
+
+    // Position of a `width` x `height` screen of a `Universe` that shows the camera of the
+    // flame, or its whole attractor if it has none
+    pub fn fitting_position(&self, width: u32, height: u32) -> Result<Position, FlameError> {
+        let camera = match self.camera {
+            Some(camera) => camera,
+            None => {
+                let bounds = self.bounds(&self.validate()?);
+                return Ok(density::fitting_position(bounds, to_complex, width, height));
+            }
+        };
+        let zoom_factor = Position::zoom_factor_fitting(camera.height, camera.width, width, height);
+        Ok(Position::from_centre(
+            to_complex(camera.centre),
+            zoom_factor,
+            width,
+            height,
+        ))
+    }
+}
+
+// Point of the complex plane at which a point of a flame is shown. `y` grows downwards along the
+// rows like the real axis
+pub fn to_complex((x, y): (f64, f64)) -> Complex<f64> {
+    Complex::new(y, x)
+}
+
+// State of one run of the chaos game
+struct Game {
+    point: (f64, f64),
+    color: f64,
+}
+
+impl Game {
+    fn new(random: &mut Random) -> Game {
+        Game {
+            point: (2.0 * random.next_f64() - 1.0, 2.0 * random.next_f64() - 1.0),
+            color: random.next_f64(),
+        }
+    }
+
+    // Applies a transform chosen by weight and returns the point and colour to plot, None if the
+    // point left the plane and the game starts over
+    fn step(
+        &mut self,
+        flame: &Flame,
+        choice: &Ifs,
+        random: &mut Random,
+    ) -> Option<((f64, f64), f64)> {
+        let transform = &flame.transforms[choice.choose(random.next_f64())];
+        self.point = transform.apply(self.point, random);
+        self.color = transform.blend_color(self.color);
+        if !(self.point.0.is_finite() && self.point.1.is_finite()) {
+            *self = Game::new(random);
+            return None;
+        }
+        match &flame.final_transform {
+            Some(last) => {
+                let point = last.apply(self.point, random);
+                Some((point, last.blend_color(self.color)))
+            }
+            None => Some((self.point, self.color)),
+        }
+    }
+}
+
+// Density renderer of a flame. Every cell of the supersampled grid sums the red, green and blue
+// of the colours plotted on it and their count
+#[derive(Debug, Clone)]
+pub struct FlameRenderer {
+    screen: Screen,
+    flame: Flame,
+    choice: Ifs,
+    // Screen of the supersampled grid
+    grid: Screen,
+    histogram: Vec<[f32; 4]>,
+    samples: u64,
+}
+
+impl FlameRenderer {
+    pub fn new(screen: Screen, flame: Flame) -> Result<FlameRenderer, FlameError> {
+        let choice = flame.validate()?;
+        let factor = flame.supersample;
+        let position = &screen.position;
+        let grid = Screen::new(
+            Position::new(
+                position.get_x() * factor as i64,
+                position.get_y() * factor as i64,
+                position.get_zoom_factor(),
+            ),
+            screen.width * factor,
+            screen.height * factor,
+        );
+        let cells = grid.pixels();
+        Ok(FlameRenderer {
+            screen,
+            flame,
+            choice,
+            grid,
+            histogram: vec![[0.0; 4]; cells],
+            samples: 0,
+        })
+    }
+
+    pub fn flame(&self) -> &Flame {
+        &self.flame
+    }
+
+    // Sums of red, green, blue and count of the supersampled grid
+    pub fn histogram(&self) -> &[[f32; 4]] {
+        &self.histogram
+    }
+
+    // Spreads every cell of the histogram over a disc whose radius shrinks with its count. Every
+    // cell of the estimate gathers the discs covering it, in rows in parallel on the current
+    // rayon thread pool
+    fn estimate_density(&self) -> Option<Vec<[f32; 4]>> {
+        let flame = &self.flame;
+        if flame.estimator_radius < 1.0 {
+            return None;
+        }
+        let (width, height) = (self.grid.width as i64, self.grid.height as i64);
+        // Radius of the disc of every cell and the inverse of the sum of its kernel weights,
+        // which normalises the kernel over the cells it covers
+        let discs: Vec<(f64, f64)> = self
+            .histogram
+            .par_iter()
+            .map(|cell| {
+                if cell[3] <= 0.0 {
+                    return (0.0, 0.0);
+                }
+                let radius = (flame.estimator_radius
+                    / (cell[3] as f64 + 1.0).powf(flame.estimator_curve))
+                .max(flame.estimator_minimum);
+                let extent = radius.floor() as i64;
+                if extent < 1 {
+                    return (radius, 1.0);
+                }
+                let mut total = 0.0;
+                for dr in -extent..=extent {
+                    for dc in -extent..=extent {
+                        total += kernel(dr, dc, radius).max(0.0);
+                    }
+                }
+                (radius, 1.0 / total)
+            })
+            .collect();
+        let extent = discs
+            .iter()
+            .map(|(radius, _)| radius.floor() as i64)
+            .max()
+            .unwrap_or(0);
+        let mut estimate = vec![[0.0f32; 4]; self.histogram.len()];
+        estimate
+            .par_chunks_mut(width as usize)
+            .enumerate()
+            .for_each(|(row, cells)| {
+                let row = row as i64;
+                for (column, sum) in cells.iter_mut().enumerate() {
+                    let column = column as i64;
+                    for r in (row - extent).max(0)..=(row + extent).min(height - 1) {
+                        for c in (column - extent).max(0)..=(column + extent).min(width - 1) {
+                            let idx = (r * width + c) as usize;
+                            let (radius, normalisation) = discs[idx];
+                            // Discs smaller than a cell stay on their own cell
+                            let weight = if radius < 1.0 {
+                                if r == row && c == column {
+                                    1.0
+                                } else {
+                                    0.0
+                                }
+                            } else {
+                                kernel(r - row, c - column, radius) * normalisation
+                            };
+                            if weight > 0.0 {
+                                add_cell(sum, &self.histogram[idx], weight as f32);
+                            }
+                        }
+                    }
+                }
+            });
+        Some(estimate)
+    }
+
+    // Sums the cells of the grid covered by every pixel of the screen
+    fn downsample(&self, grid: &[[f32; 4]]) -> Vec<[f32; 4]> {
+        let factor = self.flame.supersample as usize;
+        let width = self.screen.width as usize;
+        let grid_width = self.grid.width as usize;
+        (0..self.screen.pixels())
+            .into_par_iter()
+            .map(|idx| {
+                let (row, column) = (idx / width, idx % width);
+                let mut sum = [0.0; 4];
+                for r in row * factor..(row + 1) * factor {
+                    for c in column * factor..(column + 1) * factor {
+                        add_cell(&mut sum, &grid[r * grid_width + c], 1.0);
+                    }
+                }
+                sum
+            })
+            .collect()
+    }
+
+    // Colour of a pixel with the sums of its plotted colours, given the largest count of all
+    // pixels
+    fn tone_map(&self, cell: &[f32; 4], max: f32) -> (u8, u8, u8) {
+        let count = cell[3];
+        if count <= 0.0 {
+            return (0, 0, 0);
+        }
+        let flame = &self.flame;
+        let brightness = density::log_density(count, max) * flame.brightness;
+        let alpha = brightness.powf(1.0 / flame.gamma);
+        let channel = |sum: f32| -> u8 {
+            let color = (sum / count) as f64;
+            let value = flame.vibrancy * alpha * color
+                + (1.0 - flame.vibrancy) * (color * brightness).powf(1.0 / flame.gamma);
+            (value.clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+        (channel(cell[0]), channel(cell[1]), channel(cell[2]))
+    }
+}
+
+// Epanechnikov kernel of a disc at an offset of rows and columns from its centre, positive
+// within the disc
+fn kernel(dr: i64, dc: i64, radius: f64) -> f64 {
+    1.0 - ((dr * dr + dc * dc) as f64) / (radius * radius)
+}
+
+fn add_cell(sum: &mut [f32; 4], cell: &[f32; 4], weight: f32) {
+    for (sum, value) in sum.iter_mut().zip(cell) {
+        *sum += value * weight;
+    }
+}
+
+impl DensityRenderer for FlameRenderer {
+    fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    fn restart(
+        &self,
+        screen: Screen,
+    ) -> Result<Box<dyn DensityRenderer>, Box<dyn Error + Send + Sync>> {
+        Ok(Box::new(FlameRenderer::new(screen, self.flame.clone())?))
+    }
+
+    // Every sample is a plotted point, every task plays its own game from a random start
+    fn accumulate(&mut self, samples: u64) {
+        let cells = self.histogram.len();
+        let (flame, choice, grid) = (&self.flame, &self.choice, &self.grid);
+        let palette_size = flame.palette.len();
+        density::sample_parallel(
+            &mut self.histogram,
+            self.samples..self.samples + samples,
+            POINTS_PER_TASK,
+            || vec![[0.0f32; 4]; cells],
+            |a: &mut Vec<[f32; 4]>, b: &Vec<[f32; 4]>| {
+                a.iter_mut().zip(b).for_each(|(a, b)| add_cell(a, b, 1.0))
+            },
+            |mut random| {
+                let mut game = Game::new(&mut random);
+                for _ in 0..FUSE {
+                    game.step(flame, choice, &mut random);
+                }
+                move |histogram: &mut Vec<[f32; 4]>| {
+                    let (point, color) = match game.step(flame, choice, &mut random) {
+                        Some(plot) => plot,
+                        None => return,
+                    };
+                    if let Some(idx) = grid.pixel(to_complex(point)) {
+                        let entry =
+                            (color.clamp(0.0, 1.0) * (palette_size - 1) as f64).round() as usize;
+                        let (r, g, b) = flame.palette[entry];
+                        let cell = &mut histogram[idx];
+                        cell[0] += r as f32 / 255.0;
+                        cell[1] += g as f32 / 255.0;
+                        cell[2] += b as f32 / 255.0;
+                        cell[3] += 1.0;
+                    }
+                }
+            },
+        );
+        self.samples += samples;
+    }
+
+    fn to_cells(&self) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let estimate = self.estimate_density();
+        let pixels = self.downsample(estimate.as_ref().unwrap_or(&self.histogram));
+        let max = pixels.iter().map(|cell| cell[3]).fold(0.0f32, f32::max);
+        let rgb: Vec<(u8, u8, u8)> = pixels
+            .par_iter()
+            .map(|cell| self.tone_map(cell, max))
+            .collect();
+        (
+            rgb.iter().map(|c| c.0).collect(),
+            rgb.iter().map(|c| c.1).collect(),
+            rgb.iter().map(|c| c.2).collect(),
+        )
+    }
+
+    fn samples(&self) -> u64 {
+        self.samples
+    }
+}
+
+fn size_text(values: &[f64]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Reads a transform from the attributes of an `xform` or `finalxform` element. `coefs` are
+// written column by column as `xx yx xy yy ox oy`, mapping (x, y) to
+// (xx x + xy y + ox, yx x + yy y + oy)
+fn parse_transform(element: &Element) -> Result<Transform, FlameError> {
+    let affine = |name: &str| -> Result<Option<AffineMap>, FlameError> {
+        if element.attribute(name).is_none() {
+            return Ok(None);
+        }
+        let c = element.numbers(name)?;
+        if c.len() != 6 {
+            return Err(FlameError::Invalid(name.to_string(), size_text(&c)));
+        }
+        Ok(Some(AffineMap::new(c[0], c[2], c[1], c[3], c[4], c[5])))
+    };
+    let weight = element.number("weight", 1.0)?;
+    let coefficients = affine("coefs")?
+        .unwrap_or_else(|| AffineMap::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0))
+        .with_weight(weight);
+    let mut variations = Vec::new();
+    for attribute in element.0.attributes() {
+        let (name, value) = (attribute.name(), attribute.value());
+        let known = [
+            "weight",
+            "color",
+            "symmetry",
+            "color_speed",
+            "coefs",
+            "post",
+        ];
+        if known.contains(&name) || IGNORED_XFORM_ATTRIBUTES.contains(&name) {
+            continue;
+        }
+        let amount = match value.trim().parse::<f64>() {
+            Ok(amount) => amount,
+            // Parameters of parametric variations can be anything
+            Err(_) if name.contains('_') => continue,
+            Err(_) => return Err(FlameError::Invalid(name.to_string(), value.to_string())),
+        };
+        match Variation::from_name(name) {
+            Some(variation) => variations.push((variation, amount)),
+            // Parameters of variations like `julian_power`
+            None if name.contains('_') => {}
+            None if amount == 0.0 => {}
+            None => return Err(FlameError::UnsupportedVariation(name.to_string())),
+        }
+    }
+    if variations.is_empty() {
+        variations.push((Variation::Linear, 1.0));
+    }
+    let color = element.numbers("color")?.first().cloned().unwrap_or(0.0);
+    let mut transform = Transform::new(coefficients, variations, color);
+    transform.post = affine("post")?;
+    if element.attribute("color_speed").is_some() {
+        transform.color_speed = element.number("color_speed", 0.5)?;
+    } else {
+        // Older files describe the speed by the symmetry, which keeps the colour at 1
+        transform.color_speed = (1.0 - element.number("symmetry", 0.0)?) / 2.0;
+    }
+    Ok(transform)
+}
+
+// Reads the colours of a `palette` element, written as hexadecimal RRGGBB triples
+fn parse_hex_palette(text: &str) -> Result<Vec<(u8, u8, u8)>, FlameError> {
+    let digits: Vec<u8> = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| {
+            c.to_digit(16)
+                .map(|digit| digit as u8)
+                .ok_or_else(|| FlameError::InvalidPalette(format!("invalid digit '{}'", c)))
+        })
+        .collect::<Result<Vec<u8>, FlameError>>()?;
+    if digits.is_empty() || !digits.len().is_multiple_of(6) || digits.len() / 6 > PALETTE_SIZE {
+        return Err(FlameError::InvalidPalette(format!(
+            "{} hexadecimal digits do not make up to {} colours",
+            digits.len(),
+            PALETTE_SIZE
+        )));
+    }
+    Ok(digits
+        .chunks(6)
+        .map(|c| (c[0] * 16 + c[1], c[2] * 16 + c[3], c[4] * 16 + c[5]))
+        .collect())
+}
+
+// Element of a `.flame` file with the attributes read like flam3
+#[derive(Debug, Clone, Copy)]
+struct Element<'a, 'input>(roxmltree::Node<'a, 'input>);
+
+impl<'a, 'input> Element<'a, 'input> {
+    fn attribute(&self, name: &str) -> Option<&'a str> {
+        self.0.attribute(name)
+    }
+
+    // Text directly inside of the element
+    fn text(&self) -> &'a str {
+        self.0.text().unwrap_or("")
+    }
+
+    fn number(&self, name: &str, default: f64) -> Result<f64, FlameError> {
+        match self.attribute(name) {
+            Some(value) => value
+                .trim()
+                .parse()
+                .map_err(|_| FlameError::Invalid(name.to_string(), value.to_string())),
+            None => Ok(default),
+        }
+    }
+
+    // Whitespace separated numbers, empty if the attribute is missing
+    fn numbers(&self, name: &str) -> Result<Vec<f64>, FlameError> {
+        let value = self.attribute(name).unwrap_or("");
+        value
+            .split_whitespace()
+            .map(|number| {
+                number
+                    .parse()
+                    .map_err(|_| FlameError::Invalid(name.to_string(), value.to_string()))
+            })
+            .collect()
+    }
+}
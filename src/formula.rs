@@ -0,0 +1,460 @@
+//! Iteration formulas written by the user, compiled to bytecode for a small stack machine.
+//!
+//! A formula is a list of statements separated by `;` or new lines, `#` starts a comment. Every
+//! iteration runs all statements: assignments `name = expression` define helper variables and the
+//! value of the last statement becomes the next `z`, so both `z^2 + c` and `z = z^2 + c` describe
+//! the mandelbrot set. Expressions are read by the parser of `expression`.
+//!
+//! - Variables: `z` (starts at 0), `c` and `pixel` (the point of the pixel), `n` (the number of
+//!   the iteration) and the constants `i`, `pi` and `e`
+//! - Operators: `+ - * / ^`, comparisons `< <= > >= == !=` of real parts only, so `z == c`
+//!   ignores the imaginary parts, and `&& || !`, with 1 for true and 0 for false
+//! - Functions: `sqr sqrt exp log sin cos tan sinh cosh conj abs re im arg` and `pow(a, b)`
+//! - Conditionals: `if(condition, then, else)`, only the chosen branch is evaluated
+//!
+//! A point escapes once `|z| >= 2` like in the built-in kernels. Formulas are evaluated on dual
+//! numbers, so they provide dz/dc for distance estimation without further work.
+
+use crate::dual::Dual;
+use crate::expression::{BinaryOp, Dialect, Expr, Parser, Scope, SyntaxError, Token};
+use crate::mandelbrot::{self, Escape};
+use num::complex::Complex;
+
+// Slots of the predefined variables
+const Z: usize = 0;
+const C: usize = 1;
+const PIXEL: usize = 2;
+const N: usize = 3;
+const PREDEFINED: [&str; 4] = ["z", "c", "pixel", "n"];
+const CONSTANTS: [&str; 3] = ["i", "pi", "e"];
+
+pub type FormulaError = SyntaxError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Sqr,
+    Sqrt,
+    Exp,
+    Log,
+    Sin,
+    Cos,
+    Tan,
+    Sinh,
+    Cosh,
+    Conj,
+    Abs,
+    Re,
+    Im,
+    Arg,
+}
+
+impl Function {
+    const ALL: [(&'static str, Function); 14] = [
+        ("sqr", Function::Sqr),
+        ("sqrt", Function::Sqrt),
+        ("exp", Function::Exp),
+        ("log", Function::Log),
+        ("sin", Function::Sin),
+        ("cos", Function::Cos),
+        ("tan", Function::Tan),
+        ("sinh", Function::Sinh),
+        ("cosh", Function::Cosh),
+        ("conj", Function::Conj),
+        ("abs", Function::Abs),
+        ("re", Function::Re),
+        ("im", Function::Im),
+        ("arg", Function::Arg),
+    ];
+
+    fn from_name(name: &str) -> Option<Function> {
+        Function::ALL
+            .iter()
+            .find(|(function, _)| *function == name)
+            .map(|(_, function)| *function)
+    }
+
+    fn apply(self, x: Dual) -> Dual {
+        match self {
+            Function::Sqr => x * x,
+            Function::Sqrt => x.sqrt(),
+            Function::Exp => x.exp(),
+            Function::Log => x.ln(),
+            Function::Sin => x.sin(),
+            Function::Cos => x.cos(),
+            Function::Tan => x.tan(),
+            Function::Sinh => x.sinh(),
+            Function::Cosh => x.cosh(),
+            Function::Conj => x.conj(),
+            Function::Abs => x.abs(),
+            Function::Re => x.re(),
+            Function::Im => x.im(),
+            Function::Arg => x.arg(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+// Instructions of the stack machine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Push(Complex<f64>),
+    Load(usize),
+    Store(usize),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    // Power with a constant integer exponent
+    PowI(i32),
+    Neg,
+    Not,
+    And,
+    Or,
+    Compare(Comparison),
+    Call(Function),
+    // Jumps to the instruction if the popped value is false
+    JumpIfFalse(usize),
+    Jump(usize),
+}
+
+// A compiled formula
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    source: String,
+    program: Vec<Op>,
+    variables: usize,
+    stack_size: usize,
+}
+
+impl Formula {
+    pub fn compile(source: &str) -> Result<Formula, FormulaError> {
+        // Comments are blanked out, so that columns stay those of the source
+        let code: Vec<&str> = source
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .collect();
+        let mut parser = Parser::new(&code.join("\n"), 1, Dialect::Standard)?;
+        let mut compiler = Compiler {
+            program: Vec::new(),
+            variables: PREDEFINED.iter().map(|name| name.to_string()).collect(),
+            depth: 0,
+            stack_size: 0,
+        };
+        // Position of the last expression statement
+        let mut last_expression = None;
+        let mut statements = 0;
+        loop {
+            match parser.peek() {
+                Token::End => break,
+                Token::Separator => {
+                    parser.advance();
+                    continue;
+                }
+                _ => {}
+            }
+            if let Some(position) = last_expression {
+                return Err(SyntaxError::new(
+                    position,
+                    "only the last statement can be an expression, assign it to a variable \
+                     instead",
+                ));
+            }
+            let start = parser.position();
+            statements += 1;
+            match parser.assignment() {
+                Some(name) => {
+                    if ["c", "pixel", "n", "i", "pi", "e"].contains(&name.as_str()) {
+                        let message = format!("'{}' is predefined and cannot be assigned", name);
+                        return Err(SyntaxError::new(start, message));
+                    }
+                    if compiler.arity(&name).is_some() {
+                        let message = format!("'{}' is a function and cannot be assigned", name);
+                        return Err(SyntaxError::new(start, message));
+                    }
+                    let value = parser.expression(&compiler)?;
+                    compiler.expression(&value);
+                    let slot = compiler.slot(name);
+                    compiler.emit(Op::Store(slot));
+                }
+                None => {
+                    let value = parser.expression(&compiler)?;
+                    compiler.expression(&value);
+                    compiler.emit(Op::Store(Z));
+                    last_expression = Some(start);
+                }
+            }
+            match parser.peek() {
+                Token::Separator | Token::End => {}
+                token => {
+                    let message = format!("expected end of statement, found {}", token);
+                    return parser.error(message);
+                }
+            }
+        }
+        if statements == 0 {
+            return parser.error("the formula is empty");
+        }
+        Ok(Formula {
+            source: source.to_string(),
+            program: compiler.program,
+            variables: compiler.variables.len(),
+            stack_size: compiler.stack_size,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn program(&self) -> &[Op] {
+        &self.program
+    }
+
+    // Smoothed iteration count and distance estimate of the point, None if it has not escaped
+    // within `max_iter` iterations. The distance estimate uses dz/dc, which is tracked by
+    // evaluating the formula on dual numbers
+    pub fn escape(&self, point: Complex<f64>, max_iter: u32) -> Option<Escape> {
+        let mut variables = vec![Dual::default(); self.variables];
+        variables[C] = Dual::variable(point);
+        variables[PIXEL] = Dual::variable(point);
+        let mut stack = Vec::with_capacity(self.stack_size);
+        let mut iter = 0;
+        while variables[Z].value.norm_sqr() < 4.0 && iter < max_iter {
+            variables[N] = Dual::constant(Complex::new(iter as f64, 0.0));
+            self.run(&mut variables, &mut stack);
+            iter += 1;
+        }
+        if iter == max_iter {
+            return None;
+        }
+        let norm_sqr = variables[Z].value.norm_sqr();
+        if !norm_sqr.is_finite() {
+            // NaN or overflow, the smoothing is undefined
+            return Some(Escape {
+                smoothed_iterations: iter as f64,
+                distance: f64::INFINITY,
+            });
+        }
+        Some(mandelbrot::escape_from(
+            iter,
+            norm_sqr,
+            variables[Z].derivative.norm_sqr(),
+        ))
+    }
+
+    pub fn iteration_quotient(&self, point: Complex<f64>, max_iter: u32) -> f64 {
+        match self.escape(point, max_iter) {
+            Some(escape) => escape.smoothed_iterations / max_iter as f64,
+            None => 1.0,
+        }
+    }
+
+    // Runs the statements once, updating the variables and their derivatives. Comparisons and
+    // logical operations are constant, their derivative is 0
+    pub fn run(&self, variables: &mut [Dual], stack: &mut Vec<Dual>) {
+        let truth = |value: bool| Dual::constant(Complex::new(if value { 1.0 } else { 0.0 }, 0.0));
+        let mut pc = 0;
+        while pc < self.program.len() {
+            let op = self.program[pc];
+            pc += 1;
+            match op {
+                Op::Push(value) => stack.push(Dual::constant(value)),
+                Op::Load(slot) => stack.push(variables[slot]),
+                Op::Store(slot) => variables[slot] = stack.pop().unwrap_or_default(),
+                Op::Neg => {
+                    let x = stack.pop().unwrap_or_default();
+                    stack.push(-x);
+                }
+                Op::Not => {
+                    let x = stack.pop().unwrap_or_default();
+                    stack.push(truth(x.value.re == 0.0));
+                }
+                Op::PowI(exponent) => {
+                    let x = stack.pop().unwrap_or_default();
+                    stack.push(x.powi(exponent));
+                }
+                Op::Call(function) => {
+                    let x = stack.pop().unwrap_or_default();
+                    stack.push(function.apply(x));
+                }
+                Op::JumpIfFalse(target) => {
+                    if stack.pop().unwrap_or_default().value.re == 0.0 {
+                        pc = target;
+                    }
+                }
+                Op::Jump(target) => pc = target,
+                binary => {
+                    let b = stack.pop().unwrap_or_default();
+                    let a = stack.pop().unwrap_or_default();
+                    stack.push(match binary {
+                        Op::Add => a + b,
+                        Op::Sub => a - b,
+                        Op::Mul => a * b,
+                        Op::Div => a / b,
+                        Op::Pow => a.powc(b),
+                        Op::And => truth(a.value.re != 0.0 && b.value.re != 0.0),
+                        Op::Or => truth(a.value.re != 0.0 || b.value.re != 0.0),
+                        Op::Compare(comparison) => {
+                            let (a, b) = (a.value, b.value);
+                            truth(match comparison {
+                                Comparison::Less => a.re < b.re,
+                                Comparison::LessEqual => a.re <= b.re,
+                                Comparison::Greater => a.re > b.re,
+                                Comparison::GreaterEqual => a.re >= b.re,
+                                Comparison::Equal => a.re == b.re,
+                                Comparison::NotEqual => a.re != b.re,
+                            })
+                        }
+                        _ => unreachable!("not a binary operation"),
+                    });
+                }
+            }
+        }
+    }
+}
+
+// Emits the bytecode of parsed expressions
+struct Compiler {
+    program: Vec<Op>,
+    variables: Vec<String>,
+    // Current and maximum number of values on the stack
+    depth: usize,
+    stack_size: usize,
+}
+
+impl Scope for Compiler {
+    fn is_variable(&self, name: &str) -> bool {
+        CONSTANTS.contains(&name) || self.variables.iter().any(|variable| variable == name)
+    }
+
+    fn arity(&self, name: &str) -> Option<usize> {
+        match name {
+            "pow" => Some(2),
+            "if" => Some(3),
+            _ => Function::from_name(name).map(|_| 1),
+        }
+    }
+}
+
+impl Compiler {
+    // Emits the instruction, keeping track of the size of the stack
+    fn emit(&mut self, op: Op) {
+        let popped = match op {
+            Op::Push(_) | Op::Load(_) | Op::Jump(_) => 0,
+            Op::Store(_) | Op::JumpIfFalse(_) => 1,
+            Op::Neg | Op::Not | Op::PowI(_) | Op::Call(_) => 1,
+            _ => 2,
+        };
+        let pushed = match op {
+            Op::Store(_) | Op::JumpIfFalse(_) | Op::Jump(_) => 0,
+            _ => 1,
+        };
+        self.depth = self.depth - popped + pushed;
+        self.stack_size = self.stack_size.max(self.depth);
+        self.program.push(op);
+    }
+
+    // Slot of the variable, which is added if it is new
+    fn slot(&mut self, name: String) -> usize {
+        match self.variables.iter().position(|variable| *variable == name) {
+            Some(slot) => slot,
+            None => {
+                self.variables.push(name);
+                self.variables.len() - 1
+            }
+        }
+    }
+
+    // Emits the instructions leaving the value of the expression on the stack. The parser has
+    // checked its variables and functions
+    fn expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Number(value) => self.emit(Op::Push(*value)),
+            Expr::Variable(name) => match name.as_str() {
+                "i" => self.emit(Op::Push(Complex::new(0.0, 1.0))),
+                "pi" => self.emit(Op::Push(Complex::new(std::f64::consts::PI, 0.0))),
+                "e" => self.emit(Op::Push(Complex::new(std::f64::consts::E, 0.0))),
+                _ => {
+                    let slot = self.slot(name.clone());
+                    self.emit(Op::Load(slot));
+                }
+            },
+            Expr::Negate(value) => {
+                self.expression(value);
+                self.emit(Op::Neg);
+            }
+            Expr::Not(value) => {
+                self.expression(value);
+                self.emit(Op::Not);
+            }
+            Expr::Binary(BinaryOp::Pow, base, exponent) => self.power(base, exponent),
+            Expr::Binary(op, left, right) => {
+                self.expression(left);
+                self.expression(right);
+                self.emit(match op {
+                    BinaryOp::Add => Op::Add,
+                    BinaryOp::Sub => Op::Sub,
+                    BinaryOp::Mul => Op::Mul,
+                    BinaryOp::Div => Op::Div,
+                    BinaryOp::Pow => Op::Pow,
+                    BinaryOp::Less => Op::Compare(Comparison::Less),
+                    BinaryOp::LessEqual => Op::Compare(Comparison::LessEqual),
+                    BinaryOp::Greater => Op::Compare(Comparison::Greater),
+                    BinaryOp::GreaterEqual => Op::Compare(Comparison::GreaterEqual),
+                    BinaryOp::Equal => Op::Compare(Comparison::Equal),
+                    BinaryOp::NotEqual => Op::Compare(Comparison::NotEqual),
+                    BinaryOp::And => Op::And,
+                    BinaryOp::Or => Op::Or,
+                });
+            }
+            Expr::Call(name, arguments) => match (name.as_str(), arguments.as_slice()) {
+                ("if", [condition, then, otherwise]) => {
+                    // Only the chosen branch is evaluated
+                    self.expression(condition);
+                    let jump_to_else = self.program.len();
+                    self.emit(Op::JumpIfFalse(0));
+                    self.expression(then);
+                    let jump_to_end = self.program.len();
+                    self.emit(Op::Jump(0));
+                    // Only one of the branches leaves its value on the stack
+                    self.depth -= 1;
+                    self.program[jump_to_else] = Op::JumpIfFalse(self.program.len());
+                    self.expression(otherwise);
+                    self.program[jump_to_end] = Op::Jump(self.program.len());
+                }
+                ("pow", [base, exponent]) => self.power(base, exponent),
+                (_, [argument]) => {
+                    self.expression(argument);
+                    let function = Function::from_name(name).expect("the parser checks functions");
+                    self.emit(Op::Call(function));
+                }
+                _ => unreachable!("the parser checks the number of arguments"),
+            },
+            Expr::Modulus(_) => unreachable!("only Fractint formulas have a modulus"),
+        }
+    }
+
+    // Uses the faster integer power for constant integer exponents
+    fn power(&mut self, base: &Expr, exponent: &Expr) {
+        self.expression(base);
+        if let Expr::Number(exponent) = exponent {
+            let integral = exponent.im == 0.0 && exponent.re.fract() == 0.0;
+            if integral && exponent.re.abs() <= i32::MAX as f64 {
+                self.emit(Op::PowI(exponent.re as i32));
+                return;
+            }
+        }
+        self.expression(exponent);
+        self.emit(Op::Pow);
+    }
+}
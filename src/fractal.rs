@@ -0,0 +1,150 @@
+use crate::formula::Formula;
+use crate::julia;
+use crate::lyapunov::Lyapunov;
+use crate::mandelbrot;
+use crate::newton::Newton;
+use crate::palette::Palette;
+use num::complex::Complex;
+use std::sync::Arc;
+
+// Prefixes of the specifications of fractals with parameters
+const FORMULA_PREFIX: &str = "formula:";
+const NEWTON_PREFIX: &str = "newton:";
+const LYAPUNOV_PREFIX: &str = "lyapunov:";
+const JULIA_PREFIX: &str = "julia:";
+
+// The fractals that can be rendered by the escape time kernels
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fractal {
+    Mandelbrot,
+    // Iteration formula written by the user, see `formula`
+    Formula(Arc<Formula>),
+    // Basins of the roots of a polynomial under Newton's method, see `newton`
+    Newton(Arc<Newton>),
+    // Stability of the logistic map with rates alternating between a and b, see `lyapunov`
+    Lyapunov(Arc<Lyapunov>),
+    // Filled Julia set of z^2 + c, see `julia`
+    Julia(Complex<f64>),
+}
+
+impl Fractal {
+    pub const ALL: [Fractal; 1] = [Fractal::Mandelbrot];
+
+    // Name used in URLs, files and the JavaScript API
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fractal::Mandelbrot => "mandelbrot",
+            Fractal::Formula(_) => "formula",
+            Fractal::Newton(_) => "newton",
+            Fractal::Lyapunov(_) => "lyapunov",
+            Fractal::Julia(_) => "julia",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Fractal> {
+        Fractal::ALL.iter().find(|fractal| fractal.name() == name).cloned()
+    }
+
+    // Text that describes the fractal completely, the name for built-in fractals,
+    // `formula:` followed by the source for formulas, `newton:` followed by the parameters
+    // for Newton fractals, `lyapunov:` followed by the sequence for Lyapunov fractals and `julia:`
+    // followed by the real and imaginary part of c for Julia sets
+    pub fn spec(&self) -> String {
+        match self {
+            Fractal::Julia(c) => format!("{}{},{}", JULIA_PREFIX, c.re, c.im),
+            Fractal::Formula(formula) => format!("{}{}", FORMULA_PREFIX, formula.source()),
+            Fractal::Newton(newton) => format!("{}{}", NEWTON_PREFIX, newton.to_spec()),
+            Fractal::Lyapunov(lyapunov) => format!("{}{}", LYAPUNOV_PREFIX, lyapunov.sequence()),
+            fractal => fractal.name().to_string(),
+        }
+    }
+
+    // Parses a specification returned by `spec`. The names `newton`, `lyapunov` and `julia` alone
+    // select the Newton fractal of z^3 - 1, the Lyapunov fractal of the sequence AB and the Julia
+    // set of `julia::DEFAULT_PARAMETER`
+    pub fn from_spec(spec: &str) -> Option<Fractal> {
+        if let Some(parameter) = spec.strip_prefix(JULIA_PREFIX) {
+            let (re, im) = parameter.split_once(',')?;
+            let c = Complex::new(re.trim().parse().ok()?, im.trim().parse().ok()?);
+            return julia::is_valid_parameter(c).then_some(Fractal::Julia(c));
+        }
+        if let Some(source) = spec.strip_prefix(FORMULA_PREFIX) {
+            let formula = Formula::compile(source).ok()?;
+            return Some(Fractal::Formula(Arc::new(formula)));
+        }
+        if let Some(parameters) = spec.strip_prefix(NEWTON_PREFIX) {
+            let newton = Newton::parse(parameters).ok()?;
+            return Some(Fractal::Newton(Arc::new(newton)));
+        }
+        if let Some(sequence) = spec.strip_prefix(LYAPUNOV_PREFIX) {
+            let lyapunov = Lyapunov::new(sequence).ok()?;
+            return Some(Fractal::Lyapunov(Arc::new(lyapunov)));
+        }
+        match spec {
+            "newton" => Some(Fractal::Newton(Arc::new(Newton::default()))),
+            "lyapunov" => Some(Fractal::Lyapunov(Arc::new(Lyapunov::default()))),
+            "julia" => Some(Fractal::Julia(julia::DEFAULT_PARAMETER)),
+            _ => Fractal::from_name(spec),
+        }
+    }
+
+    // Smoothed iteration count and distance estimate of the point, None if it belongs to the set
+    pub fn escape(&self, point: Complex<f64>, max_iter: u32) -> Option<mandelbrot::Escape> {
+        match self {
+            Fractal::Mandelbrot => mandelbrot::mandelbrot_escape(point, max_iter),
+            Fractal::Formula(formula) => formula.escape(point, max_iter),
+            Fractal::Newton(newton) => newton.escape(point, max_iter),
+            Fractal::Lyapunov(lyapunov) => lyapunov.escape(point, max_iter),
+            Fractal::Julia(c) => mandelbrot::julia_escape(point, *c, max_iter),
+        }
+    }
+
+    // Smoothed iteration count of the point relative to `max_iter`, see
+    // `mandelbrot::mandelbrot_iteration_quotient`
+    pub fn iteration_quotient(&self, point: Complex<f64>, max_iter: u32) -> f64 {
+        match self {
+            Fractal::Mandelbrot => mandelbrot::mandelbrot_iteration_quotient(point, max_iter),
+            Fractal::Formula(formula) => formula.iteration_quotient(point, max_iter),
+            Fractal::Newton(newton) => newton.iteration_quotient(point, max_iter),
+            Fractal::Lyapunov(lyapunov) => lyapunov.iteration_quotient(point, max_iter),
+            Fractal::Julia(c) => mandelbrot::julia_iteration_quotient(point, *c, max_iter),
+        }
+    }
+}
+
+// Everything besides the position that determines the colour of a pixel
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderSettings {
+    pub fractal: Fractal,
+    pub max_iterations: u32,
+    pub palette: Palette,
+}
+
+impl RenderSettings {
+    pub fn new(fractal: Fractal, max_iterations: u32, palette: Palette) -> RenderSettings {
+        RenderSettings {
+            fractal,
+            max_iterations,
+            palette,
+        }
+    }
+
+    pub fn rgb_value_at(&self, point: Complex<f64>) -> (u8, u8, u8) {
+        if let Fractal::Newton(newton) = &self.fractal {
+            // Coloured by basin rather than by iteration count
+            return newton.rgb_value(point, self.max_iterations, &self.palette);
+        }
+        let quotient = self.fractal.iteration_quotient(point, self.max_iterations);
+        self.palette.rgb_value(quotient)
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings::new(
+            Fractal::Mandelbrot,
+            mandelbrot::DEFAULT_MAX_ITERATIONS,
+            Palette::default(),
+        )
+    }
+}
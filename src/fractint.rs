@@ -0,0 +1,709 @@
+//! Import of Fractint parameter (`.par`) and formula (`.frm`) files.
+//!
+//! A parameter file contains named entries of whitespace separated `key=value` pairs:
+//!
+//! ```text
+//! Seahorse { ; comment
+//!   reset=2004 type=mandel corners=-0.76/-0.73/0.09/0.12
+//!   maxiter=500 inside=0 colors=000<30>0F0<30>FF0
+//!   }
+//! ```
+//!
+//! The location (`corners` or `center-mag`), `maxiter` and the colour table are interpreted, the
+//! colour table is approximated by the closest `Palette`. Entries of `type=formula` refer to a
+//! formula of a formula file by `formulaname`, written like
+//!
+//! ```text
+//! Mandel { ; init : iteration, bailout
+//!   z = 0, c = pixel:
+//!   z = sqr(z) + c
+//!   |z| <= 4
+//!   }
+//! ```
+//!
+//! Formulas are parsed into expressions by the parser of `expression` and compared to the
+//! iterations of the fractal types of this crate. Formulas that compute anything else, as well as keys of parameter entries that
+//! would change the image, are reported as unsupported.
+
+use crate::export::ViewMetadata;
+use crate::expression::{Dialect, Parser, Scope, SyntaxError, Token};
+use crate::fractal::{Fractal, RenderSettings};
+use crate::palette::Palette;
+use crate::viewport::Viewport;
+use num::complex::Complex;
+use std::collections::HashMap;
+use std::fmt;
+
+// Iteration limit of entries without `maxiter`
+pub const DEFAULT_MAXITER: u32 = 150;
+
+// Escape condition `|z| <= BAILOUT` used by the escape time kernels, `|z|` being the squared
+// modulus in Fractint
+const BAILOUT: f64 = 4.0;
+
+// Keys of parameter entries that change the image in ways that cannot be reproduced
+const UNSUPPORTED_KEYS: [&str; 7] = [
+    "decomp",
+    "biomorph",
+    "potential",
+    "logmap",
+    "distest",
+    "invert",
+    "finattract",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FractintError {
+    Syntax { line: usize, message: String },
+    Missing(&'static str),
+    Invalid(&'static str, String),
+    UnknownFormula(String),
+    Unsupported(String),
+}
+
+impl fmt::Display for FractintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FractintError::Syntax { line, message } => {
+                write!(f, "syntax error in line {}: {}", line, message)
+            }
+            FractintError::Missing(key) => write!(f, "missing '{}'", key),
+            FractintError::Invalid(key, value) => {
+                write!(f, "invalid value '{}' for '{}'", value, key)
+            }
+            FractintError::UnknownFormula(name) => write!(f, "unknown formula '{}'", name),
+            FractintError::Unsupported(what) => write!(f, "unsupported {}", what),
+        }
+    }
+}
+
+impl std::error::Error for FractintError {}
+
+impl From<SyntaxError> for FractintError {
+    fn from(err: SyntaxError) -> FractintError {
+        FractintError::Syntax {
+            line: err.line,
+            message: err.message,
+        }
+    }
+}
+
+fn syntax_error<T>(line: usize, message: impl Into<String>) -> Result<T, FractintError> {
+    Err(FractintError::Syntax {
+        line,
+        message: message.into(),
+    })
+}
+
+// Named block `name { ... }` of a parameter or formula file, with comments removed
+struct Block {
+    name: String,
+    line: usize,
+    body: String,
+}
+
+// Splits a file into its blocks. Text between blocks is ignored, Fractint files commonly start
+// with free form comments
+fn blocks(text: &str) -> Result<Vec<Block>, FractintError> {
+    let mut blocks = Vec::new();
+    let mut current: Option<Block> = None;
+    let mut header = String::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line_number = idx + 1;
+        let line = line.split(';').next().unwrap_or("");
+        match current.as_mut() {
+            None => match line.split_once('{') {
+                Some((name, rest)) => {
+                    header.push_str(name);
+                    let name = header.trim().to_string();
+                    header.clear();
+                    if name.is_empty() {
+                        return syntax_error(line_number, "block without a name");
+                    }
+                    let block = Block {
+                        name,
+                        line: line_number,
+                        body: String::new(),
+                    };
+                    current = Some(block);
+                    if let Some(block) = close_block(&mut current, rest) {
+                        blocks.push(block);
+                    }
+                }
+                None => header = line.to_string(),
+            },
+            Some(_) => {
+                if let Some(block) = close_block(&mut current, line) {
+                    blocks.push(block);
+                }
+            }
+        }
+    }
+    match current {
+        Some(block) => syntax_error(block.line, format!("'{}' is not closed", block.name)),
+        None => Ok(blocks),
+    }
+}
+
+// Appends the line to the open block and returns the block if the line closes it
+fn close_block(current: &mut Option<Block>, line: &str) -> Option<Block> {
+    let block = current.as_mut()?;
+    match line.split_once('}') {
+        Some((rest, _)) => {
+            block.body.push_str(rest);
+            current.take()
+        }
+        None => {
+            block.body.push_str(line);
+            block.body.push('\n');
+            None
+        }
+    }
+}
+
+pub use crate::expression::{BinaryOp, Expr};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Assign(String, Expr),
+    Condition(Expr),
+}
+
+// Formula of a formula file. The last statement of `iteration` is the bailout condition
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    pub name: String,
+    pub init: Vec<Statement>,
+    pub iteration: Vec<Statement>,
+}
+
+// Functions of formulas with a single argument, calls of others are unsupported
+const FUNCTIONS: [&str; 15] = [
+    "sqr", "sqrt", "abs", "cabs", "conj", "real", "imag", "flip", "exp", "log", "sin", "cos",
+    "tan", "sinh", "cosh",
+];
+
+// Formulas may read any variable and call functions with a single argument. Which of them are
+// supported is only known when the formula is compared to the fractal types
+struct FormulaScope;
+
+impl Scope for FormulaScope {
+    fn is_variable(&self, _name: &str) -> bool {
+        true
+    }
+
+    fn arity(&self, _name: &str) -> Option<usize> {
+        Some(1)
+    }
+}
+
+// Statements of a formula body, split at the ':' separating the initialisation. Statements end
+// at new lines and ','
+fn parse_formula(name: &str, body: &str, first_line: usize) -> Result<Formula, FractintError> {
+    // Names are case insensitive
+    let mut parser = Parser::new(&body.to_lowercase(), first_line, Dialect::Fractint)?;
+    let mut init = Vec::new();
+    let mut iteration = Vec::new();
+    let mut in_init = true;
+    loop {
+        match parser.peek() {
+            Token::End => break,
+            Token::Separator | Token::Symbol(",") => {
+                parser.advance();
+            }
+            Token::Symbol(":") => {
+                if !in_init {
+                    return syntax_error(parser.position().0, "more than one ':'");
+                }
+                in_init = false;
+                parser.advance();
+            }
+            _ => {
+                let statement = parse_statement(&mut parser)?;
+                if in_init {
+                    init.push(statement);
+                } else {
+                    iteration.push(statement);
+                }
+                match parser.peek() {
+                    Token::End | Token::Separator | Token::Symbol(",") | Token::Symbol(":") => {}
+                    token => {
+                        let message = format!("unexpected {}", token);
+                        return syntax_error(parser.position().0, message);
+                    }
+                }
+            }
+        }
+    }
+    if in_init {
+        // Without ':' the whole formula is the iteration
+        iteration = init;
+        init = Vec::new();
+    }
+    match iteration.last() {
+        Some(Statement::Condition(_)) => Ok(Formula {
+            name: name.to_string(),
+            init,
+            iteration,
+        }),
+        _ => syntax_error(
+            parser.position().0,
+            "the formula does not end with a bailout condition",
+        ),
+    }
+}
+
+fn parse_statement(parser: &mut Parser) -> Result<Statement, FractintError> {
+    if let Token::Name(name) = parser.peek() {
+        if ["if", "elseif", "else", "endif"].contains(&name.as_str()) {
+            return Err(FractintError::Unsupported(format!(
+                "'{}' in line {}, conditional blocks are not supported",
+                name,
+                parser.position().0
+            )));
+        }
+    }
+    Ok(match parser.assignment() {
+        Some(name) => Statement::Assign(name, parser.expression(&FormulaScope)?),
+        None => Statement::Condition(parser.expression(&FormulaScope)?),
+    })
+}
+
+// Formulas of a formula file. Formulas that cannot be parsed only cause an error when they are
+// used, so that a single unsupported formula does not make the whole file unusable
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FormulaFile {
+    formulas: Vec<(String, Result<Formula, FractintError>)>,
+}
+
+impl FormulaFile {
+    pub fn parse(text: &str) -> Result<FormulaFile, FractintError> {
+        let formulas = blocks(text)?
+            .into_iter()
+            // `comment { ... }` blocks are free form text
+            .filter(|block| !block.name.eq_ignore_ascii_case("comment"))
+            .map(|block| {
+                // Drop symmetry annotations like `Mandel(XAXIS)`
+                let name = block
+                    .name
+                    .split('(')
+                    .next()
+                    .unwrap_or("")
+                    .trim()
+                    .to_string();
+                let formula = parse_formula(&name, &block.body, block.line);
+                (name, formula)
+            })
+            .collect();
+        Ok(FormulaFile { formulas })
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.formulas
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    // The formula with the given name, compared case insensitively like Fractint does
+    pub fn get(&self, name: &str) -> Result<&Formula, FractintError> {
+        match self
+            .formulas
+            .iter()
+            .find(|(formula, _)| formula.eq_ignore_ascii_case(name))
+        {
+            Some((_, formula)) => formula.as_ref().map_err(Clone::clone),
+            None => Err(FractintError::UnknownFormula(name.to_string())),
+        }
+    }
+}
+
+impl Formula {
+    // The fractal type that iterates the same series as the formula
+    pub fn fractal(&self) -> Result<Fractal, FractintError> {
+        let not_supported = || {
+            FractintError::Unsupported(format!(
+                "formula '{}', it is not equivalent to any of the supported fractal types",
+                self.name
+            ))
+        };
+
+        // Values of the variables after the initialisation, in terms of `pixel`
+        let mut values: HashMap<String, Expr> = HashMap::new();
+        for statement in self.init.iter() {
+            match statement {
+                Statement::Assign(name, expr) => {
+                    let value = substitute(expr, &values)?;
+                    values.insert(name.clone(), value);
+                }
+                Statement::Condition(_) => return Err(not_supported()),
+            }
+        }
+        let initial_z = values
+            .get("z")
+            .cloned()
+            .unwrap_or(Expr::Number(Complex::new(0.0, 0.0)));
+
+        // Variables assigned in the iteration are only known within it, the others keep their
+        // initial value. `z` carries the state from one iteration to the next
+        let (bailout, steps) = match self.iteration.split_last() {
+            Some((Statement::Condition(bailout), steps)) => (bailout, steps),
+            _ => return Err(not_supported()),
+        };
+        values.insert("z".to_string(), Expr::Variable("z".to_string()));
+        for statement in steps {
+            match statement {
+                Statement::Assign(name, expr) => {
+                    let value = substitute(expr, &values)?;
+                    values.insert(name.clone(), value);
+                }
+                Statement::Condition(_) => return Err(not_supported()),
+            }
+        }
+        // The bailout is tested on the new value of `z`
+        let step = values
+            .insert("z".to_string(), Expr::Variable("z".to_string()))
+            .ok_or_else(not_supported)?;
+        let bailout = substitute(bailout, &values)?;
+
+        let z = Expr::Variable("z".to_string());
+        let pixel = Expr::Variable("pixel".to_string());
+        let starts_like_mandelbrot = is_zero(&initial_z) || initial_z == pixel;
+        let steps_like_mandelbrot = match &step {
+            Expr::Binary(BinaryOp::Add, left, right) => {
+                (is_square_of(left, &z) && **right == pixel)
+                    || (is_square_of(right, &z) && **left == pixel)
+            }
+            _ => false,
+        };
+        if !starts_like_mandelbrot || !steps_like_mandelbrot {
+            return Err(not_supported());
+        }
+        if !is_escape_condition(&bailout, &z) {
+            return Err(FractintError::Unsupported(format!(
+                "bailout of formula '{}', only |z| <= {} is supported",
+                self.name, BAILOUT
+            )));
+        }
+        Ok(Fractal::Mandelbrot)
+    }
+}
+
+// Replaces the variables by their values. Variables without a value other than `pixel` are
+// parameters or predefined values of Fractint that are not supported
+fn substitute(expr: &Expr, values: &HashMap<String, Expr>) -> Result<Expr, FractintError> {
+    let substitute_box = |expr: &Expr| substitute(expr, values).map(Box::new);
+    Ok(match expr {
+        Expr::Number(value) => Expr::Number(*value),
+        Expr::Variable(name) => match values.get(name) {
+            Some(value) => value.clone(),
+            None if name == "pixel" => expr.clone(),
+            None => {
+                return Err(FractintError::Unsupported(format!(
+                    "variable '{}', it is neither assigned nor 'pixel'",
+                    name
+                )))
+            }
+        },
+        Expr::Negate(value) => Expr::Negate(substitute_box(value)?),
+        Expr::Not(value) => Expr::Not(substitute_box(value)?),
+        Expr::Binary(op, left, right) => {
+            Expr::Binary(*op, substitute_box(left)?, substitute_box(right)?)
+        }
+        Expr::Call(name, arguments) => {
+            if !FUNCTIONS.contains(&name.as_str()) {
+                return Err(FractintError::Unsupported(format!("function '{}'", name)));
+            }
+            let arguments = arguments
+                .iter()
+                .map(|argument| substitute(argument, values))
+                .collect::<Result<_, _>>()?;
+            Expr::Call(name.clone(), arguments)
+        }
+        Expr::Modulus(value) => Expr::Modulus(substitute_box(value)?),
+    })
+}
+
+fn is_zero(expr: &Expr) -> bool {
+    *expr == Expr::Number(Complex::new(0.0, 0.0))
+}
+
+// Whether the expression is `sqr(x)`, `x * x` or `x ^ 2`
+fn is_square_of(expr: &Expr, x: &Expr) -> bool {
+    match expr {
+        Expr::Call(name, arguments) => name == "sqr" && *arguments == [x.clone()],
+        Expr::Binary(BinaryOp::Mul, left, right) => **left == *x && **right == *x,
+        Expr::Binary(BinaryOp::Pow, base, exponent) => {
+            **base == *x && **exponent == Expr::Number(Complex::new(2.0, 0.0))
+        }
+        _ => false,
+    }
+}
+
+// Whether the condition is `|z| <= 4` or one of its equivalent forms
+fn is_escape_condition(condition: &Expr, z: &Expr) -> bool {
+    let modulus = Expr::Modulus(Box::new(z.clone()));
+    let bailout = Expr::Number(Complex::new(BAILOUT, 0.0));
+    match condition {
+        Expr::Binary(BinaryOp::Less, left, right)
+        | Expr::Binary(BinaryOp::LessEqual, left, right) => **left == modulus && **right == bailout,
+        Expr::Binary(BinaryOp::Greater, left, right)
+        | Expr::Binary(BinaryOp::GreaterEqual, left, right) => {
+            **left == bailout && **right == modulus
+        }
+        _ => false,
+    }
+}
+
+// Entry of a parameter file with its raw `key=value` pairs
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParEntry {
+    pub name: String,
+    pub values: Vec<(String, String)>,
+}
+
+// Location described by a parameter entry. `extent` is the size of the complex plane shown
+// across the height of the image
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParLocation {
+    pub centre: Complex<f64>,
+    pub extent: f64,
+    pub settings: RenderSettings,
+}
+
+// Parses all entries of a parameter file
+pub fn parse_par(text: &str) -> Result<Vec<ParEntry>, FractintError> {
+    blocks(text)?
+        .into_iter()
+        .map(|block| {
+            let mut values = Vec::new();
+            for (idx, line) in block.body.lines().enumerate() {
+                for pair in line.split_whitespace() {
+                    match pair.split_once('=') {
+                        Some((key, value)) => values.push((key.to_lowercase(), value.to_string())),
+                        None => {
+                            return syntax_error(
+                                block.line + idx,
+                                format!("expected key=value, found '{}'", pair),
+                            )
+                        }
+                    }
+                }
+            }
+            Ok(ParEntry {
+                name: block.name,
+                values,
+            })
+        })
+        .collect()
+}
+
+impl ParEntry {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Interprets the entry. `formulas` are searched for the formula of `type=formula` entries
+    pub fn location(&self, formulas: &FormulaFile) -> Result<ParLocation, FractintError> {
+        let fractal = match self.get("type").ok_or(FractintError::Missing("type"))? {
+            "mandel" => Fractal::Mandelbrot,
+            "formula" => {
+                let name = self
+                    .get("formulaname")
+                    .ok_or(FractintError::Missing("formulaname"))?;
+                formulas.get(name)?.fractal()?
+            }
+            other => return Err(FractintError::Unsupported(format!("type={}", other))),
+        };
+        for key in UNSUPPORTED_KEYS.iter() {
+            if let Some(value) = self.get(key) {
+                return Err(FractintError::Unsupported(format!("{}={}", key, value)));
+            }
+        }
+        if let Some(inside) = self.get("inside") {
+            if inside != "0" {
+                return Err(FractintError::Unsupported(format!("inside={}", inside)));
+            }
+        }
+        if let Some(outside) = self.get("outside") {
+            if outside != "iter" {
+                return Err(FractintError::Unsupported(format!("outside={}", outside)));
+            }
+        }
+        if let Some(params) = self.get("params") {
+            let numbers = parse_numbers("params", params)?;
+            if numbers.iter().any(|number| *number != 0.0) {
+                return Err(FractintError::Unsupported(format!("params={}", params)));
+            }
+        }
+
+        let max_iterations = match self.get("maxiter") {
+            Some(maxiter) => maxiter
+                .parse()
+                .map_err(|_| FractintError::Invalid("maxiter", maxiter.to_string()))?,
+            None => DEFAULT_MAXITER,
+        };
+        let palette = match self.get("colors") {
+            Some(colors) => Palette::closest(&decode_colors(colors)?),
+            None => Palette::default(),
+        };
+        let (centre, extent) = self.view()?;
+
+        Ok(ParLocation {
+            centre,
+            extent,
+            settings: RenderSettings::new(fractal, max_iterations, palette),
+        })
+    }
+
+    // Centre and extent across the height from `corners` or `center-mag`
+    fn view(&self) -> Result<(Complex<f64>, f64), FractintError> {
+        if let Some(corners) = self.get("corners") {
+            let numbers = parse_numbers("corners", corners)?;
+            let (x_min, x_max, y_min, y_max) = match numbers[..] {
+                [x_min, x_max, y_min, y_max] => (x_min, x_max, y_min, y_max),
+                [x_min, x_max, y_min, y_max, x_3rd, y_3rd] if x_3rd == x_min && y_3rd == y_min => {
+                    (x_min, x_max, y_min, y_max)
+                }
+                [_, _, _, _, _, _] => {
+                    return Err(FractintError::Unsupported(
+                        "rotated or skewed corners".to_string(),
+                    ))
+                }
+                _ => return Err(FractintError::Invalid("corners", corners.to_string())),
+            };
+            if y_max <= y_min || x_max <= x_min {
+                return Err(FractintError::Invalid("corners", corners.to_string()));
+            }
+            let centre = Complex::new((x_min + x_max) / 2.0, (y_min + y_max) / 2.0);
+            return Ok((centre, y_max - y_min));
+        }
+        if let Some(center_mag) = self.get("center-mag") {
+            let numbers = parse_numbers("center-mag", center_mag)?;
+            if numbers.len() < 3 || numbers.len() > 6 || numbers[2] <= 0.0 {
+                return Err(FractintError::Invalid("center-mag", center_mag.to_string()));
+            }
+            // Optional x magnification factor, rotation and skew
+            let defaults = [1.0, 0.0, 0.0];
+            if numbers[3..]
+                .iter()
+                .zip(defaults.iter())
+                .any(|(value, default)| value != default)
+            {
+                return Err(FractintError::Unsupported(format!(
+                    "center-mag={}",
+                    center_mag
+                )));
+            }
+            return Ok((Complex::new(numbers[0], numbers[1]), 2.0 / numbers[2]));
+        }
+        // Default view of the mandelbrot type
+        Ok((Complex::new(-0.5, 0.0), 3.0))
+    }
+}
+
+impl ParLocation {
+    // Square pixel viewport of a `width` x `height` image showing the location
+    pub fn viewport(&self, width: u32, height: u32) -> Viewport {
+        Viewport::new(self.centre, self.extent / height as f64, width, height)
+    }
+
+    // View of a `width` x `height` screen of a `Universe` with the same scale along the real axis
+    pub fn view_metadata(&self, width: u32, height: u32) -> ViewMetadata {
+        let pixel_size = self.extent / height as f64;
+        ViewMetadata {
+            settings: self.settings.clone(),
+            centre: self.centre,
+            zoom_factor: 1.0 / (width as f64 * pixel_size),
+            width,
+            height,
+        }
+    }
+}
+
+fn parse_numbers(key: &'static str, value: &str) -> Result<Vec<f64>, FractintError> {
+    value
+        .split('/')
+        .map(|number| number.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| FractintError::Invalid(key, value.to_string()))
+}
+
+// Number of colours of a Fractint colour table, including the one of the inside
+const MAX_COLORS: usize = 256;
+
+// Decodes a colour table of three characters per colour, each encoding a value from 0 to 63,
+// where `<n>` stands for n colours interpolated between its neighbours. The first colour is the
+// one of the inside and not part of the returned gradient. Tables of more than `MAX_COLORS`
+// colours are invalid
+pub fn decode_colors(colors: &str) -> Result<Vec<(u8, u8, u8)>, FractintError> {
+    if colors.starts_with('@') {
+        return Err(FractintError::Unsupported(format!(
+            "colour map file {}",
+            colors
+        )));
+    }
+    let invalid = || FractintError::Invalid("colors", colors.to_string());
+    let channel = |c: u8| -> Option<u8> {
+        let value = match c {
+            b'0'..=b'9' => c - b'0',
+            b'A'..=b'Z' => c - b'A' + 10,
+            b'_' => 36,
+            b'`' => 37,
+            b'a'..=b'z' => c - b'a' + 38,
+            _ => return None,
+        };
+        Some((value as u32 * 255 / 63) as u8)
+    };
+
+    let bytes = colors.as_bytes();
+    let mut decoded: Vec<(u8, u8, u8)> = Vec::new();
+    let mut pending_gradient = 0;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'<' {
+            let end = colors[idx..].find('>').ok_or_else(invalid)? + idx;
+            pending_gradient = colors[idx + 1..end].parse().map_err(|_| invalid())?;
+            if pending_gradient >= MAX_COLORS - decoded.len() {
+                return Err(invalid());
+            }
+            idx = end + 1;
+            continue;
+        }
+        if idx + 3 > bytes.len() {
+            return Err(invalid());
+        }
+        let color = (
+            channel(bytes[idx]).ok_or_else(invalid)?,
+            channel(bytes[idx + 1]).ok_or_else(invalid)?,
+            channel(bytes[idx + 2]).ok_or_else(invalid)?,
+        );
+        if pending_gradient > 0 {
+            let start = *decoded.last().ok_or_else(invalid)?;
+            let steps = pending_gradient + 1;
+            for step in 1..steps {
+                let mix = |a: u8, b: u8| {
+                    (a as f64 + (b as f64 - a as f64) * step as f64 / steps as f64).round() as u8
+                };
+                decoded.push((
+                    mix(start.0, color.0),
+                    mix(start.1, color.1),
+                    mix(start.2, color.2),
+                ));
+            }
+            pending_gradient = 0;
+        }
+        if decoded.len() == MAX_COLORS {
+            return Err(invalid());
+        }
+        decoded.push(color);
+        idx += 3;
+    }
+    if decoded.len() < 2 {
+        return Err(invalid());
+    }
+    Ok(decoded.split_off(1))
+}
@@ -0,0 +1,162 @@
+use crate::state::ViewState;
+use std::collections::VecDeque;
+
+// Number of views kept for undo unless configured otherwise
+pub const DEFAULT_CAPACITY: usize = 100;
+
+// Number of rendered frames kept, frames take 3 bytes per pixel
+pub const DEFAULT_FRAME_CAPACITY: usize = 8;
+
+// Cells of a rendered view
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub cells_r: Vec<u8>,
+    pub cells_g: Vec<u8>,
+    pub cells_b: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub state: ViewState,
+    pub frame: Option<Frame>,
+}
+
+// Bounded list of visited views with a cursor on the current one. Recording a view while not at
+// the end of the list drops the views that could have been redone, like in an editor
+#[derive(Debug)]
+pub struct History {
+    capacity: usize,
+    frame_capacity: usize,
+    entries: VecDeque<HistoryEntry>,
+    current: usize,
+}
+
+impl History {
+    pub fn new(state: ViewState, capacity: usize, frame_capacity: usize) -> History {
+        let mut entries = VecDeque::new();
+        entries.push_back(HistoryEntry { state, frame: None });
+        History {
+            capacity: capacity.max(1),
+            frame_capacity,
+            entries,
+            current: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn current(&self) -> &HistoryEntry {
+        &self.entries[self.current]
+    }
+
+    pub fn get(&self, index: usize) -> Option<&HistoryEntry> {
+        self.entries.get(index)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.current > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.current + 1 < self.entries.len()
+    }
+
+    // Makes the view the current one unless it already is
+    pub fn record(&mut self, state: ViewState) {
+        if self.current().state == state {
+            return;
+        }
+        self.entries.truncate(self.current + 1);
+        self.entries.push_back(HistoryEntry { state, frame: None });
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+        self.current = self.entries.len() - 1;
+    }
+
+    // Updates the current view in place without an undo step, e.g. for a resized screen
+    pub fn replace_current(&mut self, state: ViewState) {
+        self.entries[self.current] = HistoryEntry { state, frame: None };
+    }
+
+    // Whether a frame of the view would be kept, i.e. it is the current one and has none yet
+    pub fn wants_frame(&self, state: &ViewState) -> bool {
+        let current = self.current();
+        self.frame_capacity > 0 && current.state == *state && current.frame.is_none()
+    }
+
+    // Keeps the frame for returning to the current view, if it shows that view
+    pub fn store_frame(&mut self, state: &ViewState, frame: Frame) {
+        if self.current().state != *state || self.frame_capacity == 0 {
+            return;
+        }
+        self.entries[self.current].frame = Some(frame);
+        self.evict_frames();
+    }
+
+    pub fn undo(&mut self) -> Option<&HistoryEntry> {
+        if !self.can_undo() {
+            return None;
+        }
+        self.jump(self.current - 1)
+    }
+
+    pub fn redo(&mut self) -> Option<&HistoryEntry> {
+        if !self.can_redo() {
+            return None;
+        }
+        self.jump(self.current + 1)
+    }
+
+    pub fn jump(&mut self, index: usize) -> Option<&HistoryEntry> {
+        if index >= self.entries.len() {
+            return None;
+        }
+        self.current = index;
+        self.entries.get(index)
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize, frame_capacity: usize) {
+        self.capacity = capacity.max(1);
+        self.frame_capacity = frame_capacity;
+        // Drop the oldest views, but never the current one
+        while self.entries.len() > self.capacity {
+            if self.current == 0 {
+                self.entries.pop_back();
+            } else {
+                self.entries.pop_front();
+                self.current -= 1;
+            }
+        }
+        self.evict_frames();
+    }
+
+    // Drops the frames of the views furthest from the current one
+    fn evict_frames(&mut self) {
+        loop {
+            let with_frames: Vec<usize> = (0..self.entries.len())
+                .filter(|idx| self.entries[*idx].frame.is_some())
+                .collect();
+            if with_frames.len() <= self.frame_capacity {
+                return;
+            }
+            let current = self.current;
+            let furthest = with_frames
+                .into_iter()
+                .max_by_key(|idx| (*idx as i64 - current as i64).abs());
+            if let Some(idx) = furthest {
+                self.entries[idx].frame = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,430 @@
+//! Iterated function systems: fractals that are the attractor of a set of affine maps, like the
+//! Barnsley fern and the Sierpinski triangle.
+//!
+//! They are rendered by the chaos game: a point jumps around by applying a map chosen at random
+//! by its weight, and every pixel counts how often the point landed on it. The counts are tone
+//! mapped logarithmically through the palette.
+//!
+//! Maps are written one per line as `a b c d e f [weight]`, mapping `(x, y)` to
+//! `(a x + b y + e, c x + d y + f)`. Text after `#` is a comment. Maps without a weight are
+//! chosen in proportion to the area they cover, `|a d - b c|`. The same maps can be loaded from
+//! JSON as `{"maps": [{"a": 0.5, "b": 0, "c": 0, "d": 0.5, "e": 0, "f": 0, "weight": 1}]}`.
+//!
+//! The plane of the maps is shown upright: `x` runs along the columns and `y` upwards along the
+//! rows, so `(x, y)` is shown at the complex number `-y + x i`.
+
+use crate::density::{self, DensityRenderer, Random, Screen};
+use crate::mandelbrot::Position;
+use crate::palette::Palette;
+use num::complex::Complex;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+// Names of the built-in systems accepted by `Ifs::preset`
+pub const PRESETS: [&str; 2] = ["barnsley-fern", "sierpinski"];
+
+// Largest number of maps of a system
+pub const MAX_MAPS: usize = 256;
+
+// Maps covering no area still get chosen now and then, otherwise their part of the attractor
+// would be missing, e.g. the stem of the fern
+const MIN_AREA_WEIGHT: f64 = 0.01;
+
+// Jumps made before plotting, so that the point is on the attractor
+const WARM_UP: usize = 20;
+
+// Points plotted by one parallel task with its own counts
+const POINTS_PER_TASK: u64 = 16384;
+
+// Points used to find the extent of the attractor
+const BOUNDS_POINTS: usize = 20_000;
+
+#[derive(Debug)]
+pub enum IfsError {
+    Syntax { line: usize, message: String },
+    Json(serde_json::Error),
+    Empty,
+    TooManyMaps(usize),
+    InvalidCoefficient(usize),
+    InvalidWeight(usize),
+    UnknownPreset(String),
+}
+
+impl fmt::Display for IfsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IfsError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            IfsError::Json(err) => write!(f, "invalid IFS file: {}", err),
+            IfsError::Empty => write!(f, "the system has no maps"),
+            IfsError::TooManyMaps(count) => write!(
+                f,
+                "the system has {} maps, at most {} are supported",
+                count, MAX_MAPS
+            ),
+            IfsError::InvalidCoefficient(map) => {
+                write!(f, "map {} has a coefficient that is not finite", map + 1)
+            }
+            IfsError::InvalidWeight(map) => write!(
+                f,
+                "map {} has an invalid weight, weights must be finite and positive",
+                map + 1
+            ),
+            IfsError::UnknownPreset(name) => write!(f, "unknown IFS preset '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for IfsError {}
+
+impl From<serde_json::Error> for IfsError {
+    fn from(err: serde_json::Error) -> IfsError {
+        IfsError::Json(err)
+    }
+}
+
+// `(x, y) -> (a x + b y + e, c x + d y + f)`, chosen with a probability proportional to `weight`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AffineMap {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+    pub weight: f64,
+}
+
+impl AffineMap {
+    // Map with the weight derived from its area
+    pub fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> AffineMap {
+        let mut map = AffineMap {
+            a,
+            b,
+            c,
+            d,
+            e,
+            f,
+            weight: 0.0,
+        };
+        map.weight = map.determinant().abs().max(MIN_AREA_WEIGHT);
+        map
+    }
+
+    pub fn with_weight(self, weight: f64) -> AffineMap {
+        AffineMap { weight, ..self }
+    }
+
+    pub fn determinant(&self) -> f64 {
+        self.a * self.d - self.b * self.c
+    }
+
+    pub fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            self.a * x + self.b * y + self.e,
+            self.c * x + self.d * y + self.f,
+        )
+    }
+}
+
+// Layout of JSON files, weights are optional like in the text format
+#[derive(Serialize, Deserialize)]
+struct IfsFile {
+    maps: Vec<MapRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MapRecord {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+    f: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    weight: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ifs {
+    maps: Vec<AffineMap>,
+    // Cumulative probability of choosing the maps
+    cumulative: Vec<f64>,
+}
+
+impl Ifs {
+    pub fn new(maps: Vec<AffineMap>) -> Result<Ifs, IfsError> {
+        if maps.is_empty() {
+            return Err(IfsError::Empty);
+        }
+        if maps.len() > MAX_MAPS {
+            return Err(IfsError::TooManyMaps(maps.len()));
+        }
+        for (idx, map) in maps.iter().enumerate() {
+            let coefficients = [map.a, map.b, map.c, map.d, map.e, map.f];
+            if coefficients.iter().any(|value| !value.is_finite()) {
+                return Err(IfsError::InvalidCoefficient(idx));
+            }
+            if !map.weight.is_finite() || map.weight <= 0.0 {
+                return Err(IfsError::InvalidWeight(idx));
+            }
+        }
+        let total: f64 = maps.iter().map(|map| map.weight).sum();
+        let mut sum = 0.0;
+        let cumulative = maps
+            .iter()
+            .map(|map| {
+                sum += map.weight / total;
+                sum
+            })
+            .collect();
+        Ok(Ifs { maps, cumulative })
+    }
+
+    // Parses maps written one per line as `a b c d e f [weight]`, separated by whitespace or
+    // commas
+    pub fn parse(text: &str) -> Result<Ifs, IfsError> {
+        let mut maps = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let syntax = |message: String| IfsError::Syntax {
+                line: idx + 1,
+                message,
+            };
+            let line = line.split('#').next().unwrap_or("");
+            let values = line
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|value| !value.is_empty())
+                .map(|value| {
+                    value
+                        .parse::<f64>()
+                        .map_err(|_| syntax(format!("invalid number '{}'", value)))
+                })
+                .collect::<Result<Vec<f64>, IfsError>>()?;
+            match values.len() {
+                0 => continue,
+                6 | 7 => {
+                    let v = &values;
+                    let map = AffineMap::new(v[0], v[1], v[2], v[3], v[4], v[5]);
+                    maps.push(match v.get(6) {
+                        Some(weight) => map.with_weight(*weight),
+                        None => map,
+                    });
+                }
+                count => {
+                    return Err(syntax(format!(
+                        "expected 6 coefficients and an optional weight, found {} numbers",
+                        count
+                    )))
+                }
+            }
+        }
+        Ifs::new(maps)
+    }
+
+    pub fn to_text(&self) -> String {
+        self.maps
+            .iter()
+            .map(|m| {
+                format!(
+                    "{} {} {} {} {} {} {}\n",
+                    m.a, m.b, m.c, m.d, m.e, m.f, m.weight
+                )
+            })
+            .collect()
+    }
+
+    pub fn from_json(json: &str) -> Result<Ifs, IfsError> {
+        let file: IfsFile = serde_json::from_str(json)?;
+        let maps = file
+            .maps
+            .into_iter()
+            .map(|r| {
+                let map = AffineMap::new(r.a, r.b, r.c, r.d, r.e, r.f);
+                match r.weight {
+                    Some(weight) => map.with_weight(weight),
+                    None => map,
+                }
+            })
+            .collect();
+        Ifs::new(maps)
+    }
+
+    pub fn to_json(&self) -> Result<String, IfsError> {
+        let file = IfsFile {
+            maps: self
+                .maps
+                .iter()
+                .map(|m| MapRecord {
+                    a: m.a,
+                    b: m.b,
+                    c: m.c,
+                    d: m.d,
+                    e: m.e,
+                    f: m.f,
+                    weight: Some(m.weight),
+                })
+                .collect(),
+        };
+        Ok(serde_json::to_string_pretty(&file)?)
+    }
+
+    // One of the systems named in `PRESETS`
+    pub fn preset(name: &str) -> Result<Ifs, IfsError> {
+        match name {
+            "barnsley-fern" => Ok(Ifs::barnsley_fern()),
+            "sierpinski" => Ok(Ifs::sierpinski()),
+            _ => Err(IfsError::UnknownPreset(name.to_string())),
+        }
+    }
+
+    pub fn barnsley_fern() -> Ifs {
+        Ifs::new(vec![
+            AffineMap::new(0.0, 0.0, 0.0, 0.16, 0.0, 0.0).with_weight(0.01),
+            AffineMap::new(0.85, 0.04, -0.04, 0.85, 0.0, 1.6).with_weight(0.85),
+            AffineMap::new(0.2, -0.26, 0.23, 0.22, 0.0, 1.6).with_weight(0.07),
+            AffineMap::new(-0.15, 0.28, 0.26, 0.24, 0.0, 0.44).with_weight(0.07),
+        ])
+        .unwrap()
+    }
+
+    // The Sierpinski triangle with corners (0, 0), (1, 0) and (1/2, √3/2)
+    pub fn sierpinski() -> Ifs {
+        let height = 3f64.sqrt() / 2.0;
+        Ifs::new(vec![
+            AffineMap::new(0.5, 0.0, 0.0, 0.5, 0.0, 0.0),
+            AffineMap::new(0.5, 0.0, 0.0, 0.5, 0.5, 0.0),
+            AffineMap::new(0.5, 0.0, 0.0, 0.5, 0.25, height / 2.0),
+        ])
+        .unwrap()
+    }
+
+    pub fn maps(&self) -> &[AffineMap] {
+        &self.maps
+    }
+
+    // Index of the map chosen by a uniform random number in [0, 1)
+    pub fn choose(&self, u: f64) -> usize {
+        self.cumulative
+            .partition_point(|p| *p <= u)
+            .min(self.maps.len() - 1)
+    }
+
+    // Applies a map chosen at random by weight. Points that are thrown to infinity by maps which
+    // do not contract start over at the origin
+    fn jump(&self, point: (f64, f64), random: &mut Random) -> (f64, f64) {
+        let (x, y) = self.maps[self.choose(random.next_f64())].apply(point);
+        if x.is_finite() && y.is_finite() {
+            (x, y)
+        } else {
+            (0.0, 0.0)
+        }
+    }
+
+    // Smallest and largest x and y of the points visited by a fixed run of the chaos game
+    pub fn bounds(&self) -> ((f64, f64), (f64, f64)) {
+        let mut random = Random(0);
+        let mut point = (0.0, 0.0);
+        for _ in 0..WARM_UP {
+            point = self.jump(point, &mut random);
+        }
+        let (mut x_range, mut y_range) = ((point.0, point.0), (point.1, point.1));
+        for _ in 0..BOUNDS_POINTS {
+            point = self.jump(point, &mut random);
+            x_range = (x_range.0.min(point.0), x_range.1.max(point.0));
+            y_range = (y_range.0.min(point.1), y_range.1.max(point.1));
+        }
+        (x_range, y_range)
+    }
+
+    // Position of a `width` x `height` screen of a `Universe` that shows the whole attractor
+    pub fn fitting_position(&self, width: u32, height: u32) -> Position {
+        density::fitting_position(self.bounds(), to_complex, width, height)
+    }
+}
+
+// Point of the complex plane at which a point of the maps is shown
+pub fn to_complex((x, y): (f64, f64)) -> Complex<f64> {
+    Complex::new(-y, x)
+}
+
+// Density renderer playing the chaos game of a system
+#[derive(Debug, Clone)]
+pub struct ChaosGame {
+    screen: Screen,
+    ifs: Ifs,
+    palette: Palette,
+    counts: Vec<f32>,
+    samples: u64,
+}
+
+impl ChaosGame {
+    pub fn new(screen: Screen, ifs: Ifs, palette: Palette) -> ChaosGame {
+        let pixels = screen.pixels();
+        ChaosGame {
+            screen,
+            ifs,
+            palette,
+            counts: vec![0.0; pixels],
+            samples: 0,
+        }
+    }
+
+    pub fn ifs(&self) -> &Ifs {
+        &self.ifs
+    }
+
+    pub fn counts(&self) -> &[f32] {
+        &self.counts
+    }
+}
+
+impl DensityRenderer for ChaosGame {
+    fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    fn restart(
+        &self,
+        screen: Screen,
+    ) -> Result<Box<dyn DensityRenderer>, Box<dyn Error + Send + Sync>> {
+        Ok(Box::new(ChaosGame::new(
+            screen,
+            self.ifs.clone(),
+            self.palette.clone(),
+        )))
+    }
+
+    // Every sample is a plotted point, every task plays its own game from a random start
+    fn accumulate(&mut self, samples: u64) {
+        let pixels = self.counts.len();
+        let (ifs, screen) = (&self.ifs, &self.screen);
+        density::sample_parallel(
+            &mut self.counts,
+            self.samples..self.samples + samples,
+            POINTS_PER_TASK,
+            || vec![0.0; pixels],
+            |a: &mut Vec<f32>, b: &Vec<f32>| density::add_counts(a, b),
+            |mut random| {
+                let mut point = (random.next_f64(), random.next_f64());
+                for _ in 0..WARM_UP {
+                    point = ifs.jump(point, &mut random);
+                }
+                move |counts: &mut Vec<f32>| {
+                    point = ifs.jump(point, &mut random);
+                    if let Some(idx) = screen.pixel(to_complex(point)) {
+                        counts[idx] += 1.0;
+                    }
+                }
+            },
+        );
+        self.samples += samples;
+    }
+
+    fn to_cells(&self) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        density::palette_cells(&self.counts, &self.palette)
+    }
+
+    fn samples(&self) -> u64 {
+        self.samples
+    }
+}
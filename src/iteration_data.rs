@@ -0,0 +1,251 @@
+//! Raw per pixel escape data of a rendered viewport, for offline analysis and re-colouring.
+//!
+//! Files are stored in the following little endian binary format:
+//!
+//! | Size      | Content                                                             |
+//! |-----------|---------------------------------------------------------------------|
+//! | 8         | magic bytes `FRACITER`                                              |
+//! | 2         | format version (u16), currently 2                                   |
+//! | 2         | flags (u16), bit 0 set if distance estimates are included           |
+//! | 4 + 4     | width and height in pixels (u32)                                    |
+//! | 4         | iteration limit (u32)                                               |
+//! | 8 + 8     | real and imaginary part of the centre of the viewport (f64)         |
+//! | 8         | size of a pixel in the complex plane (f64)                          |
+//! | 2 + n     | length (u16) and UTF-8 text of `Fractal::spec`                      |
+//! | 4 * w * h | smoothed iteration counts (f32) in row major order, rows top down,  |
+//! |           | `f32::INFINITY` for points that did not escape                      |
+//! | 4 * w * h | distance estimates (f32) if flag bit 0 is set, 0 for points in set  |
+
+use crate::fractal::Fractal;
+use crate::mandelbrot::Escape;
+use crate::palette::{self, Palette};
+use crate::viewport::Viewport;
+use num::complex::Complex;
+use rayon::prelude::*;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+pub const MAGIC: &[u8; 8] = b"FRACITER";
+pub const VERSION: u16 = 2;
+// Version 1 stored the length of the fractal name in a single byte
+const VERSION_SHORT_NAME: u16 = 1;
+const FLAG_DISTANCES: u16 = 1;
+
+// Largest number of pixels a file may hold, a gigapixel takes 4 GiB of iteration counts
+pub const MAX_PIXELS: u64 = 1 << 30;
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    NotIterationData,
+    UnsupportedVersion(u16),
+    UnknownFractal(String),
+    TooLarge { width: u32, height: u32 },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::Io(err) => write!(f, "{}", err),
+            FormatError::NotIterationData => write!(f, "not an iteration data file"),
+            FormatError::UnsupportedVersion(version) => write!(
+                f,
+                "iteration data version {} is not supported, expected {}",
+                version, VERSION
+            ),
+            FormatError::UnknownFractal(name) => write!(f, "unknown fractal '{}'", name),
+            FormatError::TooLarge { width, height } => write!(
+                f,
+                "{} x {} pixels exceed the limit of {} pixels",
+                width, height, MAX_PIXELS
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<io::Error> for FormatError {
+    fn from(err: io::Error) -> FormatError {
+        FormatError::Io(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IterationData {
+    pub fractal: Fractal,
+    pub viewport: Viewport,
+    pub max_iterations: u32,
+    pub iterations: Vec<f32>,
+    pub distances: Option<Vec<f32>>,
+}
+
+impl IterationData {
+    // Calculates the escape data of every pixel of the viewport, rows in parallel
+    pub fn render(
+        fractal: Fractal,
+        viewport: Viewport,
+        max_iterations: u32,
+        with_distances: bool,
+    ) -> IterationData {
+        let escapes = (0..viewport.get_height())
+            .into_par_iter()
+            .flat_map_iter(|row| {
+                let (fractal, viewport) = (&fractal, &viewport);
+                (0..viewport.get_width()).map(move |col| {
+                    fractal.escape(viewport.pixel_to_complex(col, row), max_iterations)
+                })
+            });
+        let iterations_of = |escape: &Option<Escape>| match escape {
+            Some(escape) => escape.smoothed_iterations as f32,
+            None => f32::INFINITY,
+        };
+        // Distance estimates are only kept if they are requested
+        let (iterations, distances) = if with_distances {
+            let (iterations, distances) = escapes
+                .map(|escape| {
+                    let distance = escape.map_or(0.0, |escape| escape.distance as f32);
+                    (iterations_of(&escape), distance)
+                })
+                .unzip();
+            (iterations, Some(distances))
+        } else {
+            let iterations = escapes.map(|escape| iterations_of(&escape)).collect();
+            (iterations, None)
+        };
+        IterationData {
+            fractal,
+            viewport,
+            max_iterations,
+            iterations,
+            distances,
+        }
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let flags = if self.distances.is_some() { FLAG_DISTANCES } else { 0 };
+        let centre = self.viewport.get_centre();
+        let spec = self.fractal.spec();
+        let name = spec.as_bytes();
+        let name_length = u16::try_from(name.len()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "fractal specification is longer than 65535 bytes",
+            )
+        })?;
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&flags.to_le_bytes())?;
+        writer.write_all(&self.viewport.get_width().to_le_bytes())?;
+        writer.write_all(&self.viewport.get_height().to_le_bytes())?;
+        writer.write_all(&self.max_iterations.to_le_bytes())?;
+        writer.write_all(&centre.re.to_le_bytes())?;
+        writer.write_all(&centre.im.to_le_bytes())?;
+        writer.write_all(&self.viewport.get_pixel_size().to_le_bytes())?;
+        writer.write_all(&name_length.to_le_bytes())?;
+        writer.write_all(name)?;
+        let mut write_values = |values: &[f32]| -> io::Result<()> {
+            let bytes: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes()).collect();
+            writer.write_all(&bytes)
+        };
+        write_values(&self.iterations)?;
+        if let Some(distances) = &self.distances {
+            write_values(distances)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> Result<IterationData, FormatError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(FormatError::NotIterationData);
+        }
+        let version = u16::from_le_bytes(read_array(reader)?);
+        if version != VERSION && version != VERSION_SHORT_NAME {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+        let flags = u16::from_le_bytes(read_array(reader)?);
+        let width = u32::from_le_bytes(read_array(reader)?);
+        let height = u32::from_le_bytes(read_array(reader)?);
+        let max_iterations = u32::from_le_bytes(read_array(reader)?);
+        let re = f64::from_le_bytes(read_array(reader)?);
+        let im = f64::from_le_bytes(read_array(reader)?);
+        let pixel_size = f64::from_le_bytes(read_array(reader)?);
+        let name_length = if version == VERSION_SHORT_NAME {
+            u8::from_le_bytes(read_array(reader)?) as usize
+        } else {
+            u16::from_le_bytes(read_array(reader)?) as usize
+        };
+        let mut name = vec![0; name_length];
+        reader.read_exact(&mut name)?;
+        let name = String::from_utf8_lossy(&name).into_owned();
+        let fractal = Fractal::from_spec(&name).ok_or(FormatError::UnknownFractal(name))?;
+
+        // The header is checked before anything is allocated for the values
+        let pixels = (width as usize)
+            .checked_mul(height as usize)
+            .filter(|pixels| *pixels as u64 <= MAX_PIXELS)
+            .ok_or(FormatError::TooLarge { width, height })?;
+        let iterations = read_values(reader, pixels)?;
+        let distances = if flags & FLAG_DISTANCES != 0 {
+            Some(read_values(reader, pixels)?)
+        } else {
+            None
+        };
+        Ok(IterationData {
+            fractal,
+            viewport: Viewport::new(Complex::new(re, im), pixel_size, width, height),
+            max_iterations,
+            iterations,
+            distances,
+        })
+    }
+
+    // Colours the data like the renderer would with the given palette, optionally darkening
+    // pixels close to the set based on the distance estimates
+    pub fn to_rgba(&self, palette: &Palette, distance_shading: bool) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(self.iterations.len() * 4);
+        for (idx, &iterations) in self.iterations.iter().enumerate() {
+            let quotient = if iterations.is_finite() {
+                iterations as f64 / self.max_iterations as f64
+            } else {
+                1.0
+            };
+            let mut rgb = palette.rgb_value(quotient);
+            if let (true, Some(distances)) = (distance_shading, &self.distances) {
+                if iterations.is_finite() {
+                    let pixel_size = self.viewport.get_pixel_size();
+                    rgb = palette::shade_by_distance(rgb, distances[idx] as f64, pixel_size);
+                }
+            }
+            rgba.extend_from_slice(&[rgb.0, rgb.1, rgb.2, 255]);
+        }
+        rgba
+    }
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+// Reads `count` values a chunk at a time, so that memory only grows with the data actually read
+// and a file shorter than its header claims fails early
+fn read_values(reader: &mut impl Read, count: usize) -> io::Result<Vec<f32>> {
+    const CHUNK_VALUES: usize = 1 << 16;
+    let mut values = Vec::new();
+    let mut bytes = vec![0; CHUNK_VALUES * 4];
+    while values.len() < count {
+        let chunk = &mut bytes[..(count - values.len()).min(CHUNK_VALUES) * 4];
+        reader.read_exact(chunk)?;
+        values.extend(
+            chunk
+                .chunks_exact(4)
+                .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]])),
+        );
+    }
+    Ok(values)
+}
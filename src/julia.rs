@@ -0,0 +1,58 @@
+//! Julia sets linked to the Mandelbrot set.
+//!
+//! Every point c of the plane has a Julia set of z^2 + c, which is connected exactly when c
+//! belongs to the Mandelbrot set. Low resolution previews of them are quick enough to follow the
+//! pointer over the Mandelbrot set, and a `Universe` can switch its view to one of them and later
+//! return to the view it came from.
+
+use crate::fractal::{Fractal, RenderSettings};
+use crate::mandelbrot::Position;
+use num::complex::Complex;
+use rayon::prelude::*;
+
+// Parameter of the Julia set selected by the name `julia` alone
+pub const DEFAULT_PARAMETER: Complex<f64> = Complex::new(-0.8, 0.156);
+
+// Iteration limit of previews, higher limits of the settings only add detail that is too small to
+// see at low resolution
+pub const PREVIEW_MAX_ITERATIONS: u32 = 256;
+
+// Side of the square around the origin shown at first, it holds all but the tips of the Julia
+// sets of points close to the Mandelbrot set
+const EXTENT: f64 = 3.2;
+
+// Whether the Julia set of c can be rendered. Points escape once they are further than 2 from the
+// origin only if c is not
+pub fn is_valid_parameter(c: Complex<f64>) -> bool {
+    c.re.is_finite() && c.im.is_finite() && c.norm_sqr() <= 4.0
+}
+
+// Position of a `width` x `height` screen of a `Universe` showing a whole Julia set
+pub fn default_position(width: u32, height: u32) -> Position {
+    let zoom_factor = Position::zoom_factor_fitting(EXTENT, EXTENT, width, height);
+    Position::from_centre(Complex::new(0.0, 0.0), zoom_factor, width, height)
+}
+
+// RGBA bytes of a `width` x `height` preview of the Julia set of c in row major order, oriented
+// like the view of a `Universe` and coloured with the palette of the settings. Rows are rendered
+// in parallel on the current rayon thread pool
+pub fn thumbnail(c: Complex<f64>, settings: &RenderSettings, width: u32, height: u32) -> Vec<u8> {
+    let settings = RenderSettings {
+        fractal: Fractal::Julia(c),
+        max_iterations: settings.max_iterations.min(PREVIEW_MAX_ITERATIONS),
+        palette: settings.palette.clone(),
+    };
+    let position = default_position(width, height);
+    (0..height)
+        .into_par_iter()
+        .flat_map_iter(|row| {
+            let settings = &settings;
+            let position = &position;
+            (0..width).flat_map(move |col| {
+                let point = position.pixel_to_complex(row as f64, col as f64, width, height);
+                let (r, g, b) = settings.rgb_value_at(point);
+                [r, g, b, 255]
+            })
+        })
+        .collect()
+}
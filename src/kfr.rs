@@ -0,0 +1,221 @@
+//! Reading and writing of Kalles Fraktaler location files (`.kfr`).
+//!
+//! A location file is a list of `Key: value` lines. Only the keys that describe what can be shown
+//! by this crate are interpreted: `Re`, `Im`, `Zoom`, `Iterations` and the colour table in
+//! `Colors`. Keys that would change the image in a way that cannot be reproduced, like another
+//! fractal type or a rotated view, are rejected. All other keys only tune the renderer of Kalles
+//! Fraktaler and are ignored.
+//!
+//! Kalles Fraktaler shows `4 / Zoom` of the complex plane across the height of the image. As rows
+//! run along the real axis in this crate, that span is mapped onto the real axis.
+
+use crate::export::ViewMetadata;
+use crate::fractal::{Fractal, RenderSettings};
+use crate::palette::Palette;
+use crate::viewport::Viewport;
+use num::complex::Complex;
+use std::fmt;
+
+// Deepest zoom whose pixels can still be told apart with f64 precision, deeper locations need
+// arbitrary precision which is not supported
+pub const MAX_ZOOM: f64 = 1e13;
+
+// Number of colours written to the colour table of exported files
+const EXPORTED_COLORS: usize = 16;
+
+// Keys that change the image and the only value of each that can be reproduced
+const FIXED_KEYS: [(&str, &str); 5] = [
+    ("FractalType", "0"),
+    ("Power", "2"),
+    ("Rotate", "0"),
+    ("Ratio", "360"),
+    ("InteriorColor", "0,0,0"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KfrError {
+    Missing(&'static str),
+    Invalid(String, String),
+    Unsupported(String, String),
+    ZoomTooDeep(f64),
+}
+
+impl fmt::Display for KfrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KfrError::Missing(key) => write!(f, "missing '{}' in location file", key),
+            KfrError::Invalid(key, value) => write!(f, "invalid value '{}' for '{}'", value, key),
+            KfrError::Unsupported(key, value) => {
+                write!(f, "unsupported value '{}' for '{}'", value, key)
+            }
+            KfrError::ZoomTooDeep(zoom) => write!(
+                f,
+                "zoom {:E} is deeper than the maximum of {:E}",
+                zoom, MAX_ZOOM
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KfrError {}
+
+// A location as stored in a `.kfr` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct KfrLocation {
+    pub centre: Complex<f64>,
+    pub zoom: f64,
+    pub settings: RenderSettings,
+}
+
+impl KfrLocation {
+    pub fn parse(text: &str) -> Result<KfrLocation, KfrError> {
+        let entries: Vec<(&str, &str)> = text
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect();
+        let value = |key: &'static str| {
+            entries
+                .iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| *value)
+                .ok_or(KfrError::Missing(key))
+        };
+        let invalid =
+            |key: &str, value: &str| KfrError::Invalid(key.to_string(), value.to_string());
+
+        for (key, supported) in FIXED_KEYS.iter() {
+            if let Ok(value) = value(key) {
+                if !is_same_value(value, supported) {
+                    return Err(KfrError::Unsupported(key.to_string(), value.to_string()));
+                }
+            }
+        }
+
+        let re = value("Re")?;
+        let re = re.parse().map_err(|_| invalid("Re", re))?;
+        let im = value("Im")?;
+        let im = im.parse().map_err(|_| invalid("Im", im))?;
+        let zoom_text = value("Zoom")?;
+        let zoom: f64 = zoom_text.parse().map_err(|_| invalid("Zoom", zoom_text))?;
+        if !zoom.is_finite() || zoom <= 0.0 {
+            return Err(invalid("Zoom", zoom_text));
+        }
+        if zoom > MAX_ZOOM {
+            return Err(KfrError::ZoomTooDeep(zoom));
+        }
+        let iterations = value("Iterations")?;
+        let max_iterations = iterations
+            .parse()
+            .map_err(|_| invalid("Iterations", iterations))?;
+        let palette = match value("Colors") {
+            Ok(colors) => {
+                Palette::closest(&parse_colors(colors).ok_or_else(|| invalid("Colors", colors))?)
+            }
+            Err(_) => Palette::default(),
+        };
+
+        Ok(KfrLocation {
+            centre: Complex::new(re, im),
+            zoom,
+            settings: RenderSettings::new(Fractal::Mandelbrot, max_iterations, palette),
+        })
+    }
+
+    // Writes the location in the line format of Kalles Fraktaler, with the palette sampled into
+    // its colour table
+    pub fn to_text(&self) -> String {
+        let colors: String = self
+            .settings
+            .palette
+            .sample(EXPORTED_COLORS)
+            .iter()
+            .map(|(r, g, b)| format!("{},{},{},", r, g, b))
+            .collect();
+        let mut lines = vec![
+            format!("Re: {}", self.centre.re),
+            format!("Im: {}", self.centre.im),
+            format!("Zoom: {:E}", self.zoom),
+            format!("Iterations: {}", self.settings.max_iterations),
+            "IterDiv: 1.000000".to_string(),
+            "ColorMethod: 0".to_string(),
+            "ColorOffset: 0".to_string(),
+            format!("Colors: {}", colors),
+            "Smooth: 1".to_string(),
+        ];
+        for (key, value) in FIXED_KEYS.iter() {
+            let value = if *key == "InteriorColor" {
+                format!("{},", value)
+            } else {
+                value.to_string()
+            };
+            lines.push(format!("{}: {}", key, value));
+        }
+        lines.iter().map(|line| format!("{}\r\n", line)).collect()
+    }
+
+    // Square pixel viewport of a `width` x `height` image showing the location
+    pub fn viewport(&self, width: u32, height: u32) -> Viewport {
+        Viewport::new(self.centre, self.pixel_size(height), width, height)
+    }
+
+    // View of a `width` x `height` screen of a `Universe` with the same scale along the real axis
+    pub fn view_metadata(&self, width: u32, height: u32) -> ViewMetadata {
+        // Rows are `1 / (width * zoom_factor)` apart along the real axis
+        let zoom_factor = 1.0 / (width as f64 * self.pixel_size(height));
+        ViewMetadata {
+            settings: self.settings.clone(),
+            centre: self.centre,
+            zoom_factor,
+            width,
+            height,
+        }
+    }
+
+    pub fn from_view_metadata(metadata: &ViewMetadata) -> KfrLocation {
+        let pixel_size = 1.0 / (metadata.width as f64 * metadata.zoom_factor);
+        KfrLocation {
+            centre: metadata.centre,
+            zoom: 4.0 / (pixel_size * metadata.height as f64),
+            settings: metadata.settings.clone(),
+        }
+    }
+
+    fn pixel_size(&self, height: u32) -> f64 {
+        4.0 / (self.zoom * height as f64)
+    }
+}
+
+// Compares numbers numerically, so that e.g. `360.000000` matches `360`, and lists element wise
+fn is_same_value(value: &str, expected: &str) -> bool {
+    let numbers = |text: &str| -> Option<Vec<f64>> {
+        text.split(',')
+            .map(str::trim)
+            .filter(|number| !number.is_empty())
+            .map(|number| number.parse().ok())
+            .collect()
+    };
+    match (numbers(value), numbers(expected)) {
+        (Some(value), Some(expected)) => value == expected,
+        _ => false,
+    }
+}
+
+// Parses a colour table of comma separated r,g,b triples
+fn parse_colors(colors: &str) -> Option<Vec<(u8, u8, u8)>> {
+    let channels: Vec<u8> = colors
+        .split(',')
+        .map(str::trim)
+        .filter(|channel| !channel.is_empty())
+        .map(|channel| channel.parse().ok())
+        .collect::<Option<_>>()?;
+    if channels.is_empty() || !channels.len().is_multiple_of(3) {
+        return None;
+    }
+    Some(
+        channels
+            .chunks_exact(3)
+            .map(|rgb| (rgb[0], rgb[1], rgb[2]))
+            .collect(),
+    )
+}
@@ -5,13 +5,47 @@ mod utils;
 extern crate web_sys;
 use futures_channel::oneshot;
 use js_sys::Promise;
+use num::complex::Complex;
 use rayon::{ThreadPool, ThreadPoolBuilder};
 use std::cell::UnsafeCell;
+use std::error::Error;
 use std::sync::Arc;
 use wasm_bindgen::prelude::*;
 use web_sys::console;
 
+pub mod attractor;
+pub mod bookmarks;
+pub mod buddhabrot;
+pub mod density;
+pub mod dual;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod dzi;
+pub mod export;
+pub mod expression;
+pub mod flame;
+pub mod formula;
+pub mod fractal;
+pub mod fractint;
+pub mod history;
+pub mod ifs;
+pub mod iteration_data;
+pub mod julia;
+pub mod kfr;
+pub mod lsystem;
+pub mod lyapunov;
 pub mod mandelbrot;
+pub mod mesh;
+pub mod newton;
+pub mod palette;
+pub mod raymarch;
+pub mod resample;
+pub mod slippy;
+pub mod state;
+pub mod tiles;
+pub mod viewport;
+
+use fractal::RenderSettings;
+use resample::Interpolation;
 
 #[derive(Debug)]
 struct SyncUnsafeCell<T> {
@@ -48,6 +82,13 @@ pub struct Universe {
     cells_g: Arc<SyncUnsafeCell<Vec<u8>>>,
     cells_b: Arc<SyncUnsafeCell<Vec<u8>>>,
     position: Arc<SyncUnsafeCell<mandelbrot::Position>>,
+    settings: Arc<SyncUnsafeCell<RenderSettings>>,
+    interpolation: Interpolation,
+    tile_cache: Arc<SyncUnsafeCell<tiles::TileCache>>,
+    history: Arc<SyncUnsafeCell<history::History>>,
+    density: Arc<SyncUnsafeCell<Option<Box<dyn density::DensityRenderer>>>>,
+    ray_marcher: Arc<SyncUnsafeCell<Option<raymarch::RayMarcher>>>,
+    julia_origin: Arc<SyncUnsafeCell<Option<state::ViewState>>>,
 }
 
 // A macro to provide `println!(..)`-style syntax for `console.log` logging.
@@ -75,6 +116,7 @@ fn recalculate_cells(
     col_start: u32,
     col_end: u32,
     position: &mandelbrot::Position,
+    settings: &RenderSettings,
     cells_r: &mut Vec<u8>,
     cells_g: &mut Vec<u8>,
     cells_b: &mut Vec<u8>,
@@ -88,7 +130,8 @@ fn recalculate_cells(
         row_range.for_each(|row| {
             // log!("ForEach Col {:?}, Row {:?}", col, row);
             let idx = get_index(width, row, col);
-            let (r, g, b) = mandelbrot::mandelbrot_rgb_value(row, col, width, height, position);
+            let point = position.pixel_to_complex(row as f64, col as f64, width, height);
+            let (r, g, b) = settings.rgb_value_at(point);
             if r == cells_r[idx] && g == cells_g[idx] && b == cells_b[idx] {
                 // log!("No change");
             } else {
@@ -112,35 +155,797 @@ fn recalculate_cells(
 
 // Interanl functions NOT exposed to JS
 impl Universe {
-    fn get_index(&self, row: u32, column: u32) -> usize {
-        (row * self.width + column) as usize
+    // Creates a universe rendering on the given thread pool, which allows using it natively
+    pub fn with_thread_pool(
+        width: u32,
+        height: u32,
+        position: mandelbrot::Position,
+        thread_pool: ThreadPool,
+    ) -> Universe {
+        Universe::with_settings(width, height, position, RenderSettings::default(), thread_pool)
+    }
+
+    // Creates a universe showing the given settings from the start
+    pub fn with_settings(
+        width: u32,
+        height: u32,
+        position: mandelbrot::Position,
+        settings: RenderSettings,
+        thread_pool: ThreadPool,
+    ) -> Universe {
+        let size = (width * height) as usize;
+        let state = state::ViewState {
+            settings: settings.clone(),
+            position: position.clone(),
+            width,
+            height,
+        };
+        let history = history::History::new(
+            state,
+            history::DEFAULT_CAPACITY,
+            history::DEFAULT_FRAME_CAPACITY,
+        );
+        let universe = Universe {
+            width,
+            height,
+            pool: Arc::new(thread_pool),
+            cells_r: Arc::new(SyncUnsafeCell::new(vec![0; size])),
+            cells_g: Arc::new(SyncUnsafeCell::new(vec![0; size])),
+            cells_b: Arc::new(SyncUnsafeCell::new(vec![0; size])),
+            position: Arc::new(SyncUnsafeCell::new(position)),
+            settings: Arc::new(SyncUnsafeCell::new(settings)),
+            interpolation: Interpolation::Bilinear,
+            tile_cache: Arc::new(SyncUnsafeCell::new(tiles::TileCache::new(
+                tiles::DEFAULT_CACHE_CAPACITY,
+            ))),
+            history: Arc::new(SyncUnsafeCell::new(history)),
+            density: Arc::new(SyncUnsafeCell::new(None)),
+            ray_marcher: Arc::new(SyncUnsafeCell::new(None)),
+            julia_origin: Arc::new(SyncUnsafeCell::new(None)),
+        };
+        universe.update();
+        universe
+    }
+
+    pub fn get_position(&self) -> mandelbrot::Position {
+        self.position.get().clone()
+    }
+
+    pub fn screen(&self) -> density::Screen {
+        density::Screen::new(self.get_position(), self.width, self.height)
     }
+
+    // Switches the screen to the density image of the renderer. Nothing is drawn until samples
+    // are accumulated
+    pub fn start_density(&self, renderer: Box<dyn density::DensityRenderer>) {
+        *self.ray_marcher.get() = None;
+        *self.density.get() = Some(renderer);
+    }
+
+    // Traces more samples of the density image on the thread pool and draws it. Fails if the
+    // renderer cannot be restarted for a changed screen
+    pub fn draw_density(&self, samples: u64) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let cells = (self.cells_r.get(), self.cells_g.get(), self.cells_b.get());
+        let screen = self.screen();
+        self.pool
+            .install(|| accumulate_density(self.density.get(), screen, cells, samples))
+    }
+
+    // Shows the attractor of the system, coloured with the palette of the settings
+    pub fn start_chaos_game(&self, ifs: ifs::Ifs) {
+        *self.position.get() = ifs.fitting_position(self.width, self.height);
+        self.record_view();
+        let palette = self.get_settings().palette;
+        self.start_density(Box::new(ifs::ChaosGame::new(self.screen(), ifs, palette)));
+    }
+
+    // Shows the whole attractor, coloured with the palette of the settings
+    pub fn start_strange_attractor(&self, attractor: attractor::Attractor) {
+        *self.position.get() = attractor.fitting_position(self.width, self.height);
+        self.record_view();
+        let palette = self.get_settings().palette;
+        let renderer = attractor::AttractorRenderer::new(self.screen(), attractor, palette);
+        self.start_density(Box::new(renderer));
+    }
+
+    // Shows the camera of the flame, or its whole attractor if it has none
+    pub fn start_fractal_flame(&self, flame: flame::Flame) -> Result<(), flame::FlameError> {
+        let position = flame.fitting_position(self.width, self.height)?;
+        *self.position.get() = position;
+        self.record_view();
+        let renderer = flame::FlameRenderer::new(self.screen(), flame)?;
+        self.start_density(Box::new(renderer));
+        Ok(())
+    }
+
+    // Shows the lines drawn by the turtle, coloured by their depth with the palette of the settings
+    pub fn start_l_system(&self, system: &lsystem::LSystem) -> Result<(), lsystem::LSystemError> {
+        let segments = system.segments()?;
+        *self.position.get() = lsystem::fitting_position(&segments, self.width, self.height);
+        self.record_view();
+        let palette = self.get_settings().palette;
+        let renderer = lsystem::LSystemRenderer::new(self.screen(), segments, palette);
+        self.start_density(Box::new(renderer));
+        Ok(())
+    }
+
+    // Traces the fractal in space seen by the camera, coloured with the palette of the settings.
+    // The view in the plane stays where it is, nothing is drawn until pixels are traced
+    pub fn start_ray_marching(&self, solid: raymarch::Solid, camera: raymarch::Camera) {
+        let palette = self.get_settings().palette;
+        let marcher = raymarch::RayMarcher::new(self.width, self.height, solid, camera, palette);
+        *self.density.get() = None;
+        *self.ray_marcher.get() = Some(marcher);
+    }
+
+    // Moves the camera of the fractal in space, the image starts over
+    pub fn move_camera(&self, camera: raymarch::Camera) {
+        if let Some(marcher) = self.ray_marcher.get() {
+            if *marcher.camera() != camera {
+                *marcher = marcher.restart(self.width, self.height, camera);
+            }
+        }
+    }
+
+    // Traces more pixels of the fractal in space on the thread pool and draws them
+    pub fn draw_ray_marching(&self, samples: u64) {
+        let cells = (self.cells_r.get(), self.cells_g.get(), self.cells_b.get());
+        let size = (self.width, self.height);
+        self.pool
+            .install(|| trace_solid(self.ray_marcher.get(), size, cells, samples));
+    }
+
+    pub fn ray_marcher(&self) -> Option<&raymarch::RayMarcher> {
+        self.ray_marcher.get().as_ref()
+    }
+
+    // Preview of the Julia set of c with the settings of the view, rendered on the thread pool
+    pub fn julia_thumbnail(&self, c: Complex<f64>, width: u32, height: u32) -> Vec<u8> {
+        let settings = self.get_settings();
+        self.pool
+            .install(|| julia::thumbnail(c, &settings, width, height))
+    }
+
+    // Like `julia_thumbnail` for the point at the given pixel of the view
+    pub fn julia_thumbnail_at(&self, row: f64, col: f64, width: u32, height: u32) -> Vec<u8> {
+        let c = self
+            .position
+            .get()
+            .pixel_to_complex(row, col, self.width, self.height);
+        self.julia_thumbnail(c, width, height)
+    }
+
+    // Shows the whole Julia set of c and renders it. The view is remembered for `leave_julia`,
+    // unless it already shows a Julia set entered this way
+    pub fn enter_julia(&self, c: Complex<f64>) {
+        let origin = self.julia_origin.get();
+        if origin.is_none() {
+            *origin = Some(self.view_state());
+        }
+        *self.position.get() = julia::default_position(self.width, self.height);
+        self.set_settings(RenderSettings {
+            fractal: fractal::Fractal::Julia(c),
+            ..self.get_settings()
+        });
+        self.update();
+    }
+
+    // View shown before the first `enter_julia`, None if it has been left
+    pub fn julia_origin(&self) -> Option<state::ViewState> {
+        self.julia_origin.get().clone()
+    }
+
+    pub fn density(&self) -> Option<&dyn density::DensityRenderer> {
+        self.density.get().as_deref()
+    }
+
+    fn set_newton(
+        &self,
+        newton: Result<newton::Newton, newton::NewtonError>,
+        relaxation: f64,
+        nova: bool,
+    ) -> Result<(), JsValue> {
+        let newton = newton
+            .map_err(|err| JsValue::from(err.to_string()))?
+            .with_relaxation(Complex::new(relaxation, 0.0))
+            .with_nova(nova);
+        self.set_settings(RenderSettings {
+            fractal: fractal::Fractal::Newton(Arc::new(newton)),
+            ..self.get_settings()
+        });
+        Ok(())
+    }
+
+    pub fn get_settings(&self) -> RenderSettings {
+        self.settings.get().clone()
+    }
+
+    // Changes what is rendered, cached tiles of the previous settings are dropped. Call `update`
+    // to render the view with the new settings
+    pub fn set_settings(&self, settings: RenderSettings) {
+        self.apply_settings(settings);
+        self.record_view();
+    }
+
+    fn apply_settings(&self, settings: RenderSettings) {
+        let current = &mut self.settings.get();
+        if **current != settings {
+            **current = settings;
+            self.tile_cache.get().clear();
+        }
+    }
+
+    // Makes the current view the latest entry of the navigation history
+    fn record_view(&self) {
+        self.forget_julia_origin();
+        self.history.get().record(self.view_state());
+    }
+
+    // Forgets the view `leave_julia` returns to once the view no longer shows a Julia set
+    fn forget_julia_origin(&self) {
+        if !matches!(self.settings.get().fractal, fractal::Fractal::Julia(_)) {
+            *self.julia_origin.get() = None;
+        }
+    }
+
+    // Moves to a view of the history. Its cells are restored from the frame kept for it if there
+    // is one, otherwise the view is rendered again
+    fn show_history_entry(&self, entry: history::HistoryEntry) {
+        let state = entry.state;
+        let mut position = state.position;
+        let same_screen = (state.width, state.height) == (self.width, self.height);
+        if !same_screen {
+            position.resize(state.width, state.height, self.width, self.height);
+        }
+        *self.position.get() = position;
+        self.apply_settings(state.settings);
+        self.forget_julia_origin();
+        match entry.frame {
+            Some(frame) if same_screen => {
+                *self.cells_r.get() = frame.cells_r;
+                *self.cells_g.get() = frame.cells_g;
+                *self.cells_b.get() = frame.cells_b;
+            }
+            _ => self.update(),
+        }
+    }
+
+    // RGBA bytes of the cells in row major order
+    pub fn rgba(&self) -> Vec<u8> {
+        let cells_r = self.cells_r.get();
+        let cells_g = self.cells_g.get();
+        let cells_b = self.cells_b.get();
+        let mut rgba = Vec::with_capacity(cells_r.len() * 4);
+        for idx in 0..cells_r.len() {
+            rgba.extend_from_slice(&[cells_r[idx], cells_g[idx], cells_b[idx], 255]);
+        }
+        rgba
+    }
+
+    pub fn view_metadata(&self) -> export::ViewMetadata {
+        export::ViewMetadata {
+            settings: self.get_settings(),
+            centre: self.position.get().centre(self.width, self.height),
+            zoom_factor: self.position.get().get_zoom_factor(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    // Moves to the view described by the metadata and renders it. If the metadata was recorded
+    // with another screen size the centre and the scale along the real axis are kept
+    pub fn restore_view(&self, metadata: &export::ViewMetadata) {
+        let mut position = mandelbrot::Position::from_centre(
+            metadata.centre,
+            metadata.zoom_factor,
+            metadata.width,
+            metadata.height,
+        );
+        position.resize(metadata.width, metadata.height, self.width, self.height);
+        *self.position.get() = position;
+        self.set_settings(metadata.settings.clone());
+        self.update();
+    }
+
+    // Moves to the view of the state and renders it. If the state was recorded with another
+    // screen size the centre and the scale along the real axis are kept
+    pub fn restore_state(&self, state: &state::ViewState) {
+        let mut position = state.position.clone();
+        position.resize(state.width, state.height, self.width, self.height);
+        *self.position.get() = position;
+        self.set_settings(state.settings.clone());
+        self.update();
+    }
+
+    // Bookmarks the current view with a thumbnail downsampled from the cells
+    pub fn bookmark(
+        &self,
+        name: &str,
+        tags: Vec<String>,
+    ) -> Result<bookmarks::Bookmark, png::EncodingError> {
+        let width = bookmarks::THUMBNAIL_WIDTH.min(self.width);
+        let height = (self.height as f64 * width as f64 / self.width as f64).round() as u32;
+        let height = height.max(1);
+        let scale = (self.width as f64 / width as f64, self.height as f64 / height as f64);
+        let mut channels = Vec::new();
+        for cells_mutex in [&self.cells_r, &self.cells_g, &self.cells_b] {
+            let mut channel = vec![0; (width * height) as usize];
+            resample::resample(
+                cells_mutex.get(),
+                (self.width, self.height),
+                &mut channel,
+                (width, height),
+                Interpolation::Bilinear,
+                |row, col| (row as f64 * scale.1, col as f64 * scale.0),
+            );
+            channels.push(channel);
+        }
+        let rgba: Vec<u8> = channels[0]
+            .iter()
+            .zip(&channels[1])
+            .zip(&channels[2])
+            .flat_map(|((r, g), b)| [*r, *g, *b, 255])
+            .collect();
+        Ok(bookmarks::Bookmark {
+            name: name.to_string(),
+            tags,
+            thumbnail: Some(export::encode_png(width, height, &rgba)?),
+            state: self.view_state(),
+        })
+    }
+
+    pub fn view_state(&self) -> state::ViewState {
+        state::ViewState {
+            settings: self.get_settings(),
+            position: self.get_position(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    // Encodes the cells as PNG recording the current view in its metadata
+    pub fn encode_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        export::encode_view_png(&self.rgba(), &self.view_metadata())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_png(&self, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(path, self.encode_png()?)?;
+        Ok(())
+    }
+
+    // Restores the view recorded in a PNG written by `save_png`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_png(&self, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        let metadata = export::read_view_png(&std::fs::read(path)?)?;
+        self.restore_view(&metadata);
+        Ok(())
+    }
+
+    // Replaces the cells with a resampled version of the image rendered at `previous`, so that
+    // they approximate the current position until the next `update`
+    fn preview_from(&self, previous: &mandelbrot::Position) {
+        let position_mutex = self.position.clone();
+        let position = &position_mutex.get();
+        let width = self.width;
+        let height = self.height;
+        let source_pixel = |row: u32, col: u32| {
+            let point = position.pixel_to_complex(row as f64, col as f64, width, height);
+            previous.complex_to_pixel(point, width, height)
+        };
+        for cells_mutex in [&self.cells_r, &self.cells_g, &self.cells_b] {
+            let cells = &mut cells_mutex.get();
+            let source = cells.clone();
+            resample::resample(
+                &source,
+                (width, height),
+                cells,
+                (width, height),
+                self.interpolation,
+                source_pixel,
+            );
+        }
+    }
+}
+
+// Keeps the cells as frame of the current history entry if they show the view of that entry
+fn remember_frame(
+    history: &mut history::History,
+    position: &mandelbrot::Position,
+    settings: &RenderSettings,
+    (width, height): (u32, u32),
+    (cells_r, cells_g, cells_b): (&[u8], &[u8], &[u8]),
+) {
+    let state = state::ViewState {
+        settings: settings.clone(),
+        position: position.clone(),
+        width,
+        height,
+    };
+    // The cells are only copied once for every recorded view
+    if history.wants_frame(&state) {
+        let frame = history::Frame {
+            cells_r: cells_r.to_vec(),
+            cells_g: cells_g.to_vec(),
+            cells_b: cells_b.to_vec(),
+        };
+        history.store_frame(&state, frame);
+    }
+}
+
+// Adds samples to the density image and draws it. The image starts over if the view has changed
+// since it was started
+fn accumulate_density(
+    renderer: &mut Option<Box<dyn density::DensityRenderer>>,
+    screen: density::Screen,
+    (cells_r, cells_g, cells_b): (&mut Vec<u8>, &mut Vec<u8>, &mut Vec<u8>),
+    samples: u64,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let renderer = match renderer {
+        Some(renderer) => renderer,
+        None => return Ok(()),
+    };
+    if *renderer.screen() != screen {
+        *renderer = renderer.restart(screen)?;
+    }
+    renderer.accumulate(samples);
+    let (r, g, b) = renderer.to_cells();
+    *cells_r = r;
+    *cells_g = g;
+    *cells_b = b;
+    Ok(())
+}
+
+// Traces more pixels of the fractal in space and draws them. The image only starts over if the
+// screen size has changed, panning and zooming the plane does not move the camera
+fn trace_solid(
+    marcher: &mut Option<raymarch::RayMarcher>,
+    (width, height): (u32, u32),
+    (cells_r, cells_g, cells_b): (&mut Vec<u8>, &mut Vec<u8>, &mut Vec<u8>),
+    samples: u64,
+) {
+    let marcher = match marcher {
+        Some(marcher) => marcher,
+        None => return,
+    };
+    if marcher.size() != (width, height) {
+        *marcher = marcher.restart(width, height, marcher.camera().clone());
+    }
+    marcher.accumulate(samples);
+    let (r, g, b) = marcher.to_cells();
+    *cells_r = r;
+    *cells_g = g;
+    *cells_b = b;
+}
+
+// Configures a rayon thread pool which will pull web workers from the pool
+fn web_thread_pool(pool: &pool::WorkerPool, threads: usize) -> ThreadPool {
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .spawn_handler(|thread| Ok(pool.run(|| thread.run()).unwrap()))
+        .build()
+        .unwrap()
+}
+
+// Returns the range of `0..size` for which `offset + i` lies within `0..source_size`
+fn covered_range(offset: i64, size: u32, source_size: u32) -> (u32, u32) {
+    let start = (-offset).max(0).min(size as i64);
+    let end = (source_size as i64 - offset).max(start).min(size as i64);
+    (start as u32, end as u32)
+}
+
+fn is_whole_pixel(value: f64) -> bool {
+    (value - value.round()).abs() < 1e-6
+}
+
+// Complex numbers from pairs of real and imaginary parts, an unpaired last value is ignored
+fn complex_pairs(values: &[f64]) -> Vec<Complex<f64>> {
+    values
+        .chunks_exact(2)
+        .map(|pair| Complex::new(pair[0], pair[1]))
+        .collect()
 }
 
 /// Public methods, exported to JavaScript.
 #[wasm_bindgen]
 impl Universe {
+    // Draws the current view tile by tile on the calling thread, reusing cached tiles
     pub fn update(&self) {
         let position_mutex = self.position.clone();
         let cells_r_mutex = self.cells_r.clone();
         let cells_g_mutex = self.cells_g.clone();
         let cells_b_mutex = self.cells_b.clone();
+        let settings_mutex = self.settings.clone();
+        let tile_cache_mutex = self.tile_cache.clone();
         let position = &mut position_mutex.get();
+        let settings = &settings_mutex.get();
         let cells_r = &mut cells_r_mutex.get();
         let cells_g = &mut cells_g_mutex.get();
         let cells_b = &mut cells_b_mutex.get();
-        recalculate_cells(
-            0,
-            self.height,
-            0,
-            self.width,
+        let tile_cache = &mut tile_cache_mutex.get();
+        tiles::draw_view(
             position,
-            cells_r,
-            cells_g,
-            cells_b,
+            settings,
+            tile_cache,
+            (cells_r, cells_g, cells_b),
             self.width,
             self.height,
+            tiles::render_tiles,
         );
+        remember_frame(
+            self.history.get(),
+            position,
+            settings,
+            (self.width, self.height),
+            (cells_r, cells_g, cells_b),
+        );
+    }
+
+    // Same as `update` but renders missing tiles in parallel on the thread pool, centre first
+    pub fn render(&self, pool: &pool::WorkerPool) -> Result<Promise, JsValue> {
+        let (tx, rx) = oneshot::channel();
+        let position_mutex = self.position.clone();
+        let cells_r_mutex = self.cells_r.clone();
+        let cells_g_mutex = self.cells_g.clone();
+        let cells_b_mutex = self.cells_b.clone();
+        let settings_mutex = self.settings.clone();
+        let tile_cache_mutex = self.tile_cache.clone();
+        let history_mutex = self.history.clone();
+        let width = self.width;
+        let height = self.height;
+        let thread_pool = self.pool.clone();
+
+        pool.run(move || {
+            thread_pool.install(|| {
+                let position = &mut position_mutex.get();
+                let settings = &settings_mutex.get();
+                let cells_r = &mut cells_r_mutex.get();
+                let cells_g = &mut cells_g_mutex.get();
+                let cells_b = &mut cells_b_mutex.get();
+                let tile_cache = &mut tile_cache_mutex.get();
+                tiles::draw_view(
+                    position,
+                    settings,
+                    tile_cache,
+                    (cells_r, cells_g, cells_b),
+                    width,
+                    height,
+                    tiles::render_tiles_parallel,
+                );
+                remember_frame(
+                    history_mutex.get(),
+                    position,
+                    settings,
+                    (width, height),
+                    (cells_r, cells_g, cells_b),
+                );
+                tx.send(()).unwrap();
+            });
+        })?;
+
+        let done = async move {
+            match rx.await {
+                Ok(()) => Ok(JsValue::undefined()),
+                Err(_) => Err(JsValue::undefined()),
+            }
+        };
+        Ok(wasm_bindgen_futures::future_to_promise(done))
+    }
+
+    // Limits the number of rendered tiles kept around for revisiting parts of the plane
+    pub fn set_tile_cache_capacity(&self, capacity: usize) {
+        let tile_cache_mutex = self.tile_cache.clone();
+        tile_cache_mutex.get().set_capacity(capacity);
+    }
+
+    // Starts a Buddhabrot of the current view, counting orbits of up to `max_iterations` in all
+    // channels. Call `accumulate_density` to draw it progressively
+    pub fn start_buddhabrot(&self, max_iterations: u32) {
+        let bands = buddhabrot::buddhabrot_bands(max_iterations);
+        self.start_density(Box::new(buddhabrot::Buddhabrot::new(self.screen(), bands)));
+    }
+
+    // Like `start_buddhabrot` with long orbits in red, medium ones in green and short ones in blue
+    pub fn start_nebulabrot(&self) {
+        let bands = buddhabrot::nebulabrot_bands();
+        self.start_density(Box::new(buddhabrot::Buddhabrot::new(self.screen(), bands)));
+    }
+
+    // Returns to the escape time rendering of the view from a density image or a fractal in
+    // space
+    pub fn stop_density(&self) {
+        *self.density.get() = None;
+        *self.ray_marcher.get() = None;
+        self.update();
+    }
+
+    pub fn density_samples(&self) -> f64 {
+        self.density().map_or(0.0, |renderer| renderer.samples() as f64)
+    }
+
+    // Starts the chaos game of an iterated function system written in the text format of `ifs`
+    // and moves the view to its attractor. Call `accumulate_density` to draw it progressively
+    pub fn start_ifs(&self, text: &str) -> Result<(), JsValue> {
+        let ifs = ifs::Ifs::parse(text).map_err(|err| JsValue::from(err.to_string()))?;
+        self.start_chaos_game(ifs);
+        Ok(())
+    }
+
+    // Like `start_ifs` with the maps of a JSON file
+    pub fn start_ifs_json(&self, json: &str) -> Result<(), JsValue> {
+        let ifs = ifs::Ifs::from_json(json).map_err(|err| JsValue::from(err.to_string()))?;
+        self.start_chaos_game(ifs);
+        Ok(())
+    }
+
+    // Like `start_ifs` with one of the systems of `ifs::PRESETS`
+    pub fn start_ifs_preset(&self, name: &str) -> Result<(), JsValue> {
+        let ifs = ifs::Ifs::preset(name).map_err(|err| JsValue::from(err.to_string()))?;
+        self.start_chaos_game(ifs);
+        Ok(())
+    }
+
+    // Starts tracing the orbits of one of the `attractor_types` with parameters in the order of
+    // `attractor_parameter_names`, or the default ones if `parameters` is empty. Flows in space
+    // are projected onto the plane `projection`, "xy", "xz" or "yz", or the usual one if it is
+    // empty. Call `accumulate_density` to draw it progressively
+    pub fn start_attractor(
+        &self,
+        name: &str,
+        parameters: &[f64],
+        projection: &str,
+    ) -> Result<(), JsValue> {
+        let to_js = |err: attractor::AttractorError| JsValue::from(err.to_string());
+        let mut attractor = attractor::Attractor::from_name(name, parameters).map_err(to_js)?;
+        if !projection.is_empty() {
+            let projection = attractor::Projection::from_name(projection).ok_or_else(|| {
+                to_js(attractor::AttractorError::UnknownProjection(
+                    projection.to_string(),
+                ))
+            })?;
+            attractor = attractor.with_projection(projection);
+        }
+        self.start_strange_attractor(attractor);
+        Ok(())
+    }
+
+    // Starts rendering the flame with the given name of a `.flame` file, or its first flame if
+    // the name is empty. Call `accumulate_density` to draw it progressively
+    pub fn start_flame(&self, xml: &str, name: &str) -> Result<(), JsValue> {
+        let to_js = |err: flame::FlameError| JsValue::from(err.to_string());
+        let flame = flame::Flame::parse(xml, name).map_err(to_js)?;
+        self.start_fractal_flame(flame).map_err(to_js)
+    }
+
+    // Expands an L-system written in the text format of `lsystem` and moves the view to its
+    // lines. Call `accumulate_density` with the number of lines to draw per frame
+    pub fn start_lsystem(&self, text: &str) -> Result<(), JsValue> {
+        let to_js = |err: lsystem::LSystemError| JsValue::from(err.to_string());
+        let system = lsystem::LSystem::parse(text).map_err(to_js)?;
+        self.start_l_system(&system).map_err(to_js)
+    }
+
+    // Like `start_lsystem` with one of the `lsystem_presets`
+    pub fn start_lsystem_preset(&self, name: &str) -> Result<(), JsValue> {
+        let to_js = |err: lsystem::LSystemError| JsValue::from(err.to_string());
+        let system = lsystem::LSystem::preset(name).map_err(to_js)?;
+        self.start_l_system(&system).map_err(to_js)
+    }
+
+    // Lines of an L-system as an SVG image of `size` pixels on the longer side
+    pub fn lsystem_svg(&self, text: &str, size: u32) -> Result<String, JsValue> {
+        let to_js = |err: lsystem::LSystemError| JsValue::from(err.to_string());
+        let segments = lsystem::LSystem::parse(text)
+            .and_then(|system| system.segments())
+            .map_err(to_js)?;
+        Ok(lsystem::to_svg(&segments, size))
+    }
+
+    // Starts ray marching one of the `solid_types` with parameters in the order of
+    // `solid_parameter_names`, or the default ones if `parameters` is empty. `camera` holds the
+    // position, the point looked at and the vertical field of view in degrees, or is empty for a
+    // view of the whole fractal. Call `trace_solid` with the number of pixels to trace per frame
+    // to draw it progressively
+    pub fn start_solid(
+        &self,
+        name: &str,
+        parameters: &[f64],
+        camera: &[f64],
+    ) -> Result<(), JsValue> {
+        let to_js = |err: raymarch::RayMarchError| JsValue::from(err.to_string());
+        let solid = raymarch::Solid::from_name(name, parameters).map_err(to_js)?;
+        let camera = match camera {
+            [] => solid.default_camera(),
+            values => raymarch::Camera::from_values(values).map_err(to_js)?,
+        };
+        self.start_ray_marching(solid, camera);
+        Ok(())
+    }
+
+    // Moves the camera of the fractal in space to the position, the point looked at and the
+    // vertical field of view like in `start_solid`
+    pub fn set_solid_camera(&self, camera: &[f64]) -> Result<(), JsValue> {
+        let camera = raymarch::Camera::from_values(camera)
+            .map_err(|err| JsValue::from(err.to_string()))?;
+        self.move_camera(camera);
+        Ok(())
+    }
+
+    pub fn traced_pixels(&self) -> f64 {
+        self.ray_marcher().map_or(0.0, |marcher| marcher.samples() as f64)
+    }
+
+    // Traces `samples` more pixels of the fractal in space on the worker pool and draws the image
+    // with all pixels so far, e.g. once per animation frame
+    pub fn trace_solid(&self, pool: &pool::WorkerPool, samples: u32) -> Result<Promise, JsValue> {
+        let (tx, rx) = oneshot::channel();
+        let cells_r_mutex = self.cells_r.clone();
+        let cells_g_mutex = self.cells_g.clone();
+        let cells_b_mutex = self.cells_b.clone();
+        let ray_marcher_mutex = self.ray_marcher.clone();
+        let size = (self.width, self.height);
+        let thread_pool = self.pool.clone();
+
+        pool.run(move || {
+            thread_pool.install(|| {
+                let cells = (
+                    cells_r_mutex.get(),
+                    cells_g_mutex.get(),
+                    cells_b_mutex.get(),
+                );
+                trace_solid(ray_marcher_mutex.get(), size, cells, samples as u64);
+                tx.send(()).unwrap();
+            });
+        })?;
+
+        let done = async move {
+            match rx.await {
+                Ok(()) => Ok(JsValue::undefined()),
+                Err(_) => Err(JsValue::undefined()),
+            }
+        };
+        Ok(wasm_bindgen_futures::future_to_promise(done))
+    }
+
+    // Adds `samples` more samples to the density image on the worker pool and draws the image
+    // with all samples so far, e.g. once per animation frame
+    pub fn accumulate_density(
+        &self,
+        pool: &pool::WorkerPool,
+        samples: u32,
+    ) -> Result<Promise, JsValue> {
+        let (tx, rx) = oneshot::channel();
+        let position_mutex = self.position.clone();
+        let cells_r_mutex = self.cells_r.clone();
+        let cells_g_mutex = self.cells_g.clone();
+        let cells_b_mutex = self.cells_b.clone();
+        let density_mutex = self.density.clone();
+        let width = self.width;
+        let height = self.height;
+        let thread_pool = self.pool.clone();
+
+        pool.run(move || {
+            thread_pool.install(|| {
+                let cells = (
+                    cells_r_mutex.get(),
+                    cells_g_mutex.get(),
+                    cells_b_mutex.get(),
+                );
+                let screen = density::Screen::new(position_mutex.get().clone(), width, height);
+                let result =
+                    accumulate_density(density_mutex.get(), screen, cells, samples as u64);
+                tx.send(result.map_err(|err| err.to_string())).unwrap();
+            });
+        })?;
+
+        let done = async move {
+            match rx.await {
+                Ok(Ok(())) => Ok(JsValue::undefined()),
+                Ok(Err(message)) => Err(JsValue::from(message)),
+                Err(_) => Err(JsValue::undefined()),
+            }
+        };
+        Ok(wasm_bindgen_futures::future_to_promise(done))
     }
 
     pub fn new(
@@ -154,32 +959,39 @@ impl Universe {
     ) -> Universe {
         utils::set_panic_hook();
         let position = mandelbrot::Position::new(x, y, zoom);
+        Universe::with_thread_pool(width, height, position, web_thread_pool(pool, threads))
+    }
 
-        let mut cells_r = Vec::with_capacity((width * height) as usize);
-        cells_r.resize((width * height) as usize, 0);
-        let mut cells_g = Vec::with_capacity((width * height) as usize);
-        cells_g.resize((width * height) as usize, 0);
-        let mut cells_b = Vec::with_capacity((width * height) as usize);
-        cells_b.resize((width * height) as usize, 0);
-
-        // Configure a rayon thread pool which will pull web workers from
-        let thread_pool = ThreadPoolBuilder::new()
-            .num_threads(threads)
-            .spawn_handler(|thread| Ok(pool.run(|| thread.run()).unwrap()))
-            .build()
-            .unwrap();
-
-        let universe = Universe {
+    // Creates a universe showing the view serialised by `to_state`. If the state was recorded with
+    // another screen size the centre and the scale along the real axis are kept, otherwise the
+    // render is identical
+    pub fn from_state(
+        state: &str,
+        width: u32,
+        height: u32,
+        pool: &pool::WorkerPool,
+        threads: usize,
+    ) -> Result<Universe, JsValue> {
+        utils::set_panic_hook();
+        let state =
+            state::ViewState::decode(state).map_err(|err| JsValue::from(err.to_string()))?;
+        let mut position = state.position;
+        if (state.width, state.height) != (width, height) {
+            position.resize(state.width, state.height, width, height);
+        }
+        let thread_pool = web_thread_pool(pool, threads);
+        Ok(Universe::with_settings(
             width,
             height,
-            pool: Arc::new(thread_pool),
-            cells_r: Arc::new(SyncUnsafeCell::new(cells_r)),
-            cells_g: Arc::new(SyncUnsafeCell::new(cells_g)),
-            cells_b: Arc::new(SyncUnsafeCell::new(cells_b)),
-            position: Arc::new(SyncUnsafeCell::new(position)),
-        };
-        universe.update();
-        return universe;
+            position,
+            state.settings,
+            thread_pool,
+        ))
+    }
+
+    // Compact URL-safe string of the current view, see `state` for the format
+    pub fn to_state(&self) -> String {
+        self.view_state().encode()
     }
 
     pub fn width(&self) -> u32 {
@@ -208,16 +1020,374 @@ impl Universe {
         cells_b.as_ptr()
     }
 
-    pub fn zoom_in(self) -> f64 {
+    // Resizes the screen without recreating the thread pool and renders it. The centre and scale
+    // of the view are kept and cells that are still visible are reused, the whole screen is
+    // rendered again if the new pixels do not line up with the old ones. Empty screens, e.g. of
+    // minimised windows, are ignored
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let position_mutex = self.position.clone();
+        let position = &mut position_mutex.get();
+        let previous = position.clone();
+        let (old_width, old_height) = (self.width, self.height);
+        position.resize(old_width, old_height, width, height);
+
+        let source_pixel = |row: u32, col: u32| {
+            let point = position.pixel_to_complex(row as f64, col as f64, width, height);
+            previous.complex_to_pixel(point, old_width, old_height)
+        };
+        // Old cells can be copied as they are if the new pixels are a whole pixel translation
+        let (row_offset, col_offset) = source_pixel(0, 0);
+        let (next_row, next_col) = source_pixel(1, 1);
+        let aligned = is_whole_pixel(row_offset)
+            && is_whole_pixel(col_offset)
+            && is_whole_pixel(next_row - row_offset - 1.0)
+            && is_whole_pixel(next_col - col_offset - 1.0);
+        let interpolation = if aligned {
+            Interpolation::Nearest
+        } else {
+            self.interpolation
+        };
+
+        for cells_mutex in [&self.cells_r, &self.cells_g, &self.cells_b] {
+            let cells = &mut cells_mutex.get();
+            let mut resized = vec![0; (width * height) as usize];
+            resample::resample(
+                cells,
+                (old_width, old_height),
+                &mut resized,
+                (width, height),
+                interpolation,
+                source_pixel,
+            );
+            **cells = resized;
+        }
+        self.width = width;
+        self.height = height;
+        // Resizing shows the same view, so it is not an undo step of its own
+        let view_state = self.view_state();
+        self.history.get().replace_current(view_state);
+
+        if !aligned {
+            self.update();
+            return;
+        }
+        let (row_start, row_end) = covered_range(row_offset.round() as i64, height, old_height);
+        let (col_start, col_end) = covered_range(col_offset.round() as i64, width, old_width);
+        let settings = &self.settings.get();
+        let cells_r = &mut self.cells_r.get();
+        let cells_g = &mut self.cells_g.get();
+        let cells_b = &mut self.cells_b.get();
+        // Only the strips around the reused cells have to be calculated
+        let strips = [
+            (0, row_start, 0, width),
+            (row_end, height, 0, width),
+            (row_start, row_end, 0, col_start),
+            (row_start, row_end, col_end, width),
+        ];
+        for (row_start, row_end, col_start, col_end) in strips {
+            recalculate_cells(
+                row_start, row_end, col_start, col_end, position, settings, cells_r, cells_g,
+                cells_b, width, height,
+            );
+        }
+    }
+
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
+
+    pub fn fractal(&self) -> String {
+        self.settings.get().fractal.name().to_string()
+    }
+
+    // The setters below drop cached tiles, call `update` to render the view with the change.
+    // Fractals with parameters can be selected by their `Fractal::spec`
+    pub fn set_fractal(&self, name: &str) -> Result<(), JsValue> {
+        let fractal = fractal::Fractal::from_spec(name)
+            .ok_or_else(|| JsValue::from(format!("unknown fractal '{}'", name)))?;
+        self.set_settings(RenderSettings {
+            fractal,
+            ..self.get_settings()
+        });
+        Ok(())
+    }
+
+    // Source of the formula iterated by the view, if it shows a formula
+    pub fn formula(&self) -> Option<String> {
+        match &self.settings.get().fractal {
+            fractal::Fractal::Formula(formula) => Some(formula.source().to_string()),
+            _ => None,
+        }
+    }
+
+    // Iterates the formula written by the user, see `formula` for the language. Errors describe
+    // the line and column of the problem
+    pub fn set_formula(&self, source: &str) -> Result<(), JsValue> {
+        let formula =
+            formula::Formula::compile(source).map_err(|err| JsValue::from(err.to_string()))?;
+        self.set_settings(RenderSettings {
+            fractal: fractal::Fractal::Formula(Arc::new(formula)),
+            ..self.get_settings()
+        });
+        Ok(())
+    }
+
+    // Newton fractal of the polynomial with the roots, given as pairs of real and imaginary
+    // parts. `relaxation` scales the Newton steps and `nova` selects the Nova variant
+    pub fn set_newton_roots(
+        &self,
+        roots: &[f64],
+        relaxation: f64,
+        nova: bool,
+    ) -> Result<(), JsValue> {
+        let roots = complex_pairs(roots);
+        self.set_newton(newton::Newton::from_roots(&roots), relaxation, nova)
+    }
+
+    // Like `set_newton_roots` with the coefficients of the polynomial from the constant term up
+    pub fn set_newton_coefficients(
+        &self,
+        coefficients: &[f64],
+        relaxation: f64,
+        nova: bool,
+    ) -> Result<(), JsValue> {
+        let coefficients = complex_pairs(coefficients);
+        self.set_newton(newton::Newton::from_coefficients(&coefficients), relaxation, nova)
+    }
+
+    // Lyapunov fractal of the sequence of A and B over the region `a_min..a_max` along the
+    // rows and `b_min..b_max` along the columns, rendered right away
+    pub fn set_lyapunov(
+        &self,
+        sequence: &str,
+        a_min: f64,
+        a_max: f64,
+        b_min: f64,
+        b_max: f64,
+    ) -> Result<(), JsValue> {
+        let to_js = |err: lyapunov::LyapunovError| JsValue::from(err.to_string());
+        let lyapunov = lyapunov::Lyapunov::new(sequence).map_err(to_js)?;
+        let region = lyapunov::Region {
+            a_min,
+            a_max,
+            b_min,
+            b_max,
+        };
+        let settings = RenderSettings {
+            fractal: fractal::Fractal::Lyapunov(Arc::new(lyapunov)),
+            ..self.get_settings()
+        };
+        let metadata = region
+            .view_metadata(settings, self.width, self.height)
+            .map_err(to_js)?;
+        self.restore_view(&metadata);
+        Ok(())
+    }
+
+    pub fn max_iterations(&self) -> u32 {
+        self.settings.get().max_iterations
+    }
+
+    pub fn set_max_iterations(&self, max_iterations: u32) {
+        self.set_settings(RenderSettings {
+            max_iterations,
+            ..self.get_settings()
+        });
+    }
+
+    pub fn palette(&self) -> String {
+        self.settings.get().palette.name().to_string()
+    }
+
+    pub fn set_palette(&self, name: &str) -> Result<(), JsValue> {
+        let palette = palette::Palette::from_name(name)
+            .ok_or_else(|| JsValue::from(format!("unknown palette '{}'", name)))?;
+        self.set_settings(RenderSettings {
+            palette,
+            ..self.get_settings()
+        });
+        Ok(())
+    }
+
+    // PNG of the current cells with the view recorded in tEXt chunks, e.g. for creating a Blob
+    pub fn to_png(&self) -> Result<Vec<u8>, JsValue> {
+        self.encode_png()
+            .map_err(|err| JsValue::from(err.to_string()))
+    }
+
+    // Restores the view recorded in a PNG created by `to_png`
+    pub fn load_png(&self, bytes: &[u8]) -> Result<(), JsValue> {
+        let metadata = export::read_view_png(bytes).map_err(|err| JsValue::from(err.to_string()))?;
+        self.restore_view(&metadata);
+        Ok(())
+    }
+
+    // The current view as a Kalles Fraktaler location file
+    pub fn to_kfr(&self) -> String {
+        kfr::KfrLocation::from_view_metadata(&self.view_metadata()).to_text()
+    }
+
+    // Moves to the location of a Kalles Fraktaler location file
+    pub fn load_kfr(&self, text: &str) -> Result<(), JsValue> {
+        let location = kfr::KfrLocation::parse(text).map_err(|err| JsValue::from(err.to_string()))?;
+        self.restore_view(&location.view_metadata(self.width, self.height));
+        Ok(())
+    }
+
+    // Moves to the entry with the given name of a Fractint parameter file. Formulas of
+    // `type=formula` entries are looked up in the formula file `frm`, which may be empty
+    pub fn load_par(&self, par: &str, name: &str, frm: &str) -> Result<(), JsValue> {
+        let to_js = |err: fractint::FractintError| JsValue::from(err.to_string());
+        let formulas = fractint::FormulaFile::parse(frm).map_err(to_js)?;
+        let entry = fractint::parse_par(par)
+            .map_err(to_js)?
+            .into_iter()
+            .find(|entry| entry.name == name)
+            .ok_or_else(|| JsValue::from(format!("no entry '{}' in parameter file", name)))?;
+        let location = entry.location(&formulas).map_err(to_js)?;
+        self.restore_view(&location.view_metadata(self.width, self.height));
+        Ok(())
+    }
+
+    // Moves to one of the built-in `famous_locations`, keeping the palette
+    pub fn go_to_location(&self, name: &str) -> Result<(), JsValue> {
+        let location = bookmarks::famous_location(name)
+            .ok_or_else(|| JsValue::from(format!("unknown location '{}'", name)))?;
+        let mut metadata = location.view_metadata(self.width, self.height);
+        metadata.settings.palette = self.get_settings().palette;
+        self.restore_view(&metadata);
+        Ok(())
+    }
+
+    // Preview of the Julia set of the point at the given pixel of the Mandelbrot set, e.g. the one
+    // under the pointer, rendered on the worker pool. Resolves to the RGBA bytes of a `width` x
+    // `height` image, fails if the view shows another fractal
+    pub fn julia_preview(
+        &self,
+        pool: &pool::WorkerPool,
+        row: f64,
+        col: f64,
+        width: u32,
+        height: u32,
+    ) -> Result<Promise, JsValue> {
+        if !matches!(self.settings.get().fractal, fractal::Fractal::Mandelbrot) {
+            return Err(JsValue::from("Julia sets are previewed from the Mandelbrot set"));
+        }
+        let (tx, rx) = oneshot::channel();
+        let c = self
+            .position
+            .get()
+            .pixel_to_complex(row, col, self.width, self.height);
+        let settings = self.get_settings();
+        let thread_pool = self.pool.clone();
+
+        pool.run(move || {
+            thread_pool.install(|| {
+                tx.send(julia::thumbnail(c, &settings, width, height)).unwrap();
+            });
+        })?;
+
+        let done = async move {
+            match rx.await {
+                Ok(rgba) => Ok(js_sys::Uint8Array::from(&rgba[..]).into()),
+                Err(_) => Err(JsValue::undefined()),
+            }
+        };
+        Ok(wasm_bindgen_futures::future_to_promise(done))
+    }
+
+    // Switches the view to the Julia set of the point at the given pixel and renders it, fails
+    // for points too far from the Mandelbrot set to have one worth showing
+    pub fn show_julia(&self, row: f64, col: f64) -> Result<(), JsValue> {
+        let c = self
+            .position
+            .get()
+            .pixel_to_complex(row, col, self.width, self.height);
+        if !julia::is_valid_parameter(c) {
+            return Err(JsValue::from(format!("no Julia set for c = {}", c)));
+        }
+        self.enter_julia(c);
+        Ok(())
+    }
+
+    // Returns to the view the first `show_julia` came from and renders it, false if there is none
+    pub fn leave_julia(&self) -> bool {
+        match self.julia_origin.get().take() {
+            Some(origin) => {
+                self.restore_state(&origin);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Real and imaginary part of c if the view shows a Julia set, empty otherwise
+    pub fn julia_parameter(&self) -> Vec<f64> {
+        match self.settings.get().fractal {
+            fractal::Fractal::Julia(c) => vec![c.re, c.im],
+            _ => Vec::new(),
+        }
+    }
+
+    // Returns to the previous view of the navigation history, false if there is none. Views whose
+    // frame is still kept are shown instantly, others are rendered again
+    pub fn undo(&self) -> bool {
+        let entry = self.history.get().undo().cloned();
+        entry.map(|entry| self.show_history_entry(entry)).is_some()
+    }
+
+    // Goes forward again after `undo`, false if there is no view to go to
+    pub fn redo(&self) -> bool {
+        let entry = self.history.get().redo().cloned();
+        entry.map(|entry| self.show_history_entry(entry)).is_some()
+    }
+
+    // Moves to the view with the given index of the navigation history, oldest first
+    pub fn jump_to_history(&self, index: usize) -> bool {
+        let entry = self.history.get().jump(index).cloned();
+        entry.map(|entry| self.show_history_entry(entry)).is_some()
+    }
+
+    pub fn history_length(&self) -> usize {
+        self.history.get().len()
+    }
+
+    pub fn history_index(&self) -> usize {
+        self.history.get().current_index()
+    }
+
+    // The view with the given index of the navigation history as returned by `to_state`
+    pub fn history_state(&self, index: usize) -> Option<String> {
+        self.history.get().get(index).map(|entry| entry.state.encode())
+    }
+
+    // Limits the number of views kept for undo and how many of them keep their rendered frame
+    pub fn set_history_capacity(&self, capacity: usize, frame_capacity: usize) {
+        self.history.get().set_capacity(capacity, frame_capacity);
+    }
+
+    // Zooming immediately resamples the existing cells as a preview, call `update` to refine it
+    pub fn zoom_in(&self) -> f64 {
         let position_mutex = self.position.clone();
         let position = &mut position_mutex.get();
-        return position.zoom_in();
+        let previous = position.clone();
+        let zoom_factor = position.zoom_in();
+        self.record_view();
+        self.preview_from(&previous);
+        zoom_factor
     }
 
-    pub fn zoom_out(self) -> f64 {
+    pub fn zoom_out(&self) -> f64 {
         let position_mutex = self.position.clone();
         let position = &mut position_mutex.get();
-        return position.zoom_out();
+        let previous = position.clone();
+        let zoom_factor = position.zoom_out();
+        self.record_view();
+        self.preview_from(&previous);
+        zoom_factor
     }
 
     pub fn move_vertical(&self, offset: i64, pool: &pool::WorkerPool) -> Result<Promise, JsValue> {
@@ -226,9 +1396,15 @@ impl Universe {
         let cells_r_mutex = self.cells_r.clone();
         let cells_g_mutex = self.cells_g.clone();
         let cells_b_mutex = self.cells_b.clone();
+        let settings_mutex = self.settings.clone();
+        let tile_cache_mutex = self.tile_cache.clone();
+        let history_mutex = self.history.clone();
         let width = self.width.clone();
         let height = self.height.clone();
         let thread_pool = self.pool.clone();
+        // The view is recorded before it is drawn on the pool, like the other moves
+        let new_y = position_mutex.get().move_vertical(offset);
+        self.record_view();
 
         pool.run(move || {
             thread_pool.install(|| {
@@ -236,37 +1412,25 @@ impl Universe {
                 let cells_g = &mut cells_g_mutex.get();
                 let cells_b = &mut cells_b_mutex.get();
                 let position = &mut position_mutex.get();
-                let new_y = position.move_vertical(offset);
-                let is_up = offset < 0;
-
-                let start_new = if is_up {
-                    0
-                } else {
-                    height - offset.abs() as u32
-                };
-
-                let end_new = if is_up { offset.abs() as u32 } else { height };
-
-                // Copy cells that don't need to be recalcualted
-                let offset_cells = width * offset.abs() as u32;
-                let start_index_copy = if is_up { 0 } else { offset_cells } as usize;
-                let end_index_copy = if is_up {
-                    height * width - offset_cells
-                } else {
-                    height * width
-                } as usize;
-                let target_index_copy = if is_up {
-                    offset.abs() as u32 * width
-                } else {
-                    0
-                } as usize;
-                cells_r.copy_within(start_index_copy..end_index_copy, target_index_copy);
-                cells_g.copy_within(start_index_copy..end_index_copy, target_index_copy);
-                cells_b.copy_within(start_index_copy..end_index_copy, target_index_copy);
-
-                recalculate_cells(
-                    start_new, end_new, 0, width, position, cells_r, cells_g, cells_b, width,
+                let settings = &settings_mutex.get();
+                let tile_cache = &mut tile_cache_mutex.get();
+                let history = &mut history_mutex.get();
+                // Tiles that stay visible are taken from the cache
+                tiles::draw_view(
+                    position,
+                    settings,
+                    tile_cache,
+                    (cells_r, cells_g, cells_b),
+                    width,
                     height,
+                    tiles::render_tiles_parallel,
+                );
+                remember_frame(
+                    history,
+                    position,
+                    settings,
+                    (width, height),
+                    (cells_r, cells_g, cells_b),
                 );
                 tx.send(new_y).unwrap();
             });
@@ -285,70 +1449,11 @@ impl Universe {
     pub fn move_horizontal(&self, offset: i64) -> i64 {
         let position_mutex = self.position.clone();
         let position = &mut position_mutex.get();
-        let cells_r_mutex = self.cells_r.clone();
-        let cells_r = &mut cells_r_mutex.get();
-        let cells_g_mutex = self.cells_g.clone();
-        let cells_g = &mut cells_g_mutex.get();
-        let cells_b_mutex = self.cells_b.clone();
-        let cells_b = &mut cells_b_mutex.get();
         let new_x = position.move_horizontal(offset);
-
-        let is_left = offset < 0;
-
-        let col_start_new = if is_left {
-            0
-        } else {
-            self.width - offset.abs() as u32
-        };
-
-        let col_end_new = if is_left {
-            offset.abs() as u32
-        } else {
-            self.width
-        };
-
-        let copy_range = if is_left {
-            col_end_new..self.width
-        } else {
-            0..col_start_new
-        };
-        if is_left {
-            for col in copy_range.rev() {
-                let source_col = (col as i64 + offset) as u32;
-                for row in 0..self.height {
-                    let idx = self.get_index(row, col);
-                    let idx_source = self.get_index(row, source_col);
-                    cells_r[idx] = cells_r[idx_source].clone();
-                    cells_g[idx] = cells_g[idx_source].clone();
-                    cells_b[idx] = cells_b[idx_source].clone();
-                }
-            }
-        } else {
-            for col in copy_range {
-                let source_col = (col as i64 + offset) as u32;
-                for row in 0..self.height {
-                    let idx = self.get_index(row, col);
-                    let idx_source = self.get_index(row, source_col);
-                    cells_r[idx] = cells_r[idx_source].clone();
-                    cells_g[idx] = cells_g[idx_source].clone();
-                    cells_b[idx] = cells_b[idx_source].clone();
-                }
-            }
-        }
-
-        recalculate_cells(
-            0,
-            self.height,
-            0,
-            self.width,
-            position,
-            cells_r,
-            cells_g,
-            cells_b,
-            self.width,
-            self.height,
-        );
-        return new_x;
+        self.record_view();
+        // Tiles that stay visible are taken from the cache
+        self.update();
+        new_x
     }
 }
 
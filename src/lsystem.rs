@@ -0,0 +1,817 @@
+//! L-systems: strings of symbols rewritten in parallel by production rules, drawn by a turtle.
+//!
+//! A system is written as settings and rules, one per line, with comments after `#`:
+//!
+//! ```text
+//! axiom: A(1)
+//! angle: 30
+//! iterations: 6
+//! A(s) : s > 0.1 -> F(s) [+A(s * 0.6)] [-A(s * 0.6)]
+//! F -> F F ; 0.7
+//! F -> F ; 0.3
+//! ```
+//!
+//! Rules rewrite every module, a symbol with optional numeric parameters, whose symbol and number
+//! of parameters match the predecessor. Parametric rules name the parameters of the predecessor
+//! and compute the parameters of the successor with the arithmetic of `expression` (`+ - * / ^`,
+//! numbers like `1e-3`). They apply only where their condition after `:` holds, written with
+//! comparisons, `&&`, `||` and `!`. Rules with the same predecessor are stochastic: one of the
+//! matching rules is chosen at random by the weight after `;`, 1 by default. The random choices
+//! only depend on `seed`.
+//!
+//! The turtle starts at the origin heading up, or at `heading` degrees counterclockwise from the
+//! x axis, and interprets
+//!
+//! * `F`, `G` and the symbols of the `draw` setting: move forward drawing a line,
+//! * `f`: move forward without drawing,
+//! * `+` and `-`: turn left and right by `angle` degrees,
+//! * `|`: turn around,
+//! * `[` and `]`: save and restore position and heading.
+//!
+//! The first parameter of a module overrides the step length of 1 or the angle. Other symbols
+//! only take part in rewriting. Expansion stops with an error once the string gets longer than
+//! `MAX_MODULES`.
+//!
+//! Lines are drawn upright like the maps of `ifs`, or exported as SVG paths.
+
+use crate::density::{DensityRenderer, Random, Screen};
+use crate::expression::{BinaryOp, Dialect, Expr, Parser, Scope, SyntaxError, Token};
+use crate::ifs;
+use crate::mandelbrot::Position;
+use crate::palette::Palette;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+use wasm_bindgen::prelude::*;
+
+// Longest string of modules an expansion may produce
+pub const MAX_MODULES: usize = 1_000_000;
+
+// Most rewriting steps of an expansion
+pub const MAX_ITERATIONS: u32 = 32;
+
+// Most parameters of a module
+pub const MAX_PARAMETERS: usize = 8;
+
+// Names and sources of the built-in systems
+pub const PRESETS: [(&str, &str); 7] = [
+    (
+        "koch",
+        "# Koch snowflake\naxiom: F--F--F\nangle: 60\nheading: 0\niterations: 4\nF -> F+F--F+F\n",
+    ),
+    (
+        "dragon",
+        "# Heighway dragon\naxiom: FX\nangle: 90\nheading: 0\niterations: 12\n\
+         X -> X+YF+\nY -> -FX-Y\n",
+    ),
+    (
+        "hilbert",
+        "# Hilbert curve\naxiom: A\nangle: 90\nheading: 0\niterations: 5\n\
+         A -> +BF-AFA-FB+\nB -> -AF+BFB+FA-\n",
+    ),
+    (
+        "sierpinski",
+        "# Sierpinski arrowhead curve\naxiom: A\nangle: 60\nheading: 0\niterations: 7\n\
+         draw: AB\nA -> B-A-B\nB -> A+B+A\n",
+    ),
+    (
+        "plant",
+        "# Fractal plant\naxiom: X\nangle: 25\nheading: 65\niterations: 5\n\
+         X -> F+[[X]-X]-F[-FX]+X\nF -> FF\n",
+    ),
+    (
+        "stochastic-plant",
+        "# Plant whose branches grow at random\naxiom: F\nangle: 25.7\niterations: 5\n\
+         F -> F[+F]F[-F]F ; 1\nF -> F[+F]F ; 1\nF -> F[-F]F ; 1\n",
+    ),
+    (
+        "parametric-tree",
+        "# Tree whose branches get shorter until they stop\naxiom: A(1)\nangle: 30\n\
+         iterations: 10\nA(s) : s >= 0.05 -> F(s) [+A(s * 0.7)] [-A(s * 0.65)]\n",
+    ),
+];
+
+// Names of the systems for `Universe::start_lsystem_preset`
+#[wasm_bindgen]
+pub fn lsystem_presets() -> Vec<String> {
+    PRESETS.iter().map(|(name, _)| name.to_string()).collect()
+}
+
+// Turtle commands of the symbols
+const DRAW_SYMBOLS: [char; 2] = ['F', 'G'];
+const MOVE_SYMBOL: char = 'f';
+
+// Characters that cannot be symbols since they structure rules
+const RESERVED: [char; 5] = ['(', ')', ',', ';', ':'];
+
+// Share of the palette used to colour lines by the depth of their branch, the first colours are
+// too dark for lines on black
+const FIRST_QUOTIENT: f64 = 0.3;
+const LAST_QUOTIENT: f64 = 0.99;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LSystemError {
+    Syntax { line: usize, message: String },
+    MissingAxiom,
+    TooManyIterations(u32),
+    TooLarge { iteration: u32 },
+    UnknownPreset(String),
+}
+
+impl fmt::Display for LSystemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LSystemError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            LSystemError::MissingAxiom => write!(f, "the system has no axiom"),
+            LSystemError::TooManyIterations(iterations) => write!(
+                f,
+                "{} iterations are too many, at most {} are supported",
+                iterations, MAX_ITERATIONS
+            ),
+            LSystemError::TooLarge { iteration } => write!(
+                f,
+                "iteration {} produces more than {} modules",
+                iteration, MAX_MODULES
+            ),
+            LSystemError::UnknownPreset(name) => write!(f, "unknown L-system preset '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for LSystemError {}
+
+// Symbol with parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub symbol: char,
+    pub parameters: Vec<f64>,
+}
+
+impl Module {
+    pub fn new(symbol: char, parameters: Vec<f64>) -> Module {
+        Module { symbol, parameters }
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol)?;
+        if !self.parameters.is_empty() {
+            let parameters: Vec<String> = self.parameters.iter().map(|p| p.to_string()).collect();
+            write!(f, "({})", parameters.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+// Writes modules as a string like `F(1)+F(0.5)`
+pub fn modules_to_string(modules: &[Module]) -> String {
+    let mut text = String::new();
+    for module in modules {
+        write!(text, "{}", module).unwrap();
+    }
+    text
+}
+
+// Parameters of a predecessor, the names expressions of its rule may use. Rules call no functions
+struct Parameters<'a>(&'a [&'a str]);
+
+impl Scope for Parameters<'_> {
+    fn is_variable(&self, name: &str) -> bool {
+        self.0.contains(&name)
+    }
+
+    fn arity(&self, _name: &str) -> Option<usize> {
+        None
+    }
+}
+
+// Value of an expression over the parameters of a module and their names, conditions are 1 when
+// they hold and 0 when they do not
+fn evaluate(expr: &Expr, names: &[String], parameters: &[f64]) -> f64 {
+    let truth = |condition: bool| if condition { 1.0 } else { 0.0 };
+    let value = |expr: &Expr| evaluate(expr, names, parameters);
+    match expr {
+        Expr::Number(number) => number.re,
+        Expr::Variable(name) => names
+            .iter()
+            .position(|parameter| parameter == name)
+            .map_or(f64::NAN, |idx| parameters[idx]),
+        Expr::Negate(a) => -value(a),
+        Expr::Not(a) => truth(value(a) == 0.0),
+        Expr::Binary(op, a, b) => {
+            let (a, b) = (value(a), value(b));
+            match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Sub => a - b,
+                BinaryOp::Mul => a * b,
+                BinaryOp::Div => a / b,
+                BinaryOp::Pow => a.powf(b),
+                BinaryOp::Less => truth(a < b),
+                BinaryOp::LessEqual => truth(a <= b),
+                BinaryOp::Greater => truth(a > b),
+                BinaryOp::GreaterEqual => truth(a >= b),
+                BinaryOp::Equal => truth(a == b),
+                BinaryOp::NotEqual => truth(a != b),
+                BinaryOp::And => truth(a != 0.0 && b != 0.0),
+                BinaryOp::Or => truth(a != 0.0 || b != 0.0),
+            }
+        }
+        // Only parsed in other scopes and dialects
+        Expr::Call(..) | Expr::Modulus(_) => f64::NAN,
+    }
+}
+
+// Module of a successor, with the expressions of its parameters
+#[derive(Debug, Clone, PartialEq)]
+struct Production {
+    symbol: char,
+    parameters: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    symbol: char,
+    // Names of the parameters of the predecessor
+    names: Vec<String>,
+    condition: Option<Expr>,
+    successor: Vec<Production>,
+    weight: f64,
+}
+
+impl Rule {
+    fn matches(&self, module: &Module) -> bool {
+        module.symbol == self.symbol
+            && module.parameters.len() == self.names.len()
+            && self
+                .condition
+                .as_ref()
+                .is_none_or(|condition| evaluate(condition, &self.names, &module.parameters) != 0.0)
+    }
+
+    fn rewrite(&self, module: &Module, output: &mut Vec<Module>) {
+        for production in &self.successor {
+            output.push(Module {
+                symbol: production.symbol,
+                parameters: production
+                    .parameters
+                    .iter()
+                    .map(|expression| evaluate(expression, &self.names, &module.parameters))
+                    .collect(),
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LSystem {
+    axiom: Vec<Module>,
+    rules: Vec<Rule>,
+    pub angle: f64,
+    pub heading: f64,
+    pub iterations: u32,
+    pub seed: u64,
+    draw: Vec<char>,
+}
+
+impl LSystem {
+    pub fn parse(text: &str) -> Result<LSystem, LSystemError> {
+        let mut axiom = None;
+        let mut system = LSystem {
+            axiom: Vec::new(),
+            rules: Vec::new(),
+            angle: 90.0,
+            heading: 90.0,
+            iterations: 4,
+            seed: 0,
+            draw: DRAW_SYMBOLS.to_vec(),
+        };
+        for (idx, line) in text.lines().enumerate() {
+            let syntax = |message: String| LSystemError::Syntax {
+                line: idx + 1,
+                message,
+            };
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.contains("->") {
+                system.rules.push(parse_rule(line).map_err(syntax)?);
+                continue;
+            }
+            let (key, value) = line.split_once(':').ok_or_else(|| {
+                syntax("expected a rule 'A -> B' or a setting 'key: value'".to_string())
+            })?;
+            let value = value.trim();
+            let number = || -> Result<f64, LSystemError> {
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|number| number.is_finite())
+                    .ok_or_else(|| syntax(format!("invalid number '{}'", value)))
+            };
+            match key.trim() {
+                "axiom" => {
+                    let productions = parse_successor(value, &[]).map_err(syntax)?;
+                    axiom = Some(
+                        productions
+                            .iter()
+                            .map(|production| Module {
+                                symbol: production.symbol,
+                                parameters: production
+                                    .parameters
+                                    .iter()
+                                    .map(|expression| evaluate(expression, &[], &[]))
+                                    .collect(),
+                            })
+                            .collect(),
+                    );
+                }
+                "angle" => system.angle = number()?,
+                "heading" => system.heading = number()?,
+                "iterations" => {
+                    system.iterations = value
+                        .parse()
+                        .map_err(|_| syntax(format!("invalid number of iterations '{}'", value)))?
+                }
+                "seed" => {
+                    system.seed = value
+                        .parse()
+                        .map_err(|_| syntax(format!("invalid seed '{}'", value)))?
+                }
+                "draw" => {
+                    system
+                        .draw
+                        .extend(value.chars().filter(|c| !c.is_whitespace()));
+                }
+                key => return Err(syntax(format!("unknown setting '{}'", key))),
+            }
+        }
+        system.axiom = axiom.ok_or(LSystemError::MissingAxiom)?;
+        if system.axiom.is_empty() {
+            return Err(LSystemError::MissingAxiom);
+        }
+        if system.iterations > MAX_ITERATIONS {
+            return Err(LSystemError::TooManyIterations(system.iterations));
+        }
+        Ok(system)
+    }
+
+    // One of the systems of `PRESETS`
+    pub fn preset(name: &str) -> Result<LSystem, LSystemError> {
+        let (_, source) = PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .ok_or_else(|| LSystemError::UnknownPreset(name.to_string()))?;
+        LSystem::parse(source)
+    }
+
+    pub fn axiom(&self) -> &[Module] {
+        &self.axiom
+    }
+
+    // Rewrites the axiom `iterations` times
+    pub fn expand(&self, iterations: u32) -> Result<Vec<Module>, LSystemError> {
+        if iterations > MAX_ITERATIONS {
+            return Err(LSystemError::TooManyIterations(iterations));
+        }
+        let mut random = Random(self.seed);
+        let mut modules = self.axiom.clone();
+        let mut matching = Vec::new();
+        for iteration in 1..=iterations {
+            let mut next = Vec::with_capacity(modules.len());
+            for module in &modules {
+                matching.clear();
+                matching.extend(self.rules.iter().filter(|rule| rule.matches(module)));
+                match matching.len() {
+                    0 => next.push(module.clone()),
+                    1 => matching[0].rewrite(module, &mut next),
+                    _ => {
+                        let total: f64 = matching.iter().map(|rule| rule.weight).sum();
+                        let mut choice = random.next_f64() * total;
+                        let rule = matching
+                            .iter()
+                            .find(|rule| {
+                                choice -= rule.weight;
+                                choice < 0.0
+                            })
+                            .unwrap_or(&matching[matching.len() - 1]);
+                        rule.rewrite(module, &mut next);
+                    }
+                }
+                if next.len() > MAX_MODULES {
+                    return Err(LSystemError::TooLarge { iteration });
+                }
+            }
+            modules = next;
+        }
+        Ok(modules)
+    }
+
+    // Lines drawn by the turtle interpreting the modules
+    pub fn turtle(&self, modules: &[Module]) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        let mut position = (0.0, 0.0);
+        let mut heading = self.heading;
+        let mut stack = Vec::new();
+        for module in modules {
+            let parameter = module.parameters.first().cloned();
+            match module.symbol {
+                '+' => heading += parameter.unwrap_or(self.angle),
+                '-' => heading -= parameter.unwrap_or(self.angle),
+                '|' => heading += 180.0,
+                '[' => stack.push((position, heading)),
+                ']' => {
+                    if let Some(state) = stack.pop() {
+                        (position, heading) = state;
+                    }
+                }
+                symbol if symbol == MOVE_SYMBOL || self.draw.contains(&symbol) => {
+                    let length = parameter.unwrap_or(1.0);
+                    let (sin, cos) = heading.to_radians().sin_cos();
+                    let end = (position.0 + length * cos, position.1 + length * sin);
+                    if symbol != MOVE_SYMBOL {
+                        segments.push(Segment {
+                            start: position,
+                            end,
+                            depth: stack.len() as u32,
+                        });
+                    }
+                    position = end;
+                }
+                _ => {}
+            }
+        }
+        segments
+    }
+
+    // Lines of the system expanded `iterations` times
+    pub fn segments(&self) -> Result<Vec<Segment>, LSystemError> {
+        Ok(self.turtle(&self.expand(self.iterations)?))
+    }
+}
+
+// Parses `symbol[(names)] [: condition] -> successor [; weight]`
+fn parse_rule(line: &str) -> Result<Rule, String> {
+    let (left, right) = line.split_once("->").unwrap_or((line, ""));
+    let (right, weight) = match right.split_once(';') {
+        Some((right, weight)) => {
+            let weight = weight.trim();
+            let value = weight
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite() && *value > 0.0)
+                .ok_or_else(|| format!("invalid weight '{}'", weight))?;
+            (right, value)
+        }
+        None => (right, 1.0),
+    };
+    let (predecessor, condition) = match left.split_once(':') {
+        Some((predecessor, condition)) => (predecessor.trim(), Some(condition)),
+        None => (left.trim(), None),
+    };
+    let mut chars = predecessor.chars();
+    let symbol = chars
+        .next()
+        .filter(|c| !RESERVED.contains(c))
+        .ok_or("missing predecessor")?;
+    let rest = chars.as_str().trim();
+    let names: Vec<&str> = if rest.is_empty() {
+        Vec::new()
+    } else {
+        rest.strip_prefix('(')
+            .and_then(|rest| rest.strip_suffix(')'))
+            .ok_or_else(|| format!("invalid predecessor '{}'", predecessor))?
+            .split(',')
+            .map(|name| name.trim())
+            .collect()
+    };
+    for name in &names {
+        let valid = name.chars().next().is_some_and(|c| c.is_alphabetic())
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!("invalid parameter name '{}'", name));
+        }
+    }
+    if names.len() > MAX_PARAMETERS {
+        return Err(format!(
+            "at most {} parameters are supported",
+            MAX_PARAMETERS
+        ));
+    }
+    let condition = match condition {
+        Some(condition) => match parse_expressions(condition, &names)?.as_slice() {
+            [condition] => Some(condition.clone()),
+            _ => return Err("the condition must be one expression".to_string()),
+        },
+        None => None,
+    };
+    Ok(Rule {
+        symbol,
+        names: names.iter().map(|name| name.to_string()).collect(),
+        condition,
+        successor: parse_successor(right, &names)?,
+        weight,
+    })
+}
+
+fn parse_successor(text: &str, names: &[&str]) -> Result<Vec<Production>, String> {
+    let mut productions = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(symbol) = rest.chars().next() {
+        if RESERVED.contains(&symbol) {
+            return Err(format!("unexpected '{}'", symbol));
+        }
+        rest = &rest[symbol.len_utf8()..];
+        let mut parameters = Vec::new();
+        if rest.starts_with('(') {
+            let end = closing_parenthesis(rest)
+                .ok_or_else(|| format!("missing ')' after the parameters of '{}'", symbol))?;
+            parameters = parse_expressions(&rest[1..end], names)?;
+            if parameters.len() > MAX_PARAMETERS {
+                return Err(format!(
+                    "at most {} parameters are supported",
+                    MAX_PARAMETERS
+                ));
+            }
+            rest = &rest[end + 1..];
+        }
+        productions.push(Production { symbol, parameters });
+        rest = rest.trim_start();
+    }
+    Ok(productions)
+}
+
+// Offset of the parenthesis closing the one at the start of the text
+fn closing_parenthesis(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 1 => return Some(idx),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+// Expressions separated by commas over the parameters with the names, making up the whole text
+fn parse_expressions(text: &str, names: &[&str]) -> Result<Vec<Expr>, String> {
+    let scope = Parameters(names);
+    let message = |err: SyntaxError| err.message;
+    let mut parser = Parser::new(text, 1, Dialect::Standard).map_err(message)?;
+    let mut expressions = vec![parser.expression(&scope).map_err(message)?];
+    while parser.eat(",") {
+        expressions.push(parser.expression(&scope).map_err(message)?);
+    }
+    while *parser.peek() == Token::Separator {
+        parser.advance();
+    }
+    match parser.peek() {
+        Token::End => Ok(expressions),
+        token => Err(format!("unexpected {}", token)),
+    }
+}
+
+// Line drawn by the turtle, `depth` is the number of branches it is nested in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub start: (f64, f64),
+    pub end: (f64, f64),
+    pub depth: u32,
+}
+
+// Smallest and largest x and y of the lines, None if there are none
+pub fn bounds(segments: &[Segment]) -> Option<((f64, f64), (f64, f64))> {
+    let mut points = segments.iter().flat_map(|s| [s.start, s.end]);
+    let first = points.next()?;
+    let init = ((first.0, first.0), (first.1, first.1));
+    Some(points.fold(init, |((x0, x1), (y0, y1)), (x, y)| {
+        ((x0.min(x), x1.max(x)), (y0.min(y), y1.max(y)))
+    }))
+}
+
+// Position of a `width` x `height` screen of a `Universe` that shows all lines, shown upright like
+// the maps of `ifs`
+pub fn fitting_position(segments: &[Segment], width: u32, height: u32) -> Position {
+    let ((x_min, x_max), (y_min, y_max)) = bounds(segments).unwrap_or(((0.0, 0.0), (0.0, 0.0)));
+    // Leaves a margin and keeps straight lines from filling the screen in one direction
+    let size = (x_max - x_min).max(y_max - y_min).max(1e-9);
+    let extent = |min: f64, max: f64| (max - min).max(size * 0.1) * 1.1;
+    let zoom_factor =
+        Position::zoom_factor_fitting(extent(y_min, y_max), extent(x_min, x_max), width, height);
+    let centre = ifs::to_complex(((x_min + x_max) / 2.0, (y_min + y_max) / 2.0));
+    Position::from_centre(centre, zoom_factor, width, height)
+}
+
+// SVG image of `size` pixels on the longer side that shows the lines as paths, one per
+// connected polyline, with y growing upwards
+pub fn to_svg(segments: &[Segment], size: u32) -> String {
+    let ((x_min, x_max), (y_min, y_max)) = bounds(segments).unwrap_or(((0.0, 1.0), (0.0, 1.0)));
+    let extent = (x_max - x_min).max(y_max - y_min).max(1e-9);
+    let scale = size as f64 * 0.95 / extent;
+    let margin = size as f64 * 0.025;
+    let width = ((x_max - x_min) * scale + 2.0 * margin).ceil();
+    let height = ((y_max - y_min) * scale + 2.0 * margin).ceil();
+    let point = |(x, y): (f64, f64)| -> String {
+        format!(
+            "{:.2} {:.2}",
+            (x - x_min) * scale + margin,
+            (y_max - y) * scale + margin
+        )
+    };
+    let mut path = String::new();
+    let mut last_end = None;
+    for segment in segments {
+        if last_end == Some(segment.start) {
+            write!(path, " L{}", point(segment.end)).unwrap();
+        } else {
+            write!(path, " M{} L{}", point(segment.start), point(segment.end)).unwrap();
+        }
+        last_end = Some(segment.end);
+    }
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+         viewBox=\"0 0 {} {}\">\n<path d=\"{}\" fill=\"none\" stroke=\"black\" \
+         stroke-width=\"1\" stroke-linecap=\"round\"/>\n</svg>\n",
+        width,
+        height,
+        width,
+        height,
+        path.trim_start()
+    )
+}
+
+// Renderer drawing the lines of an L-system with anti-aliasing, coloured by the depth of their
+// branch. Every sample draws the next line, the lines are not sampled randomly
+#[derive(Debug, Clone)]
+pub struct LSystemRenderer {
+    screen: Screen,
+    segments: Vec<Segment>,
+    // Deepest branch of the lines, which gets the last colour
+    max_depth: u32,
+    palette: Palette,
+    // Coverage of every pixel by lines and palette quotient of the line covering it most
+    coverage: Vec<f32>,
+    quotients: Vec<f32>,
+    samples: u64,
+}
+
+impl LSystemRenderer {
+    pub fn new(screen: Screen, segments: Vec<Segment>, palette: Palette) -> LSystemRenderer {
+        let pixels = screen.pixels();
+        let max_depth = segments.iter().map(|s| s.depth).max().unwrap_or(0);
+        LSystemRenderer {
+            screen,
+            segments,
+            max_depth,
+            palette,
+            coverage: vec![0.0; pixels],
+            quotients: vec![0.0; pixels],
+            samples: 0,
+        }
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn coverage(&self) -> &[f32] {
+        &self.coverage
+    }
+
+    fn plot(&mut self, row: i64, column: i64, coverage: f32, quotient: f32) {
+        let (width, height) = (self.screen.width as i64, self.screen.height as i64);
+        if row < 0 || column < 0 || row >= height || column >= width || coverage <= 0.0 {
+            return;
+        }
+        let idx = (row * width + column) as usize;
+        if coverage > self.coverage[idx] {
+            self.coverage[idx] = coverage;
+            self.quotients[idx] = quotient;
+        }
+    }
+
+    // Draws a line between fractional pixels, splitting every step along the longer direction
+    // between the two pixels closest to the line
+    fn draw_line(&mut self, start: (f64, f64), end: (f64, f64), quotient: f32) {
+        let (start, end) = match self.clip(start, end) {
+            Some(line) => line,
+            None => return,
+        };
+        let (d_row, d_column) = (end.0 - start.0, end.1 - start.1);
+        let steps = d_row.abs().max(d_column.abs()).ceil().max(1.0) as usize;
+        let steep = d_row.abs() > d_column.abs();
+        for step in 0..=steps {
+            let t = step as f64 / steps as f64;
+            let (row, column) = (start.0 + t * d_row, start.1 + t * d_column);
+            if steep {
+                let (base, fraction) = (column.floor(), column - column.floor());
+                let row = row.round() as i64;
+                self.plot(row, base as i64, (1.0 - fraction) as f32, quotient);
+                self.plot(row, base as i64 + 1, fraction as f32, quotient);
+            } else {
+                let (base, fraction) = (row.floor(), row - row.floor());
+                let column = column.round() as i64;
+                self.plot(base as i64, column, (1.0 - fraction) as f32, quotient);
+                self.plot(base as i64 + 1, column, fraction as f32, quotient);
+            }
+        }
+    }
+
+    // Part of a line within the screen and a border of one pixel (Liang-Barsky), so that the
+    // number of steps is bounded when zoomed in
+    fn clip(&self, start: (f64, f64), end: (f64, f64)) -> Option<((f64, f64), (f64, f64))> {
+        let (d_row, d_column) = (end.0 - start.0, end.1 - start.1);
+        let (mut t0, mut t1) = (0.0f64, 1.0f64);
+        let limits = [
+            (-d_row, start.0 + 1.0),
+            (d_row, self.screen.height as f64 - start.0),
+            (-d_column, start.1 + 1.0),
+            (d_column, self.screen.width as f64 - start.1),
+        ];
+        for (p, q) in limits {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else {
+                let t = q / p;
+                if p < 0.0 {
+                    t0 = t0.max(t);
+                } else {
+                    t1 = t1.min(t);
+                }
+            }
+        }
+        if t0 > t1 || !t0.is_finite() || !t1.is_finite() {
+            return None;
+        }
+        let at = |t: f64| (start.0 + t * d_row, start.1 + t * d_column);
+        Some((at(t0), at(t1)))
+    }
+}
+
+impl DensityRenderer for LSystemRenderer {
+    fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    fn restart(
+        &self,
+        screen: Screen,
+    ) -> Result<Box<dyn DensityRenderer>, Box<dyn Error + Send + Sync>> {
+        Ok(Box::new(LSystemRenderer::new(
+            screen,
+            self.segments.clone(),
+            self.palette.clone(),
+        )))
+    }
+
+    // Draws the next `samples` lines, the number of samples is the number of lines drawn
+    fn accumulate(&mut self, samples: u64) {
+        let first = self.samples as usize;
+        let last = (self.samples.saturating_add(samples) as usize).min(self.segments.len());
+        if first >= last {
+            return;
+        }
+        let (width, height) = (self.screen.width, self.screen.height);
+        let max_depth = self.max_depth;
+        let segments = std::mem::take(&mut self.segments);
+        for segment in &segments[first..last] {
+            let pixel = |point| {
+                self.screen
+                    .position
+                    .complex_to_pixel(ifs::to_complex(point), width, height)
+            };
+            let (start, end) = (pixel(segment.start), pixel(segment.end));
+            let quotient = FIRST_QUOTIENT
+                + (LAST_QUOTIENT - FIRST_QUOTIENT) * segment.depth as f64 / (max_depth + 1) as f64;
+            self.draw_line(start, end, quotient as f32);
+        }
+        self.samples = last as u64;
+        self.segments = segments;
+    }
+
+    fn to_cells(&self) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let pixels = self.coverage.len();
+        let (mut r, mut g, mut b) = (
+            Vec::with_capacity(pixels),
+            Vec::with_capacity(pixels),
+            Vec::with_capacity(pixels),
+        );
+        for (coverage, quotient) in self.coverage.iter().zip(&self.quotients) {
+            let (red, green, blue) = self.palette.rgb_value(*quotient as f64);
+            let shade = |channel: u8| (channel as f32 * coverage.min(1.0)).round() as u8;
+            r.push(shade(red));
+            g.push(shade(green));
+            b.push(shade(blue));
+        }
+        (r, g, b)
+    }
+
+    fn samples(&self) -> u64 {
+        self.samples
+    }
+}
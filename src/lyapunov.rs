@@ -0,0 +1,211 @@
+//! Markus-Lyapunov fractals: the stability of the logistic map `x -> r x (1 - x)` when the rate
+//! `r` alternates between `a` and `b` following a sequence like `AB` or `AABAB`.
+//!
+//! The real part of a point is `a` and the imaginary part `b`. Every pixel estimates the
+//! Lyapunov exponent `λ = 1/N Σ ln |r (1 - 2x)|` over the iteration limit after a warm-up.
+//! Stable pixels (λ < 0) are coloured by the palette with quotient `e^λ`, so superstable regions
+//! take the first colours and the edges of chaos the last. Chaotic pixels (λ >= 0) are black like
+//! the inside of the mandelbrot set.
+//!
+//! Interesting images lie in the region `0 <= a, b <= 4`, outside of it the map leaves `[0, 1]`.
+
+use crate::export::ViewMetadata;
+use crate::fractal::RenderSettings;
+use crate::mandelbrot::{Escape, Position};
+use num::complex::Complex;
+use std::fmt;
+
+// Longest supported sequence, longer ones make no visible difference
+pub const MAX_SEQUENCE_LENGTH: usize = 64;
+
+// Range of rates for which the logistic map stays within [0, 1]
+pub const MAX_RATE: f64 = 4.0;
+
+// Starting value of the logistic map
+const START: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LyapunovError {
+    EmptySequence,
+    InvalidSymbol(char),
+    SequenceTooLong(usize),
+    InvalidRegion(String),
+}
+
+impl fmt::Display for LyapunovError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LyapunovError::EmptySequence => write!(f, "the sequence is empty"),
+            LyapunovError::InvalidSymbol(symbol) => write!(
+                f,
+                "invalid symbol '{}' in the sequence, only A and B are allowed",
+                symbol
+            ),
+            LyapunovError::SequenceTooLong(length) => write!(
+                f,
+                "the sequence has {} symbols, at most {} are supported",
+                length, MAX_SEQUENCE_LENGTH
+            ),
+            LyapunovError::InvalidRegion(reason) => write!(f, "invalid region: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for LyapunovError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lyapunov {
+    // True where the sequence uses `b`
+    sequence: Vec<bool>,
+}
+
+impl Default for Lyapunov {
+    fn default() -> Lyapunov {
+        Lyapunov {
+            sequence: vec![false, true],
+        }
+    }
+}
+
+impl Lyapunov {
+    // Parses a sequence of the letters A and B, ignoring case and whitespace
+    pub fn new(sequence: &str) -> Result<Lyapunov, LyapunovError> {
+        let sequence = sequence
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c.to_ascii_uppercase() {
+                'A' => Ok(false),
+                'B' => Ok(true),
+                _ => Err(LyapunovError::InvalidSymbol(c)),
+            })
+            .collect::<Result<Vec<bool>, LyapunovError>>()?;
+        if sequence.is_empty() {
+            return Err(LyapunovError::EmptySequence);
+        }
+        if sequence.len() > MAX_SEQUENCE_LENGTH {
+            return Err(LyapunovError::SequenceTooLong(sequence.len()));
+        }
+        Ok(Lyapunov { sequence })
+    }
+
+    pub fn sequence(&self) -> String {
+        self.sequence
+            .iter()
+            .map(|b| if *b { 'B' } else { 'A' })
+            .collect()
+    }
+
+    // Estimates the Lyapunov exponent at `a + bi` over `iterations` steps after a warm-up of a
+    // quarter as many steps
+    pub fn exponent(&self, point: Complex<f64>, iterations: u32) -> f64 {
+        let (a, b) = (point.re, point.im);
+        if !(0.0..=MAX_RATE).contains(&a) || !(0.0..=MAX_RATE).contains(&b) {
+            return f64::INFINITY;
+        }
+        let rates = self
+            .sequence
+            .iter()
+            .map(|b_rate| if *b_rate { b } else { a });
+        let mut rates = rates.cycle();
+        let mut x = START;
+        for _ in 0..iterations / 4 {
+            let r = rates.next().unwrap_or(a);
+            x = r * x * (1.0 - x);
+        }
+        let iterations = iterations.max(1);
+        let mut sum = 0.0;
+        for _ in 0..iterations {
+            let r = rates.next().unwrap_or(a);
+            x = r * x * (1.0 - x);
+            sum += (r * (1.0 - 2.0 * x)).abs().ln();
+        }
+        sum / iterations as f64
+    }
+
+    // The quotient `e^λ` of stable points as smoothed iterations relative to `max_iter`, None for
+    // chaotic points. Lyapunov fractals have no distance estimate
+    pub fn escape(&self, point: Complex<f64>, max_iter: u32) -> Option<Escape> {
+        let quotient = self.iteration_quotient(point, max_iter);
+        if quotient >= 1.0 {
+            return None;
+        }
+        Some(Escape {
+            smoothed_iterations: quotient * max_iter as f64,
+            distance: f64::INFINITY,
+        })
+    }
+
+    pub fn iteration_quotient(&self, point: Complex<f64>, max_iter: u32) -> f64 {
+        let exponent = self.exponent(point, max_iter);
+        if exponent < 0.0 {
+            exponent.exp()
+        } else {
+            // Chaotic, or NaN where the estimate broke down
+            1.0
+        }
+    }
+}
+
+// Rectangle of the (a, b) parameter plane
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub a_min: f64,
+    pub a_max: f64,
+    pub b_min: f64,
+    pub b_max: f64,
+}
+
+impl Default for Region {
+    fn default() -> Region {
+        Region {
+            a_min: 2.0,
+            a_max: 4.0,
+            b_min: 2.0,
+            b_max: 4.0,
+        }
+    }
+}
+
+impl Region {
+    pub fn validate(&self) -> Result<(), LyapunovError> {
+        let invalid = |reason: &str| Err(LyapunovError::InvalidRegion(reason.to_string()));
+        let bounds = [self.a_min, self.a_max, self.b_min, self.b_max];
+        if bounds.iter().any(|bound| !bound.is_finite()) {
+            return invalid("the bounds must be finite");
+        }
+        if self.a_min >= self.a_max || self.b_min >= self.b_max {
+            return invalid("the minimum of a and b must be less than the maximum");
+        }
+        if bounds.iter().any(|bound| !(0.0..=MAX_RATE).contains(bound)) {
+            return invalid("a and b must lie between 0 and 4");
+        }
+        Ok(())
+    }
+
+    // View of a `width` x `height` screen of a `Universe` that shows the whole region, with `a`
+    // along the rows like the real axis
+    pub fn view_metadata(
+        &self,
+        settings: RenderSettings,
+        width: u32,
+        height: u32,
+    ) -> Result<ViewMetadata, LyapunovError> {
+        self.validate()?;
+        let zoom_factor = Position::zoom_factor_fitting(
+            self.a_max - self.a_min,
+            self.b_max - self.b_min,
+            width,
+            height,
+        );
+        Ok(ViewMetadata {
+            settings,
+            centre: Complex::new(
+                (self.a_min + self.a_max) / 2.0,
+                (self.b_min + self.b_max) / 2.0,
+            ),
+            zoom_factor,
+            width,
+            height,
+        })
+    }
+}
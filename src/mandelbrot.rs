@@ -1,6 +1,6 @@
 use num::complex::Complex;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Position {
     x: i64,
     y: i64,
@@ -16,6 +16,11 @@ impl Position {
         }
     }
 
+    // Whether a zoom factor read from outside, e.g. of a shared view, gives a usable view
+    pub fn is_valid_zoom_factor(zoom_factor: f64) -> bool {
+        zoom_factor.is_finite() && zoom_factor > 0.0
+    }
+
     pub fn get_x(&self) -> i64 {
         self.x
     }
@@ -59,39 +64,181 @@ impl Position {
         self.y += offset;
         return self.y;
     }
+
+    // Converts a (possibly fractional) pixel of a `width` x `height` screen into the point of the
+    // complex plane it represents at this position
+    pub fn pixel_to_complex(&self, x: f64, y: f64, width: u32, height: u32) -> Complex<f64> {
+        let real_part = (((x + self.x as f64) / width as f64) - 1.5) / self.zoom_factor;
+        let imaginary_part = (((y + self.y as f64) / height as f64) - 0.5) / self.zoom_factor;
+        Complex::new(real_part, imaginary_part)
+    }
+
+    // Adapts the position to a screen resized from `width` x `height` to `new_width` x
+    // `new_height`, keeping the centre of the view and the scale along the real axis. Nothing
+    // changes if either screen is empty or the size stays the same
+    pub fn resize(&mut self, width: u32, height: u32, new_width: u32, new_height: u32) {
+        if width == 0 || height == 0 || new_width == 0 || new_height == 0 {
+            return;
+        }
+        // Going through the centre would round the position on the same screen
+        if (width, height) == (new_width, new_height) {
+            return;
+        }
+        let centre = self.centre(width, height);
+        let zoom_factor = self.zoom_factor * width as f64 / new_width as f64;
+        *self = Position::from_centre(centre, zoom_factor, new_width, new_height);
+    }
+
+    // Point of the complex plane shown in the middle of a `width` x `height` screen
+    pub fn centre(&self, width: u32, height: u32) -> Complex<f64> {
+        self.pixel_to_complex(height as f64 / 2.0, width as f64 / 2.0, width, height)
+    }
+
+    // Position showing `centre` in the middle of a `width` x `height` screen, up to rounding to
+    // whole pixels
+    pub fn from_centre(centre: Complex<f64>, zoom_factor: f64, width: u32, height: u32) -> Position {
+        let mut position = Position::new(0, 0, zoom_factor);
+        let (x, y) = position.complex_to_pixel(centre, width, height);
+        position.x = (x - height as f64 / 2.0).round() as i64;
+        position.y = (y - width as f64 / 2.0).round() as i64;
+        position
+    }
+
+    // Largest zoom factor at which a `width` x `height` screen shows `real_extent` along its rows
+    // and `imaginary_extent` along its columns. Pixels are 1 / (width * zoom_factor) apart along
+    // the real axis and 1 / (height * zoom_factor) along the imaginary axis
+    pub fn zoom_factor_fitting(
+        real_extent: f64,
+        imaginary_extent: f64,
+        width: u32,
+        height: u32,
+    ) -> f64 {
+        let (width, height) = (width as f64, height as f64);
+        (height / (width * real_extent)).min(width / (height * imaginary_extent))
+    }
+
+    // Inverse of `pixel_to_complex`: returns the fractional pixel coordinates at which `point`
+    // is shown on a `width` x `height` screen
+    pub fn complex_to_pixel(&self, point: Complex<f64>, width: u32, height: u32) -> (f64, f64) {
+        let x = (point.re * self.zoom_factor + 1.5) * width as f64 - self.x as f64;
+        let y = (point.im * self.zoom_factor + 0.5) * height as f64 - self.y as f64;
+        (x, y)
+    }
 }
 
 
-// Returns the number of iterations it took the mandelbrot series to diverge
-// relative to the total number of iterations
-// Return value will be between 0.0 (the original point has already magnitude >2)
-// and 1.0 (the series has not diverged within the maximum number of iterations)
-// The quotient is smoothed for nicer visualization based on: https://stackoverflow.com/questions/369438/smooth-spectrum-for-mandelbrot-set-rendering
-fn mandelbrot_iteration_quotient(x: u32, y: u32, width: u32, height: u32, position: &Position) -> f64 {
-    // Convert x / y coordinates to real and imaginary values based on screen position and zoom level
-    let real_part = (((x as i64 +  position.get_x()) as f64 / width as f64)-1.5)/position.get_zoom_factor();
-    let imaginary_part = (((y as i64 + position.get_y()) as f64 /height as f64)-0.5)/position.get_zoom_factor();
-    let point = Complex::new(real_part, imaginary_part);
+// Number of iterations after which a point is considered to be part of the mandelbrot set
+pub const DEFAULT_MAX_ITERATIONS: u32 = 51;
+
+// Natural logarithm built only from basic IEEE operations, which are rounded identically on every
+// platform, so that rendered images are bit for bit the same natively and in wasm
+// (`f64::ln` is provided by the platform's libm)
+pub fn portable_ln(value: f64) -> f64 {
+    if !value.is_finite() || value <= 0.0 {
+        return value.ln();
+    }
+    // Split value into mantissa in [1, 2) and exponent
+    let mut mantissa = value;
+    let mut exponent = 0;
+    while mantissa >= 2.0 {
+        mantissa /= 2.0;
+        exponent += 1;
+    }
+    while mantissa < 1.0 {
+        mantissa *= 2.0;
+        exponent -= 1;
+    }
+    // ln(m) = 2 * atanh((m - 1) / (m + 1)), where the series converges quickly as |s| <= 1/3
+    let s = (mantissa - 1.0) / (mantissa + 1.0);
+    let s_squared = s * s;
+    let mut power = s;
+    let mut sum = 0.0;
+    for n in 0..16 {
+        sum += power / (2 * n + 1) as f64;
+        power *= s_squared;
+    }
+    exponent as f64 * std::f64::consts::LN_2 + 2.0 * sum
+}
+
+// Smoothed iteration count and distance estimate of a point that escaped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Escape {
+    pub smoothed_iterations: f64,
+    // Estimated distance of the point to the set, see
+    // https://en.wikibooks.org/wiki/Fractals/Iterations_in_the_complex_plane/demm
+    pub distance: f64,
+}
+
+// Iterates the mandelbrot series of `point` together with its derivative dz/dc and returns how it
+// escaped, or None if it has not diverged within `max_iter` iterations
+// The iteration count is smoothed for nicer visualization based on: https://stackoverflow.com/questions/369438/smooth-spectrum-for-mandelbrot-set-rendering
+pub fn mandelbrot_escape(point: Complex<f64>, max_iter: u32) -> Option<Escape> {
     let mut z = Complex::new(0.0, 0.0);
-    let max_iter = 51;
+    let mut dz = Complex::new(0.0, 0.0);
     let mut iter = 0;
-    let two: f64 = 2.0;
-    while z.norm() < two && iter < max_iter {
+    // Compare squared magnitudes to avoid the square root
+    while z.norm_sqr() < 4.0 && iter < max_iter {
+        dz = z * dz * 2.0 + 1.0;
         z = z * z + point;
         iter += 1;
     }
-    let mut quotient = 1.0;
-    if iter < max_iter {
-        let smoothed_iter = iter as f64 + 1.0 - z.norm().ln().ln()/two.ln();
-        quotient = smoothed_iter / max_iter as f64;
+    if iter == max_iter {
+        return None;
     }
-    return quotient;
+    Some(escape_from(iter, z.norm_sqr(), dz.norm_sqr()))
 }
 
-// Returns a tuple containing the RGB values for the respective pixel based on the iteration quotient
+// Iterates the series z -> z^2 + c of the Julia set of `c` starting at `point`, together with its
+// derivative dz/dpoint, and returns how it escaped like `mandelbrot_escape`
+pub fn julia_escape(point: Complex<f64>, c: Complex<f64>, max_iter: u32) -> Option<Escape> {
+    let mut z = point;
+    let mut dz = Complex::new(1.0, 0.0);
+    let mut iter = 0;
+    while z.norm_sqr() < 4.0 && iter < max_iter {
+        dz = z * dz * 2.0;
+        z = z * z + c;
+        iter += 1;
+    }
+    if iter == max_iter {
+        return None;
+    }
+    let mut escape = escape_from(iter, z.norm_sqr(), dz.norm_sqr());
+    // Points far outside escape before the first iteration
+    escape.smoothed_iterations = escape.smoothed_iterations.max(0.0);
+    Some(escape)
+}
+
+// Builds the escape of a series that left the escape radius after `iter` iterations, given the
+// squared magnitudes of its last value and derivative
+pub fn escape_from(iter: u32, norm_sqr: f64, derivative_norm_sqr: f64) -> Escape {
+    let log_magnitude = portable_ln(norm_sqr) / 2.0;
+    Escape {
+        smoothed_iterations: iter as f64 + 1.0 - portable_ln(log_magnitude) / std::f64::consts::LN_2,
+        distance: norm_sqr.sqrt() * log_magnitude / derivative_norm_sqr.sqrt(),
+    }
+}
+
+// Returns the number of iterations it took the mandelbrot series to diverge
 // relative to the total number of iterations
-pub fn mandelbrot_rgb_value(x: u32, y: u32, width: u32, height: u32, position: &Position) -> (u8, u8, u8) {
-    let quotient = mandelbrot_iteration_quotient(x, y, width, height, position);
+// Return value will be between 0.0 (the original point has already magnitude >2)
+// and 1.0 (the series has not diverged within the maximum number of iterations)
+pub fn mandelbrot_iteration_quotient(point: Complex<f64>, max_iter: u32) -> f64 {
+    match mandelbrot_escape(point, max_iter) {
+        Some(escape) => escape.smoothed_iterations / max_iter as f64,
+        None => 1.0,
+    }
+}
+
+// Same as `mandelbrot_iteration_quotient` for the Julia set of `c`
+pub fn julia_iteration_quotient(point: Complex<f64>, c: Complex<f64>, max_iter: u32) -> f64 {
+    match julia_escape(point, c, max_iter) {
+        Some(escape) => escape.smoothed_iterations / max_iter as f64,
+        None => 1.0,
+    }
+}
+
+// Maps an iteration quotient to RGB values
+pub fn quotient_rgb_value(quotient: f64) -> (u8, u8, u8) {
     if quotient == 1.0 {
         return (0, 0, 0);
     }
@@ -101,6 +248,18 @@ pub fn mandelbrot_rgb_value(x: u32, y: u32, width: u32, height: u32, position: &
     else {
         return (0, (quotient*255.0) as u8, 0);
     }
+}
 
+// Returns a tuple containing the RGB values for the respective point of the complex plane based on
+// the iteration quotient relative to the total number of iterations
+pub fn mandelbrot_rgb_value_at(point: Complex<f64>) -> (u8, u8, u8) {
+    quotient_rgb_value(mandelbrot_iteration_quotient(point, DEFAULT_MAX_ITERATIONS))
 }
 
+// Returns a tuple containing the RGB values for the respective pixel based on the iteration quotient
+// relative to the total number of iterations
+pub fn mandelbrot_rgb_value(x: u32, y: u32, width: u32, height: u32, position: &Position) -> (u8, u8, u8) {
+    // Convert x / y coordinates to real and imaginary values based on screen position and zoom level
+    let point = position.pixel_to_complex(x as f64, y as f64, width, height);
+    mandelbrot_rgb_value_at(point)
+}
@@ -0,0 +1,436 @@
+//! Export of iteration data as heightfield meshes for 3D printing, in OBJ or binary STL.
+//!
+//! Every pixel becomes a vertex whose height follows its smoothed iteration count through a
+//! transfer function, points of the set form the top plateau. The relief stands on a base and is
+//! closed by walls and a bottom face, so that the mesh is watertight: every edge is shared by
+//! exactly two triangles with opposite orientation. Normals point outwards.
+//!
+//! Flat regions can be decimated: the grid is covered by a quadtree whose leaves are as large as
+//! possible while their heights stay within a tolerance. Leaves are fanned from their centre over
+//! all vertices on their border used by smaller neighbours, so that no cracks open between them.
+
+use crate::iteration_data::IterationData;
+use std::fmt;
+use std::io::{self, Write};
+
+// Mapping from the iteration counts to heights between 0 and 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeightTransfer {
+    Linear,
+    SquareRoot,
+    // Logarithm of the iteration count, which brings out the low counts far from the set
+    Logarithmic,
+    Power(f64),
+}
+
+impl HeightTransfer {
+    // Height of a point escaping after `iterations`, 1 for points of the set
+    pub fn apply(&self, iterations: f32, max_iterations: u32) -> f64 {
+        if !iterations.is_finite() {
+            return 1.0;
+        }
+        let max = max_iterations.max(1) as f64;
+        let quotient = (iterations as f64 / max).clamp(0.0, 1.0);
+        match self {
+            HeightTransfer::Linear => quotient,
+            HeightTransfer::SquareRoot => quotient.sqrt(),
+            HeightTransfer::Logarithmic => (quotient * max).ln_1p() / max.ln_1p(),
+            HeightTransfer::Power(exponent) => quotient.powf(*exponent),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshSettings {
+    pub transfer: HeightTransfer,
+    // Passes of a 3 x 3 box blur over the heights
+    pub smoothing: u32,
+    // Width of the model along the x axis, in model units such as millimetres
+    pub size: f64,
+    // Height of the relief from its lowest to its highest point
+    pub relief: f64,
+    // Thickness below the lowest point of the relief
+    pub base: f64,
+    // Largest difference of heights within a flat region merged into larger triangles, no
+    // decimation if None
+    pub decimation: Option<f64>,
+}
+
+impl Default for MeshSettings {
+    fn default() -> MeshSettings {
+        MeshSettings {
+            transfer: HeightTransfer::Logarithmic,
+            smoothing: 1,
+            size: 100.0,
+            relief: 10.0,
+            base: 2.0,
+            decimation: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeshError {
+    TooSmall { width: u32, height: u32 },
+    InvalidSetting(&'static str),
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshError::TooSmall { width, height } => write!(
+                f,
+                "a heightfield of {} x {} pixels is too small, at least 2 x 2 are needed",
+                width, height
+            ),
+            MeshError::InvalidSetting(name) => write!(f, "invalid mesh setting '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+// Heights between 0 and 1 of a grid in row major order, rows top down
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heightfield {
+    pub width: u32,
+    pub height: u32,
+    pub values: Vec<f64>,
+}
+
+impl Heightfield {
+    pub fn from_iteration_data(data: &IterationData, transfer: HeightTransfer) -> Heightfield {
+        Heightfield {
+            width: data.viewport.get_width(),
+            height: data.viewport.get_height(),
+            values: data
+                .iterations
+                .iter()
+                .map(|iterations| transfer.apply(*iterations, data.max_iterations))
+                .collect(),
+        }
+    }
+
+    pub fn value(&self, row: u32, col: u32) -> f64 {
+        self.values[row as usize * self.width as usize + col as usize]
+    }
+
+    // Averages every value with its neighbours `passes` times, the border is extended outwards
+    pub fn smoothed(&self, passes: u32) -> Heightfield {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let mut values = self.values.clone();
+        for _ in 0..passes {
+            let previous = values.clone();
+            let at = |row: i64, col: i64| {
+                let (row, col) = (row.clamp(0, height - 1), col.clamp(0, width - 1));
+                previous[(row * width + col) as usize]
+            };
+            for row in 0..height {
+                for col in 0..width {
+                    let mut sum = 0.0;
+                    for d_row in -1..=1 {
+                        for d_col in -1..=1 {
+                            sum += at(row + d_row, col + d_col);
+                        }
+                    }
+                    values[(row * width + col) as usize] = sum / 9.0;
+                }
+            }
+        }
+        Heightfield {
+            width: self.width,
+            height: self.height,
+            values,
+        }
+    }
+}
+
+// Triangle mesh with triangles wound counterclockwise seen from outside
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<[f64; 3]>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+// Square of `size` x `size` grid cells with its top left corner at `(row, col)`
+#[derive(Debug, Clone, Copy)]
+struct Leaf {
+    row: u32,
+    col: u32,
+    size: u32,
+}
+
+impl Mesh {
+    // Heightfield mesh of the iteration data, with the x axis to the right of the image and the
+    // y axis up
+    pub fn from_iteration_data(
+        data: &IterationData,
+        settings: &MeshSettings,
+    ) -> Result<Mesh, MeshError> {
+        let field =
+            Heightfield::from_iteration_data(data, settings.transfer).smoothed(settings.smoothing);
+        Mesh::from_heightfield(&field, settings)
+    }
+
+    pub fn from_heightfield(
+        field: &Heightfield,
+        settings: &MeshSettings,
+    ) -> Result<Mesh, MeshError> {
+        if field.width < 2 || field.height < 2 {
+            return Err(MeshError::TooSmall {
+                width: field.width,
+                height: field.height,
+            });
+        }
+        let positive = |value: f64| value.is_finite() && value > 0.0;
+        if !positive(settings.size) {
+            return Err(MeshError::InvalidSetting("size"));
+        }
+        if !positive(settings.relief) {
+            return Err(MeshError::InvalidSetting("relief"));
+        }
+        // A base keeps the walls from collapsing where the relief is at its lowest
+        if !positive(settings.base) {
+            return Err(MeshError::InvalidSetting("base"));
+        }
+        if let Some(tolerance) = settings.decimation {
+            if !(tolerance.is_finite() && tolerance >= 0.0) {
+                return Err(MeshError::InvalidSetting("decimation"));
+            }
+        }
+
+        let (min, max) = field
+            .values
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+                (min.min(*value), max.max(*value))
+            });
+        let range = if max > min { max - min } else { 1.0 };
+        let spacing = settings.size / (field.width - 1) as f64;
+        let z = |row: u32, col: u32| {
+            settings.base + (field.value(row, col) - min) / range * settings.relief
+        };
+
+        let leaves = quadtree(field, settings.decimation, z);
+        // Grid points that are corners of leaves, only these become vertices
+        let columns = field.width as usize;
+        let mut used = vec![false; columns * field.height as usize];
+        for leaf in &leaves {
+            for (row, col) in leaf.corners() {
+                used[row as usize * columns + col as usize] = true;
+            }
+        }
+
+        let mut mesh = Mesh {
+            vertices: Vec::new(),
+            triangles: Vec::new(),
+        };
+        let mut top = vec![usize::MAX; used.len()];
+        let bottom_row = field.height - 1;
+        let position = |row: u32, col: u32| {
+            [
+                col as f64 * spacing,
+                (bottom_row - row) as f64 * spacing,
+                z(row, col),
+            ]
+        };
+        for row in 0..field.height {
+            for col in 0..field.width {
+                let idx = row as usize * columns + col as usize;
+                if used[idx] {
+                    top[idx] = mesh.vertices.len();
+                    mesh.vertices.push(position(row, col));
+                }
+            }
+        }
+        let vertex = |row: u32, col: u32| top[row as usize * columns + col as usize];
+
+        // Top faces, fanned from the centre over the border of every leaf
+        for leaf in &leaves {
+            let border: Vec<usize> = leaf
+                .border()
+                .filter(|(row, col)| used[*row as usize * columns + *col as usize])
+                .map(|(row, col)| vertex(row, col))
+                .collect();
+            if border.len() == 4 {
+                mesh.triangles.push([border[0], border[1], border[2]]);
+                mesh.triangles.push([border[0], border[2], border[3]]);
+            } else {
+                let centre = mesh.vertices.len();
+                let half = leaf.size / 2;
+                mesh.vertices
+                    .push(position(leaf.row + half, leaf.col + half));
+                for (idx, start) in border.iter().enumerate() {
+                    let end = border[(idx + 1) % border.len()];
+                    mesh.triangles.push([centre, *start, end]);
+                }
+            }
+        }
+
+        // Walls down to the floor along the border of the grid and a bottom face fanned from its
+        // centre
+        let outline = rectangle_border((0, 0), (field.height - 1, field.width - 1))
+            .filter(|(row, col)| used[*row as usize * columns + *col as usize])
+            .collect::<Vec<_>>();
+        let floor_start = mesh.vertices.len();
+        for (row, col) in &outline {
+            let [x, y, _] = position(*row, *col);
+            mesh.vertices.push([x, y, 0.0]);
+        }
+        let floor_centre = mesh.vertices.len();
+        let extent = (field.height - 1) as f64 * spacing;
+        mesh.vertices.push([settings.size / 2.0, extent / 2.0, 0.0]);
+        for idx in 0..outline.len() {
+            let next = (idx + 1) % outline.len();
+            let (a_top, b_top) = (
+                vertex(outline[idx].0, outline[idx].1),
+                vertex(outline[next].0, outline[next].1),
+            );
+            let (a_bottom, b_bottom) = (floor_start + idx, floor_start + next);
+            mesh.triangles.push([a_bottom, b_bottom, b_top]);
+            mesh.triangles.push([a_bottom, b_top, a_top]);
+            mesh.triangles.push([floor_centre, b_bottom, a_bottom]);
+        }
+        Ok(mesh)
+    }
+
+    // Outward unit normal of a triangle, zero for degenerate triangles
+    pub fn normal(&self, triangle: &[usize; 3]) -> [f64; 3] {
+        let [a, b, c] = triangle.map(|idx| self.vertices[idx]);
+        let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+        let n = [
+            u[1] * v[2] - u[2] * v[1],
+            u[2] * v[0] - u[0] * v[2],
+            u[0] * v[1] - u[1] * v[0],
+        ];
+        let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        if length == 0.0 {
+            return [0.0; 3];
+        }
+        [n[0] / length, n[1] / length, n[2] / length]
+    }
+
+    // Enclosed volume, from the signed volumes of the tetrahedra between the origin and the
+    // triangles
+    pub fn volume(&self) -> f64 {
+        self.triangles
+            .iter()
+            .map(|triangle| {
+                let [a, b, c] = triangle.map(|idx| self.vertices[idx]);
+                (a[0] * (b[1] * c[2] - b[2] * c[1]) - a[1] * (b[0] * c[2] - b[2] * c[0])
+                    + a[2] * (b[0] * c[1] - b[1] * c[0]))
+                    / 6.0
+            })
+            .sum()
+    }
+
+    // Wavefront OBJ with one vertex per line and 1-based triangle indices
+    pub fn write_obj(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "# fractal-rs heightfield")?;
+        for [x, y, z] in &self.vertices {
+            writeln!(writer, "v {} {} {}", x, y, z)?;
+        }
+        for [a, b, c] in &self.triangles {
+            writeln!(writer, "f {} {} {}", a + 1, b + 1, c + 1)?;
+        }
+        Ok(())
+    }
+
+    // Binary STL: an 80 byte header, the number of triangles and for every triangle its normal,
+    // its corners and an empty attribute, all little endian
+    pub fn write_stl(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut header = [b' '; 80];
+        let title = b"fractal-rs heightfield";
+        header[..title.len()].copy_from_slice(title);
+        writer.write_all(&header)?;
+        writer.write_all(&(self.triangles.len() as u32).to_le_bytes())?;
+        let mut bytes = Vec::with_capacity(self.triangles.len() * 50);
+        for triangle in &self.triangles {
+            let normal = self.normal(triangle);
+            let corners = triangle.map(|idx| self.vertices[idx]);
+            for value in normal.iter().chain(corners.iter().flatten()) {
+                bytes.extend_from_slice(&(*value as f32).to_le_bytes());
+            }
+            bytes.extend_from_slice(&0u16.to_le_bytes());
+        }
+        writer.write_all(&bytes)
+    }
+}
+
+impl Leaf {
+    fn corners(&self) -> [(u32, u32); 4] {
+        let (bottom, right) = (self.row + self.size, self.col + self.size);
+        [
+            (self.row, self.col),
+            (self.row, right),
+            (bottom, self.col),
+            (bottom, right),
+        ]
+    }
+
+    // Grid points on the border of the leaf, counterclockwise seen from above
+    fn border(&self) -> impl Iterator<Item = (u32, u32)> {
+        let corner = (self.row + self.size, self.col + self.size);
+        rectangle_border((self.row, self.col), corner)
+    }
+}
+
+// Grid points on the border of the rectangle between the top left and the bottom right corner,
+// counterclockwise seen from above starting at the bottom left corner. Rows grow downwards,
+// against the y axis of the mesh
+fn rectangle_border(
+    (top, left): (u32, u32),
+    (bottom, right): (u32, u32),
+) -> impl Iterator<Item = (u32, u32)> {
+    let along_bottom = (left..right).map(move |col| (bottom, col));
+    let up_right = (top + 1..=bottom).rev().map(move |row| (row, right));
+    let along_top = (left + 1..=right).rev().map(move |col| (top, col));
+    let down_left = (top..bottom).map(move |row| (row, left));
+    along_bottom
+        .chain(up_right)
+        .chain(along_top)
+        .chain(down_left)
+}
+
+// Leaves covering the cells of the grid. Without a tolerance every cell is a leaf, otherwise
+// squares of cells whose heights differ by at most the tolerance are merged
+fn quadtree(field: &Heightfield, tolerance: Option<f64>, z: impl Fn(u32, u32) -> f64) -> Vec<Leaf> {
+    let (rows, columns) = (field.height - 1, field.width - 1);
+    let mut leaves = Vec::new();
+    let mut pending = vec![Leaf {
+        row: 0,
+        col: 0,
+        size: rows.max(columns).next_power_of_two(),
+    }];
+    while let Some(leaf) = pending.pop() {
+        if leaf.row >= rows || leaf.col >= columns {
+            continue;
+        }
+        let inside = leaf.row + leaf.size <= rows && leaf.col + leaf.size <= columns;
+        let flat = |tolerance: f64| {
+            let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+            for row in leaf.row..=leaf.row + leaf.size {
+                for col in leaf.col..=leaf.col + leaf.size {
+                    let height = z(row, col);
+                    min = min.min(height);
+                    max = max.max(height);
+                }
+            }
+            max - min <= tolerance
+        };
+        if leaf.size == 1 || (inside && tolerance.is_some_and(flat)) {
+            leaves.push(leaf);
+            continue;
+        }
+        let half = leaf.size / 2;
+        for (d_row, d_col) in [(0, 0), (0, half), (half, 0), (half, half)] {
+            pending.push(Leaf {
+                row: leaf.row + d_row,
+                col: leaf.col + d_col,
+                size: half,
+            });
+        }
+    }
+    leaves
+}
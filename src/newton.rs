@@ -0,0 +1,383 @@
+//! Newton fractals: the basins of attraction of the roots of a polynomial under Newton's method.
+//!
+//! Every pixel is iterated with `z -> z - a p(z) / p'(z)`, starting at the point of the pixel,
+//! until the steps become tiny. The pixel is coloured by the root it converged to and darkened
+//! the more iterations that took. A relaxation `a` other than 1 gives the relaxed Newton method.
+//!
+//! The Nova variant iterates `z -> z - a p(z) / p'(z) + c` from `z = 1` with the point of the pixel
+//! as `c`. It converges to fixed points that move with `c` instead of the roots, so its pixels are
+//! coloured by the palette like escape time fractals, points that do not converge are black.
+//!
+//! Polynomials are written as `Fractal::spec` parameters separated by `;`, e.g.
+//! `roots=1,-0.5+0.8660254i,-0.5-0.8660254i;relaxation=1` or `coefficients=-1,0,0,1;nova`, where
+//! coefficients are given from the constant term up.
+
+use crate::mandelbrot::Escape;
+use crate::palette::Palette;
+use num::complex::Complex;
+use std::fmt;
+
+// Squared size of the step below which the iteration has converged
+const TOLERANCE: f64 = 1e-12;
+
+// Distance to a root within which a converged point belongs to its basin
+const ROOT_DISTANCE: f64 = 1e-4;
+
+// How quickly colours darken with the number of iterations needed to converge
+const CONVERGENCE_SHADING: f64 = 0.08;
+
+// Upper limit of the degree, the roots of larger polynomials are hard to find reliably
+pub const MAX_DEGREE: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NewtonError {
+    InvalidNumber(String),
+    UnknownParameter(String),
+    MissingPolynomial,
+    ConstantPolynomial,
+    DegreeTooLarge(usize),
+    RootsNotFound,
+}
+
+impl fmt::Display for NewtonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NewtonError::InvalidNumber(number) => write!(f, "invalid complex number '{}'", number),
+            NewtonError::UnknownParameter(parameter) => {
+                write!(f, "unknown parameter '{}'", parameter)
+            }
+            NewtonError::MissingPolynomial => write!(f, "missing roots or coefficients"),
+            NewtonError::ConstantPolynomial => {
+                write!(f, "the polynomial is constant and has no roots")
+            }
+            NewtonError::DegreeTooLarge(degree) => write!(
+                f,
+                "the polynomial has degree {}, at most {} is supported",
+                degree, MAX_DEGREE
+            ),
+            NewtonError::RootsNotFound => write!(f, "the roots of the polynomial were not found"),
+        }
+    }
+}
+
+impl std::error::Error for NewtonError {}
+
+// Result of iterating a point, `root` is the index of the root the point converged to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Convergence {
+    pub root: Option<usize>,
+    pub smoothed_iterations: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Newton {
+    // From the constant term up, the last one is not 0
+    coefficients: Vec<Complex<f64>>,
+    roots: Vec<Complex<f64>>,
+    // Whether the polynomial was given by its roots, which keeps specifications exact
+    from_roots: bool,
+    relaxation: Complex<f64>,
+    nova: bool,
+}
+
+impl Default for Newton {
+    // z^3 - 1
+    fn default() -> Newton {
+        let third = 2.0 * std::f64::consts::PI / 3.0;
+        let roots: Vec<_> = (0..3)
+            .map(|idx| Complex::from_polar(1.0, idx as f64 * third))
+            .collect();
+        Newton::from_roots(&roots).unwrap()
+    }
+}
+
+impl Newton {
+    pub fn from_roots(roots: &[Complex<f64>]) -> Result<Newton, NewtonError> {
+        if roots.is_empty() {
+            return Err(NewtonError::ConstantPolynomial);
+        }
+        if roots.len() > MAX_DEGREE {
+            return Err(NewtonError::DegreeTooLarge(roots.len()));
+        }
+        // Multiply out (z - r0)(z - r1)...
+        let mut coefficients = vec![Complex::new(1.0, 0.0)];
+        for root in roots {
+            let mut product = vec![Complex::new(0.0, 0.0); coefficients.len() + 1];
+            for (idx, coefficient) in coefficients.iter().enumerate() {
+                product[idx + 1] += coefficient;
+                product[idx] -= coefficient * root;
+            }
+            coefficients = product;
+        }
+        Ok(Newton {
+            coefficients,
+            roots: roots.to_vec(),
+            from_roots: true,
+            relaxation: Complex::new(1.0, 0.0),
+            nova: false,
+        })
+    }
+
+    pub fn from_coefficients(coefficients: &[Complex<f64>]) -> Result<Newton, NewtonError> {
+        let degree = coefficients
+            .iter()
+            .rposition(|coefficient| *coefficient != Complex::new(0.0, 0.0))
+            .unwrap_or(0);
+        if degree == 0 {
+            return Err(NewtonError::ConstantPolynomial);
+        }
+        if degree > MAX_DEGREE {
+            return Err(NewtonError::DegreeTooLarge(degree));
+        }
+        let coefficients = coefficients[..=degree].to_vec();
+        let roots = find_roots(&coefficients).ok_or(NewtonError::RootsNotFound)?;
+        Ok(Newton {
+            coefficients,
+            roots,
+            from_roots: false,
+            relaxation: Complex::new(1.0, 0.0),
+            nova: false,
+        })
+    }
+
+    pub fn with_relaxation(self, relaxation: Complex<f64>) -> Newton {
+        Newton { relaxation, ..self }
+    }
+
+    pub fn with_nova(self, nova: bool) -> Newton {
+        Newton { nova, ..self }
+    }
+
+    pub fn coefficients(&self) -> &[Complex<f64>] {
+        &self.coefficients
+    }
+
+    pub fn roots(&self) -> &[Complex<f64>] {
+        &self.roots
+    }
+
+    pub fn relaxation(&self) -> Complex<f64> {
+        self.relaxation
+    }
+
+    pub fn is_nova(&self) -> bool {
+        self.nova
+    }
+
+    // Parses the parameters of a specification, see the module documentation
+    pub fn parse(spec: &str) -> Result<Newton, NewtonError> {
+        let mut newton = None;
+        let mut relaxation = Complex::new(1.0, 0.0);
+        let mut nova = false;
+        for parameter in spec.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            let numbers = || -> Result<Vec<Complex<f64>>, NewtonError> {
+                value.split(',').map(parse_complex).collect()
+            };
+            match key.trim() {
+                "roots" => newton = Some(Newton::from_roots(&numbers()?)?),
+                "coefficients" => newton = Some(Newton::from_coefficients(&numbers()?)?),
+                "relaxation" => relaxation = parse_complex(value)?,
+                "nova" if value.is_empty() => nova = true,
+                _ => return Err(NewtonError::UnknownParameter(parameter.to_string())),
+            }
+        }
+        let newton = newton.ok_or(NewtonError::MissingPolynomial)?;
+        Ok(newton.with_relaxation(relaxation).with_nova(nova))
+    }
+
+    pub fn to_spec(&self) -> String {
+        let (key, numbers) = if self.from_roots {
+            ("roots", &self.roots)
+        } else {
+            ("coefficients", &self.coefficients)
+        };
+        let numbers: Vec<String> = numbers.iter().map(|n| format_complex(*n)).collect();
+        let mut spec = format!(
+            "{}={};relaxation={}",
+            key,
+            numbers.join(","),
+            format_complex(self.relaxation)
+        );
+        if self.nova {
+            spec.push_str(";nova");
+        }
+        spec
+    }
+
+    // Iterates the point until it converges, None if it does not within `max_iter` iterations
+    pub fn converge(&self, point: Complex<f64>, max_iter: u32) -> Option<Convergence> {
+        let (mut z, c) = if self.nova {
+            (Complex::new(1.0, 0.0), point)
+        } else {
+            (point, Complex::new(0.0, 0.0))
+        };
+        let mut previous_step = f64::INFINITY;
+        for iter in 0..max_iter {
+            let (value, derivative) = self.evaluate(z);
+            let next = z - self.relaxation * value / derivative + c;
+            let step = (next - z).norm_sqr();
+            if !step.is_finite() {
+                return None;
+            }
+            z = next;
+            if step < TOLERANCE {
+                let root = if self.nova {
+                    None
+                } else {
+                    self.roots
+                        .iter()
+                        .position(|root| (z - root).norm_sqr() < ROOT_DISTANCE * ROOT_DISTANCE)
+                };
+                return Some(Convergence {
+                    root,
+                    smoothed_iterations: smooth(iter, previous_step, step),
+                });
+            }
+            previous_step = step;
+        }
+        None
+    }
+
+    // Value and derivative of the polynomial by Horner's method
+    fn evaluate(&self, z: Complex<f64>) -> (Complex<f64>, Complex<f64>) {
+        let mut value = Complex::new(0.0, 0.0);
+        let mut derivative = Complex::new(0.0, 0.0);
+        for coefficient in self.coefficients.iter().rev() {
+            derivative = derivative * z + value;
+            value = value * z + coefficient;
+        }
+        (value, derivative)
+    }
+
+    // Iterations needed to converge, Newton fractals have no distance estimate
+    pub fn escape(&self, point: Complex<f64>, max_iter: u32) -> Option<Escape> {
+        self.converge(point, max_iter).map(|convergence| Escape {
+            smoothed_iterations: convergence.smoothed_iterations,
+            distance: f64::INFINITY,
+        })
+    }
+
+    pub fn iteration_quotient(&self, point: Complex<f64>, max_iter: u32) -> f64 {
+        match self.converge(point, max_iter) {
+            Some(convergence) => convergence.smoothed_iterations / max_iter as f64,
+            None => 1.0,
+        }
+    }
+
+    // Colour of the basin the point belongs to, shaded by the speed of convergence. The basins
+    // take evenly spaced colours of the palette, the Nova variant uses the palette directly
+    pub fn rgb_value(&self, point: Complex<f64>, max_iter: u32, palette: &Palette) -> (u8, u8, u8) {
+        let convergence = match self.converge(point, max_iter) {
+            Some(convergence) => convergence,
+            None => return (0, 0, 0),
+        };
+        if self.nova {
+            return palette.rgb_value(convergence.smoothed_iterations / max_iter as f64);
+        }
+        let root = match convergence.root {
+            Some(root) => root,
+            None => return (0, 0, 0),
+        };
+        let (r, g, b) = palette.rgb_value((root as f64 + 0.5) / self.roots.len() as f64);
+        let factor = (-CONVERGENCE_SHADING * convergence.smoothed_iterations).exp();
+        let shade = |channel: u8| (channel as f64 * factor) as u8;
+        (shade(r), shade(g), shade(b))
+    }
+}
+
+// Interpolates the iteration at which the squared step size crossed the tolerance, assuming
+// the logarithm of the step grows geometrically like for quadratic convergence
+fn smooth(iter: u32, previous_step: f64, step: f64) -> f64 {
+    if previous_step >= 1.0 || step <= 0.0 {
+        return iter as f64;
+    }
+    let (previous, last) = (previous_step.ln(), step.ln());
+    let fraction = (TOLERANCE.ln() / previous).ln() / (last / previous).ln();
+    if fraction.is_finite() {
+        iter as f64 - 1.0 + fraction.clamp(0.0, 1.0)
+    } else {
+        iter as f64
+    }
+}
+
+// All roots at once with the Durand-Kerner method, None if they do not converge
+fn find_roots(coefficients: &[Complex<f64>]) -> Option<Vec<Complex<f64>>> {
+    let degree = coefficients.len() - 1;
+    let leading = coefficients[degree];
+    let monic: Vec<_> = coefficients.iter().map(|c| c / leading).collect();
+    let evaluate = |z: Complex<f64>| {
+        monic
+            .iter()
+            .rev()
+            .fold(Complex::new(0.0, 0.0), |value, c| value * z + c)
+    };
+    // The usual starting values, powers of a number that is neither real nor a root of unity
+    let seed = Complex::new(0.4, 0.9);
+    let mut roots: Vec<_> = (0..degree).map(|idx| seed.powi(idx as i32)).collect();
+    for _ in 0..1000 {
+        let mut change: f64 = 0.0;
+        for idx in 0..degree {
+            let denominator = (0..degree)
+                .filter(|other| *other != idx)
+                .fold(Complex::new(1.0, 0.0), |product, other| {
+                    product * (roots[idx] - roots[other])
+                });
+            let step = evaluate(roots[idx]) / denominator;
+            roots[idx] -= step;
+            change = change.max(step.norm_sqr());
+        }
+        if !change.is_finite() {
+            return None;
+        }
+        if change < TOLERANCE * TOLERANCE {
+            return Some(roots);
+        }
+    }
+    None
+}
+
+// Parses complex numbers like `1`, `-2.5i`, `i` or `0.5-1e-3i`
+pub fn parse_complex(text: &str) -> Result<Complex<f64>, NewtonError> {
+    let invalid = || NewtonError::InvalidNumber(text.to_string());
+    let number: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let imaginary = |part: &str| -> Option<f64> {
+        match part {
+            "" | "+" => Some(1.0),
+            "-" => Some(-1.0),
+            _ => part.parse().ok(),
+        }
+    };
+    let (real, imag) = match number.strip_suffix('i') {
+        Some(rest) => {
+            // The sign starting the imaginary part, which is not the one of an exponent
+            let split = rest
+                .char_indices()
+                .rev()
+                .find(|(idx, c)| {
+                    (*c == '+' || *c == '-') && *idx > 0 && !rest[..*idx].ends_with(['e', 'E'])
+                })
+                .map(|(idx, _)| idx);
+            match split {
+                Some(idx) => (
+                    rest[..idx].parse().map_err(|_| invalid())?,
+                    imaginary(&rest[idx..]).ok_or_else(invalid)?,
+                ),
+                None => (0.0, imaginary(rest).ok_or_else(invalid)?),
+            }
+        }
+        None => (number.parse().map_err(|_| invalid())?, 0.0),
+    };
+    Ok(Complex::new(real, imag))
+}
+
+// Formats a complex number so that `parse_complex` returns exactly the same number
+pub fn format_complex(number: Complex<f64>) -> String {
+    if number.im == 0.0 {
+        format!("{}", number.re)
+    } else if number.im < 0.0 {
+        format!("{}-{}i", number.re, -number.im)
+    } else {
+        format!("{}+{}i", number.re, number.im)
+    }
+}
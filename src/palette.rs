@@ -0,0 +1,95 @@
+use crate::mandelbrot;
+
+// Maps iteration quotients to colours. Points inside the set (quotient 1.0) are always black
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Palette {
+    #[default]
+    Classic,
+    Grayscale,
+    Fire,
+    Ocean,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 4] = [
+        Palette::Classic,
+        Palette::Grayscale,
+        Palette::Fire,
+        Palette::Ocean,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Palette::Classic => "classic",
+            Palette::Grayscale => "grayscale",
+            Palette::Fire => "fire",
+            Palette::Ocean => "ocean",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Palette> {
+        Palette::ALL
+            .iter()
+            .find(|palette| palette.name() == name)
+            .cloned()
+    }
+
+    // `count` colours of the palette, evenly spaced over the quotients outside of the set
+    pub fn sample(&self, count: usize) -> Vec<(u8, u8, u8)> {
+        (0..count)
+            .map(|idx| self.rgb_value(idx as f64 / count as f64))
+            .collect()
+    }
+
+    // The palette whose samples are closest to the colours, which are taken to be evenly spaced
+    // like the ones returned by `sample`. Colour tables of other programs can only be
+    // approximated, samples of a palette are matched exactly
+    pub fn closest(colors: &[(u8, u8, u8)]) -> Palette {
+        let distance = |palette: &Palette| -> i64 {
+            palette
+                .sample(colors.len())
+                .iter()
+                .zip(colors)
+                .map(|(a, b)| {
+                    let d = |x: u8, y: u8| (x as i64 - y as i64).pow(2);
+                    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+                })
+                .sum()
+        };
+        Palette::ALL
+            .iter()
+            .min_by_key(|palette| distance(palette))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn rgb_value(&self, quotient: f64) -> (u8, u8, u8) {
+        if quotient >= 1.0 {
+            return (0, 0, 0);
+        }
+        let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0) as u8;
+        match self {
+            Palette::Classic => mandelbrot::quotient_rgb_value(quotient),
+            Palette::Grayscale => (channel(quotient), channel(quotient), channel(quotient)),
+            Palette::Fire => (
+                channel(3.0 * quotient),
+                channel(3.0 * quotient - 1.0),
+                channel(3.0 * quotient - 2.0),
+            ),
+            Palette::Ocean => (
+                channel(2.0 * quotient - 1.0),
+                channel(1.5 * quotient),
+                channel(0.3 + quotient),
+            ),
+        }
+    }
+}
+
+// Darkens a colour the closer its point is to the boundary of the set, which brings out thin
+// filaments. `distance` is the distance estimate and `pixel_size` the size of a pixel, both in
+// units of the complex plane
+pub fn shade_by_distance(rgb: (u8, u8, u8), distance: f64, pixel_size: f64) -> (u8, u8, u8) {
+    let factor = (distance / pixel_size).clamp(0.0, 1.0).sqrt().sqrt();
+    let shade = |channel: u8| (channel as f64 * factor) as u8;
+    (shade(rgb.0), shade(rgb.1), shade(rgb.2))
+}
@@ -0,0 +1,594 @@
+//! Fractals in space rendered by ray marching: the Mandelbulb of power n and the Mandelbox.
+//!
+//! Neither has a closed form surface, but both have a distance estimate, a lower bound of the
+//! distance from a point to the fractal. Rays from the camera advance by the estimate until they
+//! get closer to the surface than the size of a pixel. Hits are lit with Phong shading by a light
+//! above and behind the camera and darkened by ambient occlusion, estimated by sampling the
+//! distance along the normal. Colours come from the palette by the distance of the hit from the
+//! origin.
+//!
+//! The image is drawn progressively like the density renderers, `accumulate` traces the next
+//! pixels in row major order. Unlike them it does not show the plane, panning and zooming the view
+//! does not move the camera, so it only starts over for another camera or screen size.
+
+use crate::palette::Palette;
+use rayon::prelude::*;
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+// Most steps along one ray
+const MAX_STEPS: usize = 256;
+
+// Share of the distance estimate a ray advances by, the estimates of the Mandelbulb overshoot a
+// little at high powers
+const STEP_FACTOR: f64 = 0.9;
+
+// Smallest distance counting as a hit, for rays passing close to the camera
+const MIN_HIT_DISTANCE: f64 = 1e-6;
+
+// Points escaping this far from the origin diverge
+const BAILOUT: f64 = 2.0;
+
+// Distances along the normal at which occlusion is sampled, relative to the bounding radius
+const OCCLUSION_STEP: f64 = 0.02;
+const OCCLUSION_SAMPLES: u32 = 5;
+
+// Phong lighting
+const AMBIENT: f64 = 0.2;
+const DIFFUSE: f64 = 0.8;
+const SPECULAR: f64 = 0.4;
+const SHININESS: i32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SolidKind {
+    Mandelbulb,
+    Mandelbox,
+}
+
+impl SolidKind {
+    pub const ALL: [SolidKind; 2] = [SolidKind::Mandelbulb, SolidKind::Mandelbox];
+
+    // Name used in the JavaScript API
+    pub fn name(&self) -> &'static str {
+        match self {
+            SolidKind::Mandelbulb => "mandelbulb",
+            SolidKind::Mandelbox => "mandelbox",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SolidKind> {
+        SolidKind::ALL
+            .iter()
+            .find(|kind| kind.name() == name)
+            .cloned()
+    }
+
+    pub fn parameter_names(&self) -> &'static [&'static str] {
+        match self {
+            SolidKind::Mandelbulb => &["power", "iterations"],
+            SolidKind::Mandelbox => &["scale", "min_radius", "fixed_radius", "iterations"],
+        }
+    }
+
+    // The classic power 8 bulb and the box of scale 2
+    pub fn default_parameters(&self) -> &'static [f64] {
+        match self {
+            SolidKind::Mandelbulb => &[8.0, 12.0],
+            SolidKind::Mandelbox => &[2.0, 0.5, 1.0, 15.0],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RayMarchError {
+    UnknownSolid(String),
+    ParameterCount { expected: usize, found: usize },
+    InvalidParameter(&'static str, f64),
+    InvalidCamera(String),
+}
+
+impl fmt::Display for RayMarchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RayMarchError::UnknownSolid(name) => write!(f, "unknown fractal '{}'", name),
+            RayMarchError::ParameterCount { expected, found } => write!(
+                f,
+                "the fractal has {} parameters, {} were given",
+                expected, found
+            ),
+            RayMarchError::InvalidParameter(name, value) => {
+                write!(f, "invalid value {} for parameter '{}'", value, name)
+            }
+            RayMarchError::InvalidCamera(message) => write!(f, "invalid camera: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for RayMarchError {}
+
+type Vector = [f64; 3];
+
+fn add(a: Vector, b: Vector) -> Vector {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn subtract(a: Vector, b: Vector) -> Vector {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: Vector, factor: f64) -> Vector {
+    [a[0] * factor, a[1] * factor, a[2] * factor]
+}
+
+fn dot(a: Vector, b: Vector) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: Vector, b: Vector) -> Vector {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn length(a: Vector) -> f64 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: Vector) -> Vector {
+    scale(a, 1.0 / length(a))
+}
+
+// Fractal in space with a distance estimate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solid {
+    kind: SolidKind,
+    parameters: Vec<f64>,
+}
+
+impl Solid {
+    // Fractal with the parameters in the order of `parameter_names`
+    pub fn new(kind: SolidKind, parameters: &[f64]) -> Result<Solid, RayMarchError> {
+        let names = kind.parameter_names();
+        if parameters.len() != names.len() {
+            return Err(RayMarchError::ParameterCount {
+                expected: names.len(),
+                found: parameters.len(),
+            });
+        }
+        for (idx, (name, value)) in names.iter().zip(parameters).enumerate() {
+            let valid = match *name {
+                "iterations" => value.fract() == 0.0 && *value >= 1.0 && *value <= 100.0,
+                "power" => *value >= 2.0 && *value <= 32.0,
+                // The box is bounded for scales outside of [-1, 1]
+                "scale" => value.is_finite() && value.abs() > 1.0,
+                "min_radius" => *value > 0.0 && *value < parameters[idx + 1],
+                _ => value.is_finite() && *value > 0.0,
+            };
+            if !valid {
+                return Err(RayMarchError::InvalidParameter(name, *value));
+            }
+        }
+        Ok(Solid {
+            kind,
+            parameters: parameters.to_vec(),
+        })
+    }
+
+    // Fractal of one of the `solid_types`, with the default parameters if `parameters` is empty
+    pub fn from_name(name: &str, parameters: &[f64]) -> Result<Solid, RayMarchError> {
+        let kind = SolidKind::from_name(name)
+            .ok_or_else(|| RayMarchError::UnknownSolid(name.to_string()))?;
+        if parameters.is_empty() {
+            return Solid::new(kind, kind.default_parameters());
+        }
+        Solid::new(kind, parameters)
+    }
+
+    pub fn kind(&self) -> SolidKind {
+        self.kind
+    }
+
+    pub fn parameters(&self) -> &[f64] {
+        &self.parameters
+    }
+
+    // Radius of a sphere around the origin containing the fractal
+    pub fn bounding_radius(&self) -> f64 {
+        match self.kind {
+            // Points further out than the bailout escape in the first step
+            SolidKind::Mandelbulb => BAILOUT,
+            // With the usual radii, the cube of half side 2 (|scale| + 1) / (|scale| - 1) maps
+            // into itself
+            SolidKind::Mandelbox => {
+                let scale = self.parameters[0].abs();
+                2.0 * (scale + 1.0) / (scale - 1.0) * 3f64.sqrt()
+            }
+        }
+    }
+
+    // Camera looking at the whole fractal from above the front
+    pub fn default_camera(&self) -> Camera {
+        let radius = match self.kind {
+            // Bulbs of higher powers fit into a sphere of radius 1.2
+            SolidKind::Mandelbulb => 1.2,
+            // The corners of the box stick out towards the camera
+            SolidKind::Mandelbox => 1.6 * self.bounding_radius() / 3f64.sqrt(),
+        };
+        let position = [0.9 * radius, -2.4 * radius, 1.3 * radius];
+        Camera::new(position, [0.0, 0.0, 0.0], 45.0).unwrap()
+    }
+
+    // Lower bound of the distance from the point to the fractal, 0 inside of it
+    pub fn distance(&self, point: Vector) -> f64 {
+        match self.kind {
+            SolidKind::Mandelbulb => {
+                let (power, iterations) = (self.parameters[0], self.parameters[1] as usize);
+                mandelbulb_distance(point, power, iterations)
+            }
+            SolidKind::Mandelbox => {
+                let iterations = self.parameters[3] as usize;
+                mandelbox_distance(point, &self.parameters[..3], iterations)
+            }
+        }
+    }
+
+    // Direction away from the surface, from differences of the estimate at the corners of a
+    // tetrahedron around the point
+    fn normal(&self, point: Vector, epsilon: f64) -> Vector {
+        let corners = [
+            [1.0, -1.0, -1.0],
+            [-1.0, -1.0, 1.0],
+            [-1.0, 1.0, -1.0],
+            [1.0, 1.0, 1.0],
+        ];
+        let gradient = corners.iter().fold([0.0; 3], |sum, corner| {
+            let distance = self.distance(add(point, scale(*corner, epsilon)));
+            add(sum, scale(*corner, distance))
+        });
+        if length(gradient) == 0.0 {
+            return [0.0, 0.0, 1.0];
+        }
+        normalize(gradient)
+    }
+
+    // Brightness between 0 for points in a crevice and 1 for points on an open surface
+    fn ambient_occlusion(&self, point: Vector, normal: Vector) -> f64 {
+        let step = OCCLUSION_STEP * self.bounding_radius();
+        let (mut occlusion, mut total) = (0.0, 0.0);
+        for sample in 1..=OCCLUSION_SAMPLES {
+            let distance = step * sample as f64;
+            let free = self.distance(add(point, scale(normal, distance)));
+            // Nearer samples matter more
+            let weight = 0.5f64.powi(sample as i32);
+            occlusion += weight * (1.0 - free / distance).max(0.0);
+            total += weight;
+        }
+        1.0 - (occlusion / total).min(1.0)
+    }
+
+    // Distance along the ray to the surface, None if it misses. `threshold` is the size of a
+    // pixel at distance 1
+    pub fn march(&self, origin: Vector, direction: Vector, threshold: f64) -> Option<f64> {
+        // Starts and ends at the bounding sphere
+        let radius = self.bounding_radius();
+        let b = dot(origin, direction);
+        let c = dot(origin, origin) - radius * radius;
+        let discriminant = b * b - c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let (near, far) = (-b - discriminant.sqrt(), -b + discriminant.sqrt());
+        if far < 0.0 {
+            return None;
+        }
+        let mut t = near.max(0.0);
+        for _ in 0..MAX_STEPS {
+            let distance = self.distance(add(origin, scale(direction, t)));
+            if distance < (threshold * t).max(MIN_HIT_DISTANCE) {
+                return Some(t);
+            }
+            t += distance * STEP_FACTOR;
+            if t > far {
+                return None;
+            }
+        }
+        // Rays grazing the surface run out of steps close to it
+        Some(t)
+    }
+}
+
+fn mandelbulb_distance(point: Vector, power: f64, iterations: usize) -> f64 {
+    let mut z = point;
+    let mut derivative = 1.0;
+    let mut radius = length(z);
+    for _ in 0..iterations {
+        if radius > BAILOUT {
+            break;
+        }
+        if radius == 0.0 {
+            return 0.0;
+        }
+        // Raises z to the power in spherical coordinates and adds the point
+        let theta = (z[2] / radius).acos() * power;
+        let phi = z[1].atan2(z[0]) * power;
+        derivative = radius.powf(power - 1.0) * power * derivative + 1.0;
+        let scaled = radius.powf(power);
+        z = add(
+            scale(
+                [
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                ],
+                scaled,
+            ),
+            point,
+        );
+        radius = length(z);
+    }
+    if radius <= 1.0 {
+        return 0.0;
+    }
+    0.5 * radius.ln() * radius / derivative
+}
+
+fn mandelbox_distance(point: Vector, parameters: &[f64], iterations: usize) -> f64 {
+    let (box_scale, min_radius, fixed_radius) = (parameters[0], parameters[1], parameters[2]);
+    let (min_squared, fixed_squared) = (min_radius * min_radius, fixed_radius * fixed_radius);
+    let mut z = point;
+    let mut derivative = 1.0;
+    for _ in 0..iterations {
+        // Box fold: reflects coordinates beyond ±1 back into the unit cube
+        for coordinate in z.iter_mut() {
+            *coordinate = coordinate.clamp(-1.0, 1.0) * 2.0 - *coordinate;
+        }
+        // Sphere fold: inverts points within the fixed radius, scaling up points within the
+        // minimum radius linearly
+        let squared = dot(z, z);
+        let factor = if squared < min_squared {
+            fixed_squared / min_squared
+        } else if squared < fixed_squared {
+            fixed_squared / squared
+        } else {
+            1.0
+        };
+        z = add(scale(z, factor * box_scale), point);
+        derivative = derivative * factor * box_scale.abs() + 1.0;
+        if dot(z, z) > 1e12 {
+            break;
+        }
+    }
+    length(z) / derivative.abs()
+}
+
+// Forward, right and up of the image of a camera
+pub type Basis = (Vector, Vector, Vector);
+
+// Pinhole camera at `position` looking at `look_at`, with `fov` degrees between the top and bottom
+// of the image and the z axis pointing up
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera {
+    pub position: Vector,
+    pub look_at: Vector,
+    pub fov: f64,
+}
+
+impl Camera {
+    pub fn new(position: Vector, look_at: Vector, fov: f64) -> Result<Camera, RayMarchError> {
+        let invalid = |message: &str| Err(RayMarchError::InvalidCamera(message.to_string()));
+        if !position
+            .iter()
+            .chain(&look_at)
+            .all(|value| value.is_finite())
+        {
+            return invalid("the coordinates must be finite");
+        }
+        if position == look_at {
+            return invalid("the camera must not be at the point it looks at");
+        }
+        if !(fov > 0.0 && fov < 180.0) {
+            return invalid("the field of view must be between 0 and 180 degrees");
+        }
+        Ok(Camera {
+            position,
+            look_at,
+            fov,
+        })
+    }
+
+    // Camera of the position, the point looked at and the field of view, seven values in all
+    pub fn from_values(values: &[f64]) -> Result<Camera, RayMarchError> {
+        match values {
+            [x, y, z, look_x, look_y, look_z, fov] => {
+                Camera::new([*x, *y, *z], [*look_x, *look_y, *look_z], *fov)
+            }
+            _ => Err(RayMarchError::InvalidCamera(format!(
+                "expected 7 values, {} were given",
+                values.len()
+            ))),
+        }
+    }
+
+    // Unit vectors pointing forward, right and up in the image
+    pub fn basis(&self) -> Basis {
+        let forward = normalize(subtract(self.look_at, self.position));
+        let mut right = cross(forward, [0.0, 0.0, 1.0]);
+        // Looking straight up or down, the y axis points up in the image
+        if length(right) < 1e-9 {
+            right = cross(forward, [0.0, 1.0, 0.0]);
+        }
+        let right = normalize(right);
+        (forward, right, cross(right, forward))
+    }
+
+    // Direction of the ray through the centre of a pixel
+    pub fn ray(&self, row: u32, column: u32, width: u32, height: u32) -> Vector {
+        self.ray_in(self.basis(), row, column, width, height)
+    }
+
+    // Like `ray` with the basis of the camera computed once for all pixels
+    pub fn ray_in(
+        &self,
+        (forward, right, up): Basis,
+        row: u32,
+        column: u32,
+        width: u32,
+        height: u32,
+    ) -> Vector {
+        let half = height as f64 / 2.0;
+        let tangent = (self.fov.to_radians() / 2.0).tan();
+        let x = (column as f64 + 0.5 - width as f64 / 2.0) / half * tangent;
+        let y = (half - row as f64 - 0.5) / half * tangent;
+        normalize(add(forward, add(scale(right, x), scale(up, y))))
+    }
+}
+
+// Names of the fractals for `Universe::start_solid`
+#[wasm_bindgen]
+pub fn solid_types() -> Vec<String> {
+    SolidKind::ALL
+        .iter()
+        .map(|kind| kind.name().to_string())
+        .collect()
+}
+
+// Names of the parameters of a fractal, empty for unknown fractals
+#[wasm_bindgen]
+pub fn solid_parameter_names(name: &str) -> Vec<String> {
+    SolidKind::from_name(name).map_or(Vec::new(), |kind| {
+        kind.parameter_names()
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    })
+}
+
+#[wasm_bindgen]
+pub fn solid_default_parameters(name: &str) -> Vec<f64> {
+    SolidKind::from_name(name).map_or(Vec::new(), |kind| kind.default_parameters().to_vec())
+}
+
+// Renderer tracing one ray per pixel of a `width` x `height` image, the number of samples is the
+// number of pixels traced so far
+#[derive(Debug, Clone)]
+pub struct RayMarcher {
+    width: u32,
+    height: u32,
+    solid: Solid,
+    camera: Camera,
+    basis: Basis,
+    palette: Palette,
+    pixels: Vec<(u8, u8, u8)>,
+    traced: usize,
+}
+
+impl RayMarcher {
+    pub fn new(
+        width: u32,
+        height: u32,
+        solid: Solid,
+        camera: Camera,
+        palette: Palette,
+    ) -> RayMarcher {
+        RayMarcher {
+            width,
+            height,
+            solid,
+            basis: camera.basis(),
+            camera,
+            palette,
+            pixels: vec![(0, 0, 0); width as usize * height as usize],
+            traced: 0,
+        }
+    }
+
+    pub fn solid(&self) -> &Solid {
+        &self.solid
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // The same fractal seen by another camera or on another screen, with nothing traced yet
+    pub fn restart(&self, width: u32, height: u32, camera: Camera) -> RayMarcher {
+        RayMarcher::new(
+            width,
+            height,
+            self.solid.clone(),
+            camera,
+            self.palette.clone(),
+        )
+    }
+
+    // Traces the next `samples` pixels in parallel on the current rayon thread pool
+    pub fn accumulate(&mut self, samples: u64) {
+        let end = (self.traced as u64)
+            .saturating_add(samples)
+            .min(self.pixels.len() as u64) as usize;
+        let traced: Vec<(u8, u8, u8)> = (self.traced..end)
+            .into_par_iter()
+            .map(|idx| self.trace(idx))
+            .collect();
+        self.pixels[self.traced..end].copy_from_slice(&traced);
+        self.traced = end;
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.traced as u64
+    }
+
+    // Red, green and blue cells of the image, black where no pixel was traced yet
+    pub fn to_cells(&self) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let r = self.pixels.iter().map(|pixel| pixel.0).collect();
+        let g = self.pixels.iter().map(|pixel| pixel.1).collect();
+        let b = self.pixels.iter().map(|pixel| pixel.2).collect();
+        (r, g, b)
+    }
+
+    fn trace(&self, idx: usize) -> (u8, u8, u8) {
+        let (width, height) = (self.width, self.height);
+        let (row, column) = (idx as u32 / width, idx as u32 % width);
+        let direction = self.camera.ray_in(self.basis, row, column, width, height);
+        // Half the size of a pixel at distance 1
+        let threshold = (self.camera.fov.to_radians() / 2.0).tan() / height as f64;
+        let origin = self.camera.position;
+        let t = match self.solid.march(origin, direction, threshold) {
+            Some(t) => t,
+            None => return (0, 0, 0),
+        };
+        let point = add(origin, scale(direction, t));
+        let normal = self
+            .solid
+            .normal(point, (threshold * t).max(MIN_HIT_DISTANCE));
+        let occlusion = self.solid.ambient_occlusion(point, normal);
+
+        let (forward, right, up) = self.basis;
+        let to_light = normalize(add(
+            scale(forward, -0.6),
+            add(scale(up, 0.8), scale(right, -0.4)),
+        ));
+        let diffuse = dot(normal, to_light).max(0.0);
+        let reflected = subtract(scale(normal, 2.0 * dot(normal, to_light)), to_light);
+        let specular = if diffuse > 0.0 {
+            dot(reflected, scale(direction, -1.0))
+                .max(0.0)
+                .powi(SHININESS)
+        } else {
+            0.0
+        };
+
+        let quotient = 0.3 + 0.6 * (length(point) / self.solid.bounding_radius()).min(1.0);
+        let (r, g, b) = self.palette.rgb_value(quotient);
+        let light = (AMBIENT + DIFFUSE * diffuse) * occlusion;
+        let highlight = SPECULAR * specular * occlusion * 255.0;
+        let shade = |channel: u8| (channel as f64 * light + highlight).round().min(255.0) as u8;
+        (shade(r), shade(g), shade(b))
+    }
+}
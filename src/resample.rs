@@ -0,0 +1,55 @@
+use wasm_bindgen::prelude::*;
+
+// Interpolation used when existing cells are resampled to produce a preview of a new view
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Nearest,
+    Bilinear,
+}
+
+fn clamp_index(value: i64, size: u32) -> usize {
+    value.max(0).min(size as i64 - 1) as usize
+}
+
+// Fills `target` by sampling `source` at the fractional position returned by `source_pixel` for
+// every target (row, column). Both buffers are row major and their sizes are given as
+// (width, height). Positions outside of the source buffer are clamped to its nearest edge
+pub fn resample(
+    source: &[u8],
+    source_size: (u32, u32),
+    target: &mut [u8],
+    target_size: (u32, u32),
+    interpolation: Interpolation,
+    source_pixel: impl Fn(u32, u32) -> (f64, f64),
+) {
+    let (width, height) = source_size;
+    let (target_width, target_height) = target_size;
+    for row in 0..target_height {
+        for col in 0..target_width {
+            let (source_row, source_col) = source_pixel(row, col);
+            let idx = (row * target_width + col) as usize;
+            target[idx] = match interpolation {
+                Interpolation::Nearest => {
+                    let r = clamp_index(source_row.round() as i64, height);
+                    let c = clamp_index(source_col.round() as i64, width);
+                    source[r * width as usize + c]
+                }
+                Interpolation::Bilinear => {
+                    let row_floor = source_row.floor();
+                    let col_floor = source_col.floor();
+                    let row_fraction = source_row - row_floor;
+                    let col_fraction = source_col - col_floor;
+                    let r0 = clamp_index(row_floor as i64, height);
+                    let r1 = clamp_index(row_floor as i64 + 1, height);
+                    let c0 = clamp_index(col_floor as i64, width);
+                    let c1 = clamp_index(col_floor as i64 + 1, width);
+                    let at = |r: usize, c: usize| source[r * width as usize + c] as f64;
+                    let top = at(r0, c0) * (1.0 - col_fraction) + at(r0, c1) * col_fraction;
+                    let bottom = at(r1, c0) * (1.0 - col_fraction) + at(r1, c1) * col_fraction;
+                    (top * (1.0 - row_fraction) + bottom * row_fraction).round() as u8
+                }
+            };
+        }
+    }
+}
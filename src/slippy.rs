@@ -0,0 +1,181 @@
+//! Fixed global tiling of the complex plane in the XYZ ("slippy map") scheme used by map viewers
+//! such as Leaflet or OpenLayers.
+//!
+//! Zoom level `z` splits the square with real parts from `PLANE_MIN_RE` to
+//! `PLANE_MIN_RE + PLANE_SIZE` and imaginary parts from `PLANE_MAX_IM - PLANE_SIZE` to
+//! `PLANE_MAX_IM` into `2^z x 2^z` tiles of `TILE_SIZE x TILE_SIZE` pixels. `x` grows along the
+//! real axis and `y` downwards along the imaginary axis. Tiles only depend on basic floating
+//! point operations, so they are identical natively and in wasm.
+
+use crate::fractal::Fractal;
+use crate::mandelbrot;
+use num::complex::Complex;
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+// Edge length of a tile in pixels
+pub const TILE_SIZE: u32 = 256;
+
+// Deepest zoom level at which neighbouring pixels can still be told apart with f64 precision
+pub const MAX_ZOOM: u32 = 40;
+
+// Largest iteration limit tiles can be requested with, higher limits are clamped to it
+pub const MAX_TILE_ITERATIONS: u32 = 10_000;
+
+// Square of the complex plane covered by the single tile of zoom level 0
+pub const PLANE_MIN_RE: f64 = -2.5;
+pub const PLANE_MAX_IM: f64 = 2.0;
+pub const PLANE_SIZE: f64 = 4.0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TileError {
+    UnknownFractal(String),
+    ZoomTooDeep(u32),
+    OutOfRange { z: u32, x: u32, y: u32 },
+    // A URL that does not name a tile
+    NotFound(String),
+    InvalidQuery(String),
+}
+
+impl fmt::Display for TileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TileError::UnknownFractal(name) => write!(f, "unknown fractal '{}'", name),
+            TileError::ZoomTooDeep(z) => {
+                write!(f, "zoom level {} is deeper than the maximum of {}", z, MAX_ZOOM)
+            }
+            TileError::OutOfRange { z, x, y } => {
+                write!(f, "tile {}/{}/{} does not exist at zoom level {}", z, x, y, z)
+            }
+            TileError::NotFound(path) => write!(f, "no tile at {}", path),
+            TileError::InvalidQuery(pair) => write!(f, "invalid query parameter '{}'", pair),
+        }
+    }
+}
+
+impl std::error::Error for TileError {}
+
+// Upper left corner of the tile and the distance between two of its pixels
+pub fn tile_bounds(z: u32, x: u32, y: u32) -> Result<(Complex<f64>, f64), TileError> {
+    if z > MAX_ZOOM {
+        return Err(TileError::ZoomTooDeep(z));
+    }
+    let tiles = 1u64 << z;
+    if x as u64 >= tiles || y as u64 >= tiles {
+        return Err(TileError::OutOfRange { z, x, y });
+    }
+    let tile_extent = PLANE_SIZE / tiles as f64;
+    let corner = Complex::new(
+        PLANE_MIN_RE + x as f64 * tile_extent,
+        PLANE_MAX_IM - y as f64 * tile_extent,
+    );
+    Ok((corner, tile_extent / TILE_SIZE as f64))
+}
+
+// A tile requested by a URL of the form `/tiles/{fractal}/{z}/{x}/{y}.png`, optionally with
+// `?iterations=<n>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileRequest {
+    pub fractal: Fractal,
+    pub z: u32,
+    pub x: u32,
+    pub y: u32,
+    pub max_iterations: u32,
+}
+
+impl TileRequest {
+    // Parses the path and query of a URL. Iteration limits are clamped to
+    // `1..=MAX_TILE_ITERATIONS` and unknown query parameters are ignored
+    pub fn parse(url: &str) -> Result<TileRequest, TileError> {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let not_found = || TileError::NotFound(path.to_string());
+        let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        let (name, z, x, y) = match segments.as_slice() {
+            ["tiles", name, z, x, y] => (*name, *z, *x, *y),
+            _ => return Err(not_found()),
+        };
+        let y = y.strip_suffix(".png").ok_or_else(not_found)?;
+        let number = |value: &str| value.parse::<u32>().map_err(|_| not_found());
+        let fractal =
+            Fractal::from_name(name).ok_or_else(|| TileError::UnknownFractal(name.to_string()))?;
+        let (z, x, y) = (number(z)?, number(x)?, number(y)?);
+        tile_bounds(z, x, y)?;
+
+        let mut max_iterations = default_max_iterations(z);
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            if let Some(value) = pair.strip_prefix("iterations=") {
+                let iterations: u64 = value
+                    .parse()
+                    .map_err(|_| TileError::InvalidQuery(pair.to_string()))?;
+                max_iterations = iterations.clamp(1, MAX_TILE_ITERATIONS as u64) as u32;
+            }
+        }
+        Ok(TileRequest {
+            fractal,
+            z,
+            x,
+            y,
+            max_iterations,
+        })
+    }
+}
+
+// Iteration limit that keeps detail visible when zooming into the tile pyramid
+pub fn default_max_iterations(z: u32) -> u32 {
+    mandelbrot::DEFAULT_MAX_ITERATIONS + 16 * z
+}
+
+// Iteration quotients of all pixels of the tile in row major order, sampled at pixel centres
+pub fn tile_quotients(
+    fractal: &Fractal,
+    z: u32,
+    x: u32,
+    y: u32,
+    max_iterations: u32,
+) -> Result<Vec<f64>, TileError> {
+    let (corner, pixel_size) = tile_bounds(z, x, y)?;
+    let mut quotients = Vec::with_capacity((TILE_SIZE * TILE_SIZE) as usize);
+    for row in 0..TILE_SIZE {
+        for col in 0..TILE_SIZE {
+            let point = Complex::new(
+                corner.re + (col as f64 + 0.5) * pixel_size,
+                corner.im - (row as f64 + 0.5) * pixel_size,
+            );
+            quotients.push(fractal.iteration_quotient(point, max_iterations));
+        }
+    }
+    Ok(quotients)
+}
+
+// Renders tile (z, x, y) of the fractal as RGBA bytes in row major order
+pub fn render_tile(
+    fractal: &Fractal,
+    z: u32,
+    x: u32,
+    y: u32,
+    max_iterations: u32,
+) -> Result<Vec<u8>, TileError> {
+    let quotients = tile_quotients(fractal, z, x, y, max_iterations)?;
+    let mut pixels = Vec::with_capacity(quotients.len() * 4);
+    for quotient in quotients {
+        let (r, g, b) = mandelbrot::quotient_rgb_value(quotient);
+        pixels.extend_from_slice(&[r, g, b, 255]);
+    }
+    Ok(pixels)
+}
+
+// Renders a map tile of the fractal with the given name for JavaScript map viewers, returned as
+// RGBA bytes
+#[wasm_bindgen]
+pub fn render_map_tile(
+    fractal: &str,
+    z: u32,
+    x: u32,
+    y: u32,
+    max_iterations: u32,
+) -> Result<Vec<u8>, JsValue> {
+    Fractal::from_name(fractal)
+        .ok_or_else(|| TileError::UnknownFractal(fractal.to_string()))
+        .and_then(|fractal| render_tile(&fractal, z, x, y, max_iterations))
+        .map_err(|err| JsValue::from(err.to_string()))
+}
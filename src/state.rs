@@ -0,0 +1,182 @@
+//! Compact serialisation of a complete view into a URL-safe string, e.g. for sharing links.
+//!
+//! The state is a little endian binary record encoded with the URL-safe base64 alphabet without
+//! padding. Its first byte is the format version, version 2 continues with:
+//!
+//! | Size  | Content                                                   |
+//! |-------|-----------------------------------------------------------|
+//! | 1     | index of the palette in `Palette::ALL`                    |
+//! | 4     | iteration limit (u32)                                     |
+//! | 4 + 4 | width and height of the screen (u32)                      |
+//! | 8 + 8 | x and y offset of the `Position` (i64)                    |
+//! | 8     | zoom factor of the `Position` (f64)                       |
+//! | n     | UTF-8 text of `Fractal::spec` up to the end               |
+//!
+//! Version 1 has the index of the fractal in `Fractal::ALL` as a single byte in front of the
+//! palette instead of the specification at the end, such states are still decoded.
+//!
+//! New fractals and palettes have to be appended to their `ALL` lists to keep old links valid.
+
+use crate::fractal::{Fractal, RenderSettings};
+use crate::mandelbrot::Position;
+use crate::palette::Palette;
+use std::convert::TryInto;
+use std::fmt;
+
+pub const VERSION: u8 = 2;
+
+// Size of version 1 states and of version 2 states without the fractal
+const ENCODED_SIZE: usize = 39;
+const FIXED_SIZE: usize = 38;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    InvalidEncoding,
+    UnsupportedVersion(u8),
+    InvalidLength(usize),
+    UnknownFractal(u8),
+    InvalidFractal(String),
+    UnknownPalette(u8),
+    EmptyScreen,
+    InvalidZoom,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::InvalidEncoding => write!(f, "view state is not URL-safe base64"),
+            StateError::UnsupportedVersion(version) => write!(
+                f,
+                "view state version {} is not supported, expected {}",
+                version, VERSION
+            ),
+            StateError::InvalidLength(length) => {
+                write!(f, "view state has an invalid length of {} bytes", length)
+            }
+            StateError::UnknownFractal(idx) => write!(f, "unknown fractal #{}", idx),
+            StateError::InvalidFractal(spec) => write!(f, "invalid fractal '{}'", spec),
+            StateError::UnknownPalette(idx) => write!(f, "unknown palette #{}", idx),
+            StateError::EmptyScreen => write!(f, "view state has an empty screen"),
+            StateError::InvalidZoom => write!(f, "view state has an invalid zoom factor"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+// Everything needed to render a view of a `Universe` again exactly
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewState {
+    pub settings: RenderSettings,
+    pub position: Position,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ViewState {
+    pub fn encode(&self) -> String {
+        let palette = Palette::ALL
+            .iter()
+            .position(|palette| *palette == self.settings.palette)
+            .unwrap_or(0) as u8;
+        let spec = self.settings.fractal.spec();
+        let mut bytes = Vec::with_capacity(FIXED_SIZE + spec.len());
+        bytes.push(VERSION);
+        bytes.push(palette);
+        bytes.extend_from_slice(&self.settings.max_iterations.to_le_bytes());
+        bytes.extend_from_slice(&self.width.to_le_bytes());
+        bytes.extend_from_slice(&self.height.to_le_bytes());
+        bytes.extend_from_slice(&self.position.get_x().to_le_bytes());
+        bytes.extend_from_slice(&self.position.get_y().to_le_bytes());
+        bytes.extend_from_slice(&self.position.get_zoom_factor().to_le_bytes());
+        bytes.extend_from_slice(spec.as_bytes());
+        encode_base64(&bytes)
+    }
+
+    pub fn decode(state: &str) -> Result<ViewState, StateError> {
+        let bytes = decode_base64(state).ok_or(StateError::InvalidEncoding)?;
+        // Fractal and the remaining fields in the layout of version 2
+        let (fractal, bytes) = match bytes.first() {
+            Some(1) => {
+                if bytes.len() != ENCODED_SIZE {
+                    return Err(StateError::InvalidLength(bytes.len()));
+                }
+                let fractal = Fractal::ALL
+                    .get(bytes[1] as usize)
+                    .cloned()
+                    .ok_or(StateError::UnknownFractal(bytes[1]))?;
+                (fractal, &bytes[1..])
+            }
+            Some(&VERSION) => {
+                if bytes.len() < FIXED_SIZE {
+                    return Err(StateError::InvalidLength(bytes.len()));
+                }
+                let spec = String::from_utf8_lossy(&bytes[FIXED_SIZE..]);
+                let fractal = Fractal::from_spec(&spec)
+                    .ok_or_else(|| StateError::InvalidFractal(spec.to_string()))?;
+                (fractal, &bytes[..FIXED_SIZE])
+            }
+            Some(&version) => return Err(StateError::UnsupportedVersion(version)),
+            None => return Err(StateError::InvalidLength(0)),
+        };
+        let palette = Palette::ALL
+            .get(bytes[1] as usize)
+            .cloned()
+            .ok_or(StateError::UnknownPalette(bytes[1]))?;
+        // The length has been checked, so all fields are complete
+        let u32_at = |start: usize| u32::from_le_bytes(bytes[start..start + 4].try_into().unwrap());
+        let bytes_8_at = |start: usize| -> [u8; 8] { bytes[start..start + 8].try_into().unwrap() };
+        let width = u32_at(6);
+        let height = u32_at(10);
+        if width == 0 || height == 0 {
+            return Err(StateError::EmptyScreen);
+        }
+        let zoom_factor = f64::from_le_bytes(bytes_8_at(30));
+        if !Position::is_valid_zoom_factor(zoom_factor) {
+            return Err(StateError::InvalidZoom);
+        }
+        Ok(ViewState {
+            settings: RenderSettings::new(fractal, u32_at(2), palette),
+            position: Position::new(
+                i64::from_le_bytes(bytes_8_at(14)),
+                i64::from_le_bytes(bytes_8_at(22)),
+                zoom_factor,
+            ),
+            width,
+            height,
+        })
+    }
+}
+
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 4).div_ceil(3));
+    for chunk in bytes.chunks(3) {
+        let value = chunk.iter().enumerate().fold(0u32, |value, (idx, byte)| {
+            value | (*byte as u32) << (16 - 8 * idx)
+        });
+        for idx in 0..=chunk.len() {
+            encoded.push(ALPHABET[(value >> (18 - 6 * idx) & 63) as usize] as char);
+        }
+    }
+    encoded
+}
+
+pub(crate) fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.as_bytes().chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut value = 0u32;
+        for (idx, c) in chunk.iter().enumerate() {
+            let digit = ALPHABET.iter().position(|a| a == c)? as u32;
+            value |= digit << (18 - 6 * idx);
+        }
+        for idx in 0..chunk.len() - 1 {
+            bytes.push((value >> (16 - 8 * idx)) as u8);
+        }
+    }
+    Some(bytes)
+}
@@ -0,0 +1,283 @@
+use crate::fractal::RenderSettings;
+use crate::mandelbrot::Position;
+use std::collections::HashMap;
+use std::sync::mpsc;
+
+// Edge length of the square tiles the screen is split into
+pub const TILE_SIZE: u32 = 64;
+
+// Number of tiles kept in the cache unless configured otherwise
+pub const DEFAULT_CACHE_CAPACITY: usize = 1024;
+
+// Identifies a tile by its row and column in the grid of global pixel coordinates (screen pixel plus
+// position offset) of a view. The mapping from pixels to the complex plane depends on the zoom
+// factor and the screen size, so those are part of the key as well. Tiles rendered with other
+// `RenderSettings` have to be removed from the cache when the settings change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileKey {
+    zoom_bits: u64,
+    width: u32,
+    height: u32,
+    row: i64,
+    col: i64,
+}
+
+#[derive(Debug)]
+pub struct Tile {
+    cells_r: Vec<u8>,
+    cells_g: Vec<u8>,
+    cells_b: Vec<u8>,
+}
+
+impl TileKey {
+    pub fn new(position: &Position, width: u32, height: u32, row: i64, col: i64) -> TileKey {
+        TileKey {
+            zoom_bits: position.get_zoom_factor().to_bits(),
+            width,
+            height,
+            row,
+            col,
+        }
+    }
+
+    pub fn get_row(&self) -> i64 {
+        self.row
+    }
+
+    pub fn get_col(&self) -> i64 {
+        self.col
+    }
+
+    // Global pixel coordinates of the top left corner of the tile
+    fn origin(&self) -> (i64, i64) {
+        (self.row * TILE_SIZE as i64, self.col * TILE_SIZE as i64)
+    }
+}
+
+// Returns the keys of all tiles overlapping the screen, ordered by the distance of their centre to
+// the centre of the screen so that the middle of the view is rendered first
+pub fn visible_tiles(position: &Position, width: u32, height: u32) -> Vec<TileKey> {
+    let tile_size = TILE_SIZE as i64;
+    let first_row = position.get_x().div_euclid(tile_size);
+    let last_row = (position.get_x() + height as i64 - 1).div_euclid(tile_size);
+    let first_col = position.get_y().div_euclid(tile_size);
+    let last_col = (position.get_y() + width as i64 - 1).div_euclid(tile_size);
+
+    let mut keys = Vec::new();
+    for row in first_row..=last_row {
+        for col in first_col..=last_col {
+            keys.push(TileKey::new(position, width, height, row, col));
+        }
+    }
+    // Twice the coordinates to keep the centres integral
+    let centre_row = 2 * position.get_x() + height as i64;
+    let centre_col = 2 * position.get_y() + width as i64;
+    keys.sort_by_key(|key| {
+        let (row, col) = key.origin();
+        let d_row = 2 * row + tile_size - centre_row;
+        let d_col = 2 * col + tile_size - centre_col;
+        d_row * d_row + d_col * d_col
+    });
+    keys
+}
+
+// Calculates the cells of a single tile
+pub fn render_tile(key: &TileKey, position: &Position, settings: &RenderSettings) -> Tile {
+    let size = (TILE_SIZE * TILE_SIZE) as usize;
+    let mut tile = Tile {
+        cells_r: vec![0; size],
+        cells_g: vec![0; size],
+        cells_b: vec![0; size],
+    };
+    let (origin_row, origin_col) = key.origin();
+    for tile_row in 0..TILE_SIZE {
+        for tile_col in 0..TILE_SIZE {
+            // Screen pixel, which may lie outside of the screen for tiles at the border
+            let row = origin_row + tile_row as i64 - position.get_x();
+            let col = origin_col + tile_col as i64 - position.get_y();
+            let point = position.pixel_to_complex(row as f64, col as f64, key.width, key.height);
+            let (r, g, b) = settings.rgb_value_at(point);
+            let idx = (tile_row * TILE_SIZE + tile_col) as usize;
+            tile.cells_r[idx] = r;
+            tile.cells_g[idx] = g;
+            tile.cells_b[idx] = b;
+        }
+    }
+    tile
+}
+
+// Renders the tiles one after another on the current thread in the given order
+pub fn render_tiles(
+    keys: &[TileKey],
+    position: &Position,
+    settings: &RenderSettings,
+) -> Vec<(TileKey, Tile)> {
+    keys.iter()
+        .map(|key| (*key, render_tile(key, position, settings)))
+        .collect()
+}
+
+// Renders the tiles on the current rayon pool. Tiles are spawned in FIFO order so that threads
+// pick them up in the given order
+pub fn render_tiles_parallel(
+    keys: &[TileKey],
+    position: &Position,
+    settings: &RenderSettings,
+) -> Vec<(TileKey, Tile)> {
+    let (tx, rx) = mpsc::channel();
+    rayon::scope_fifo(|scope| {
+        for key in keys {
+            let tx = tx.clone();
+            scope.spawn_fifo(move |_| {
+                tx.send((*key, render_tile(key, position, settings))).unwrap();
+            });
+        }
+    });
+    drop(tx);
+    rx.into_iter().collect()
+}
+
+// Copies the part of the tile that is visible on the screen into the cells
+pub fn draw_tile(
+    key: &TileKey,
+    tile: &Tile,
+    position: &Position,
+    cells: (&mut [u8], &mut [u8], &mut [u8]),
+    width: u32,
+    height: u32,
+) {
+    let (cells_r, cells_g, cells_b) = cells;
+    let (origin_row, origin_col) = key.origin();
+    let tile_size = TILE_SIZE as i64;
+    let row_start = (origin_row - position.get_x()).max(0);
+    let row_end = (origin_row + tile_size - position.get_x()).min(height as i64);
+    let col_start = (origin_col - position.get_y()).max(0);
+    let col_end = (origin_col + tile_size - position.get_y()).min(width as i64);
+    if row_start >= row_end || col_start >= col_end {
+        return;
+    }
+    let tile_col_start = (col_start + position.get_y() - origin_col) as usize;
+    let tile_col_end = (col_end + position.get_y() - origin_col) as usize;
+    for row in row_start..row_end {
+        let tile_row = (row + position.get_x() - origin_row) as usize;
+        let tile_start = tile_row * TILE_SIZE as usize;
+        let start = (row * width as i64 + col_start) as usize;
+        let end = (row * width as i64 + col_end) as usize;
+        let source = tile_start + tile_col_start..tile_start + tile_col_end;
+        cells_r[start..end].copy_from_slice(&tile.cells_r[source.clone()]);
+        cells_g[start..end].copy_from_slice(&tile.cells_g[source.clone()]);
+        cells_b[start..end].copy_from_slice(&tile.cells_b[source]);
+    }
+}
+
+// Least recently used cache of rendered tiles
+#[derive(Debug)]
+pub struct TileCache {
+    capacity: usize,
+    clock: u64,
+    tiles: HashMap<TileKey, (u64, Tile)>,
+}
+
+impl TileCache {
+    pub fn new(capacity: usize) -> TileCache {
+        TileCache {
+            capacity,
+            clock: 0,
+            tiles: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    pub fn contains(&self, key: &TileKey) -> bool {
+        self.tiles.contains_key(key)
+    }
+
+    // Returns the tile and marks it as most recently used
+    pub fn get(&mut self, key: &TileKey) -> Option<&Tile> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.tiles.get_mut(key).map(|entry| {
+            entry.0 = clock;
+            &entry.1
+        })
+    }
+
+    pub fn insert(&mut self, key: TileKey, tile: Tile) {
+        self.clock += 1;
+        self.tiles.insert(key, (self.clock, tile));
+        self.evict();
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+    }
+
+    fn evict(&mut self) {
+        while self.tiles.len() > self.capacity {
+            let oldest = self
+                .tiles
+                .iter()
+                .min_by_key(|(_, (last_used, _))| *last_used)
+                .map(|(key, _)| *key);
+            match oldest {
+                Some(key) => self.tiles.remove(&key),
+                None => break,
+            };
+        }
+    }
+}
+
+// Draws the visible tiles of the view into the cells, taking them from the cache where possible
+// and rendering the missing ones with `render`
+pub fn draw_view(
+    position: &Position,
+    settings: &RenderSettings,
+    cache: &mut TileCache,
+    cells: (&mut [u8], &mut [u8], &mut [u8]),
+    width: u32,
+    height: u32,
+    render: impl FnOnce(&[TileKey], &Position, &RenderSettings) -> Vec<(TileKey, Tile)>,
+) {
+    let (cells_r, cells_g, cells_b) = cells;
+    let keys = visible_tiles(position, width, height);
+    let missing: Vec<TileKey> = keys.iter().filter(|key| !cache.contains(key)).copied().collect();
+    for (key, tile) in render(&missing, position, settings) {
+        cache.insert(key, tile);
+    }
+    for key in keys.iter() {
+        match cache.get(key) {
+            Some(tile) => draw_tile(
+                key,
+                tile,
+                position,
+                (&mut *cells_r, &mut *cells_g, &mut *cells_b),
+                width,
+                height,
+            ),
+            // Only happens if the cache is too small to hold the whole screen
+            None => {
+                let tile = render_tile(key, position, settings);
+                draw_tile(
+                    key,
+                    &tile,
+                    position,
+                    (&mut *cells_r, &mut *cells_g, &mut *cells_b),
+                    width,
+                    height,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use crate::fractal::RenderSettings;
+use num::complex::Complex;
+
+// Rectangle of the complex plane shown on a `width` x `height` image with square pixels. Unlike
+// `Position`, which is tied to the screen of a `Universe`, it is used for rendering images of any
+// size natively
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    centre: Complex<f64>,
+    pixel_size: f64,
+    width: u32,
+    height: u32,
+}
+
+impl Viewport {
+    pub fn new(centre: Complex<f64>, pixel_size: f64, width: u32, height: u32) -> Viewport {
+        Viewport {
+            centre,
+            pixel_size,
+            width,
+            height,
+        }
+    }
+
+    // Viewport of the given size whose width spans `extent` along the real axis
+    pub fn with_extent(centre: Complex<f64>, extent: f64, width: u32, height: u32) -> Viewport {
+        Viewport::new(centre, extent / width as f64, width, height)
+    }
+
+    pub fn get_centre(&self) -> Complex<f64> {
+        self.centre
+    }
+
+    pub fn get_pixel_size(&self) -> f64 {
+        self.pixel_size
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    // Same area of the complex plane at a different resolution
+    pub fn resized(&self, width: u32, height: u32) -> Viewport {
+        let pixel_size = self.pixel_size * self.width as f64 / width as f64;
+        Viewport::new(self.centre, pixel_size, width, height)
+    }
+
+    // Point of the complex plane at the centre of pixel (x, y), where y grows downwards
+    pub fn pixel_to_complex(&self, x: u32, y: u32) -> Complex<f64> {
+        Complex::new(
+            self.centre.re + (x as f64 + 0.5 - self.width as f64 / 2.0) * self.pixel_size,
+            self.centre.im - (y as f64 + 0.5 - self.height as f64 / 2.0) * self.pixel_size,
+        )
+    }
+
+    // Renders the `width` x `height` pixels starting at (x, y) as RGBA bytes in row major order
+    pub fn render_rgba(
+        &self,
+        settings: &RenderSettings,
+        (x, y): (u32, u32),
+        (width, height): (u32, u32),
+    ) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+        for row in y..y + height {
+            for col in x..x + width {
+                let (r, g, b) = settings.rgb_value_at(self.pixel_to_complex(col, row));
+                rgba.extend_from_slice(&[r, g, b, 255]);
+            }
+        }
+        rgba
+    }
+}
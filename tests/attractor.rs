@@ -0,0 +1,163 @@
+use fractal_rs::attractor::{
+    self, Attractor, AttractorError, AttractorKind, AttractorRenderer, Projection,
+};
+use fractal_rs::density::{DensityRenderer, Screen};
+use fractal_rs::mandelbrot::Position;
+use fractal_rs::palette::Palette;
+use fractal_rs::Universe;
+use rayon::ThreadPoolBuilder;
+
+#[test]
+pub fn test_attractor_parameters() {
+    for kind in AttractorKind::ALL.iter() {
+        assert_eq!(AttractorKind::from_name(kind.name()), Some(*kind));
+        assert_eq!(
+            kind.parameter_names().len(),
+            kind.default_parameters().len()
+        );
+        assert!(Attractor::new(*kind, kind.default_parameters()).is_ok());
+    }
+    assert_eq!(attractor::attractor_types().len(), 6);
+    assert_eq!(
+        attractor::attractor_parameter_names("henon"),
+        vec!["a".to_string(), "b".to_string()]
+    );
+    assert_eq!(
+        attractor::attractor_default_parameters("ikeda"),
+        vec![0.918]
+    );
+    assert!(attractor::attractor_parameter_names("mandelbrot").is_empty());
+
+    assert_eq!(
+        Attractor::from_name("henon", &[1.4]),
+        Err(AttractorError::ParameterCount {
+            expected: 2,
+            found: 1
+        })
+    );
+    assert_eq!(
+        Attractor::from_name("lorenz", &[10.0, 28.0, 2.0, 0.0]),
+        Err(AttractorError::InvalidParameter("dt", 0.0))
+    );
+    assert!(Attractor::from_name("clifford", &[f64::NAN, 1.0, 1.0, 1.0]).is_err());
+    assert_eq!(
+        Attractor::from_name("lozi", &[]),
+        Err(AttractorError::UnknownAttractor("lozi".to_string()))
+    );
+
+    let lorenz = Attractor::from_name("lorenz", &[]).unwrap();
+    assert_eq!(lorenz.projection(), Projection::XZ);
+    assert_eq!(
+        lorenz.with_projection(Projection::YZ).projection(),
+        Projection::YZ
+    );
+    // Maps of the plane have nothing to project
+    let henon = Attractor::from_name("henon", &[]).unwrap();
+    assert_eq!(
+        henon.with_projection(Projection::YZ).projection(),
+        Projection::XY
+    );
+    assert_eq!(Projection::from_name("xz"), Some(Projection::XZ));
+    assert_eq!(Projection::from_name("xw"), None);
+}
+
+#[test]
+pub fn test_attractor_steps() {
+    let henon = Attractor::from_name("henon", &[]).unwrap();
+    assert_eq!(henon.step([0.0, 0.0, 0.0]), [1.0, 0.0, 0.0]);
+    assert_eq!(henon.step([1.0, 0.0, 0.0]), [1.0 - 1.4, 0.3, 0.0]);
+
+    let clifford = Attractor::from_name("clifford", &[1.0, 2.0, 3.0, 4.0]).unwrap();
+    let [x, y, _] = clifford.step([0.5, 0.25, 0.0]);
+    assert!((x - (0.25f64.sin() + 3.0 * 0.5f64.cos())).abs() < 1e-15);
+    assert!((y - (1.0f64.sin() + 4.0 * 0.5f64.cos())).abs() < 1e-15);
+
+    // The fixed points of the Lorenz flow do not move
+    let lorenz = Attractor::from_name("lorenz", &[]).unwrap();
+    let beta = 8.0 / 3.0;
+    let fixed = [(beta * 27.0f64).sqrt(), (beta * 27.0f64).sqrt(), 27.0];
+    let next = lorenz.step(fixed);
+    assert!(next.iter().zip(&fixed).all(|(a, b)| (a - b).abs() < 1e-9));
+
+    // The Rössler flow rotates around the z axis
+    let rossler = Attractor::from_name("rossler", &[]).unwrap();
+    let [x, y, _] = rossler.step([1.0, 0.0, 0.0]);
+    assert!(x < 1.0 && y > 0.0);
+}
+
+#[test]
+pub fn test_attractor_bounds() {
+    let ((x_min, x_max), (y_min, y_max)) = Attractor::from_name("clifford", &[])
+        .unwrap()
+        .bounds()
+        .unwrap();
+    assert!(x_min >= -2.0 && x_max <= 2.0 && x_max - x_min > 2.0);
+    assert!(y_min >= -1.7 && y_max <= 1.7 && y_max - y_min > 2.0);
+
+    let ((x_min, x_max), (y_min, y_max)) = Attractor::from_name("henon", &[])
+        .unwrap()
+        .bounds()
+        .unwrap();
+    assert!(x_min > -1.3 && x_max < 1.3 && x_max > 1.2);
+    assert!(y_min > -0.4 && y_max < 0.4);
+
+    // The butterfly of the Lorenz attractor is about 40 wide and 50 high
+    let ((x_min, x_max), (z_min, z_max)) = Attractor::from_name("lorenz", &[])
+        .unwrap()
+        .bounds()
+        .unwrap();
+    assert!(x_min < -15.0 && x_max > 15.0 && x_max < 25.0);
+    assert!(z_min > 0.0 && z_max > 40.0 && z_max < 55.0);
+
+    // Without an attractor every orbit diverges
+    let diverging = Attractor::from_name("henon", &[3.0, 0.3]).unwrap();
+    assert_eq!(diverging.bounds(), None);
+    let screen = Screen::new(diverging.fitting_position(16, 16), 16, 16);
+    let mut renderer = AttractorRenderer::new(screen, diverging, Palette::Classic);
+    renderer.accumulate(1000);
+    assert_eq!(renderer.counts().iter().sum::<f32>(), 0.0);
+}
+
+#[test]
+pub fn test_attractor_rendering() {
+    let de_jong = Attractor::from_name("de-jong", &[]).unwrap();
+    let screen = Screen::new(de_jong.fitting_position(32, 32), 32, 32);
+    let mut one_pass = AttractorRenderer::new(screen.clone(), de_jong.clone(), Palette::Grayscale);
+    one_pass.accumulate(40_000);
+    assert_eq!(one_pass.samples(), 40_000);
+    let total: f32 = one_pass.counts().iter().sum();
+    assert!(total > 39_000.0);
+
+    let thread_pool = ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+    let mut two_passes = AttractorRenderer::new(screen, de_jong, Palette::Grayscale);
+    thread_pool.install(|| {
+        two_passes.accumulate(16384);
+        two_passes.accumulate(40_000 - 16384);
+    });
+    let difference: f32 = one_pass
+        .counts()
+        .iter()
+        .zip(two_passes.counts())
+        .map(|(a, b)| (a - b).abs())
+        .sum();
+    assert!(difference / total < 0.1);
+
+    let (r, g, b) = one_pass.to_cells();
+    assert_eq!(r, g);
+    assert_eq!(g, b);
+    assert!(r.iter().any(|value| *value > 250));
+}
+
+#[test]
+pub fn test_universe_attractor() {
+    let thread_pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let universe = Universe::with_thread_pool(32, 32, Position::new(0, 0, 0.3), thread_pool);
+    let rossler = Attractor::from_name("rossler", &[])
+        .unwrap()
+        .with_projection(Projection::XZ);
+    universe.start_strange_attractor(rossler.clone());
+    assert_eq!(universe.get_position(), rossler.fitting_position(32, 32));
+    universe.draw_density(20_000).unwrap();
+    assert_eq!(universe.density().unwrap().samples(), 20_000);
+    assert!(universe.rgba().chunks(4).any(|p| p[0] > 0));
+}
@@ -0,0 +1,71 @@
+use fractal_rs::bookmarks::{self, BookmarkError, BookmarkStore};
+use fractal_rs::fractal::{Fractal, RenderSettings};
+use fractal_rs::mandelbrot::Position;
+use fractal_rs::palette::Palette;
+use fractal_rs::Universe;
+
+mod common;
+
+fn universe(position: Position) -> Universe {
+    let settings = RenderSettings::new(Fractal::Mandelbrot, 90, Palette::Fire);
+    common::universe_with_settings(64, 48, position, settings)
+}
+
+#[test]
+pub fn test_bookmarks_json_round_trip() {
+    let mut store = BookmarkStore::new();
+    let first = universe(Position::new(-20, 3, 1.2));
+    store.insert(
+        first
+            .bookmark("start", vec!["overview".to_string()])
+            .unwrap(),
+    );
+    let second = universe(Position::new(40, -7, 4.5));
+    store.insert(second.bookmark("detail", vec![]).unwrap());
+    // Same name replaces the bookmark
+    store.insert(second.bookmark("start", vec!["again".to_string()]).unwrap());
+
+    assert_eq!(store.names(), ["start", "detail"]);
+    assert_eq!(store.names_with_tag("again"), ["start"]);
+    let thumbnail = store.thumbnail("detail").unwrap();
+    assert_eq!(&thumbnail[1..4], b"PNG");
+
+    let json = store.to_json().unwrap();
+    let imported = BookmarkStore::from_json(&json).unwrap();
+    assert_eq!(imported, store);
+
+    let restored = universe(Position::new(0, 0, 1.0));
+    restored.restore_state(&imported.get("detail").unwrap().state);
+    assert_eq!(restored.rgba(), second.rgba());
+}
+
+#[test]
+pub fn test_invalid_bookmark_files() {
+    let result = BookmarkStore::from_json(r#"{ "version": 2, "bookmarks": [] }"#);
+    assert!(matches!(result, Err(BookmarkError::UnsupportedVersion(2))));
+    let result = BookmarkStore::from_json(
+        r#"{ "version": 1, "bookmarks": [{ "name": "broken", "state": "AQ" }] }"#,
+    );
+    assert!(matches!(result, Err(BookmarkError::InvalidState(name, _)) if name == "broken"));
+    assert!(matches!(
+        BookmarkStore::from_json("[]"),
+        Err(BookmarkError::Json(_))
+    ));
+}
+
+#[test]
+pub fn test_famous_locations() {
+    let location = bookmarks::famous_location("seahorse valley").unwrap();
+    assert_eq!(location.name, "Seahorse Valley");
+    assert!(bookmarks::famous_location("Atlantis").is_none());
+    for name in bookmarks::famous_locations() {
+        let location = bookmarks::famous_location(&name).unwrap();
+        let universe = universe(Position::new(0, 0, 1.0));
+        universe.restore_view(&location.view_metadata(64, 48));
+        let position = universe.get_position();
+        let centre = position.centre(64, 48);
+        // Up to rounding the position to whole pixels
+        let pixel = 1.0 / (64.0 * position.get_zoom_factor());
+        assert!((centre - location.centre).norm() < 2.0 * pixel);
+    }
+}
@@ -0,0 +1,88 @@
+use fractal_rs::buddhabrot::{self, Band, Buddhabrot};
+use fractal_rs::density::{DensityRenderer, Screen};
+use fractal_rs::mandelbrot::Position;
+use fractal_rs::Universe;
+use rayon::ThreadPoolBuilder;
+
+fn whole_set() -> Position {
+    Position::from_centre(num::complex::Complex::new(-0.5, 0.0), 0.3, 32, 32)
+}
+
+fn screen() -> Screen {
+    Screen::new(whole_set(), 32, 32)
+}
+
+#[test]
+pub fn test_accumulation_is_deterministic() {
+    let bands = buddhabrot::buddhabrot_bands(50);
+    let mut one_pass = Buddhabrot::new(screen(), bands);
+    one_pass.accumulate(20_000);
+    assert_eq!(one_pass.samples(), 20_000);
+
+    let total: f32 = one_pass.histograms()[0].iter().sum();
+    assert!(total > 0.0);
+    // The Buddhabrot is symmetric to the real axis, which runs along the rows
+    let histogram = &one_pass.histograms()[0];
+    let left: f32 = (0..32 * 32)
+        .filter(|idx| idx % 32 < 16)
+        .map(|idx| histogram[idx])
+        .sum();
+    assert!((left / total - 0.5).abs() < 0.1);
+
+    // The same samples in several thread pools and passes give the same image
+    let thread_pool = ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+    let mut two_passes = Buddhabrot::new(screen(), bands);
+    thread_pool.install(|| {
+        two_passes.accumulate(8192);
+        two_passes.accumulate(20_000 - 8192);
+    });
+    assert_eq!(two_passes.samples(), 20_000);
+    let difference: f32 = (0..3)
+        .flat_map(|band| {
+            let a = &one_pass.histograms()[band];
+            let b = &two_passes.histograms()[band];
+            a.iter()
+                .zip(b)
+                .map(|(a, b)| (a - b).abs())
+                .collect::<Vec<f32>>()
+        })
+        .sum();
+    assert!(difference / total < 1e-3);
+    assert_eq!(one_pass.histograms()[0], one_pass.histograms()[2]);
+}
+
+#[test]
+pub fn test_nebulabrot_bands() {
+    let bands = [Band::new(0, 200), Band::new(0, 20), Band::new(1000, 2000)];
+    let mut nebulabrot = Buddhabrot::new(screen(), bands);
+    nebulabrot.accumulate(10_000);
+    let total = |band: usize| -> f32 { nebulabrot.histograms()[band].iter().sum() };
+    // Long orbits include the short ones, orbits escaping after 1000 iterations are rare
+    assert!(total(0) > total(1));
+    assert!(total(2) < total(1));
+
+    let (r, g, b) = nebulabrot.to_cells();
+    assert_eq!(r.iter().max(), Some(&255));
+    assert_eq!(g.iter().max(), Some(&255));
+    assert_eq!(r.len(), 32 * 32);
+    assert_eq!(b.len(), 32 * 32);
+}
+
+#[test]
+pub fn test_universe_density() {
+    let thread_pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let universe = Universe::with_thread_pool(32, 32, whole_set(), thread_pool);
+    let bands = buddhabrot::buddhabrot_bands(30);
+    universe.start_density(Box::new(Buddhabrot::new(universe.screen(), bands)));
+    universe.draw_density(5000).unwrap();
+    assert_eq!(universe.density().unwrap().samples(), 5000);
+    let rgba = universe.rgba();
+    // Grey image with every channel equal
+    assert!(rgba.chunks(4).all(|p| p[0] == p[1] && p[1] == p[2]));
+    assert!(rgba.chunks(4).any(|p| p[0] == 255));
+
+    // Moving the view starts the image over
+    universe.zoom_in();
+    universe.draw_density(1000).unwrap();
+    assert_eq!(universe.density().unwrap().samples(), 1000);
+}
@@ -0,0 +1,26 @@
+//! Fixtures shared by the integration tests.
+
+// Every test file only uses some of the fixtures
+#![allow(dead_code)]
+
+use fractal_rs::fractal::RenderSettings;
+use fractal_rs::mandelbrot::Position;
+use fractal_rs::Universe;
+use rayon::ThreadPoolBuilder;
+
+// Universe with the default settings rendering on a single thread
+pub fn universe(width: u32, height: u32, position: Position) -> Universe {
+    let thread_pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    Universe::with_thread_pool(width, height, position, thread_pool)
+}
+
+// Universe with the given settings rendering on a single thread
+pub fn universe_with_settings(
+    width: u32,
+    height: u32,
+    position: Position,
+    settings: RenderSettings,
+) -> Universe {
+    let thread_pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    Universe::with_settings(width, height, position, settings, thread_pool)
+}
@@ -0,0 +1,89 @@
+use fractal_rs::dzi::DeepZoom;
+use fractal_rs::fractal::{Fractal, RenderSettings};
+use fractal_rs::palette::Palette;
+use fractal_rs::viewport::Viewport;
+use num::complex::Complex;
+use png::Decoder;
+use std::fs::{self, File};
+use std::path::Path;
+
+#[test]
+pub fn test_pyramid_levels() {
+    assert_eq!(DeepZoom::max_level(1, 1), 0);
+    assert_eq!(DeepZoom::max_level(1000, 600), 10);
+    assert_eq!(DeepZoom::max_level(1024, 1024), 10);
+    assert_eq!(DeepZoom::level_size(1000, 600, 10), (1000, 600));
+    assert_eq!(DeepZoom::level_size(1000, 600, 9), (500, 300));
+    assert_eq!(DeepZoom::level_size(1000, 600, 1), (2, 2));
+    assert_eq!(DeepZoom::level_size(1000, 600, 0), (1, 1));
+}
+
+fn png_size(path: &Path) -> (u32, u32) {
+    let reader = Decoder::new(File::open(path).unwrap()).read_info().unwrap();
+    (reader.info().width, reader.info().height)
+}
+
+#[test]
+pub fn test_write_pyramid() {
+    let dir = std::env::temp_dir().join(format!("fractal-rs-dzi-{}", std::process::id()));
+    let base = dir.join("poster");
+    let settings = RenderSettings::new(Fractal::Mandelbrot, 20, Palette::Grayscale);
+    let viewport = Viewport::with_extent(Complex::new(-0.75, 0.0), 3.5, 300, 200);
+    let deep_zoom = DeepZoom {
+        tile_size: 128,
+        overlap: 1,
+    };
+    let levels = std::sync::Mutex::new(Vec::new());
+    deep_zoom
+        .write(&base, &settings, &viewport, |level| {
+            levels.lock().unwrap().push(level)
+        })
+        .unwrap();
+    assert_eq!(levels.into_inner().unwrap(), (0..=9).collect::<Vec<_>>());
+
+    assert_eq!(
+        fs::read_to_string(dir.join("poster.dzi")).unwrap(),
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" TileSize=\"128\" \
+         Overlap=\"1\" Format=\"png\">\n  <Size Width=\"300\" Height=\"200\"/>\n</Image>\n"
+    );
+
+    let files = dir.join("poster_files");
+    let mut level_dirs: Vec<String> = fs::read_dir(&files)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    level_dirs.sort_by_key(|name| name.parse::<u32>().unwrap());
+    assert_eq!(
+        level_dirs,
+        (0..=9).map(|level| level.to_string()).collect::<Vec<_>>()
+    );
+
+    // The full resolution level has 3 x 2 tiles that overlap their neighbours by a pixel
+    let mut tiles: Vec<String> = fs::read_dir(files.join("9"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    tiles.sort();
+    assert_eq!(
+        tiles,
+        ["0_0.png", "0_1.png", "1_0.png", "1_1.png", "2_0.png", "2_1.png"]
+    );
+    assert_eq!(png_size(&files.join("9/0_0.png")), (129, 129));
+    assert_eq!(png_size(&files.join("9/1_0.png")), (130, 129));
+    assert_eq!(png_size(&files.join("9/2_1.png")), (45, 73));
+    // Half the size fits into 2 x 1 tiles, the smallest level is a single pixel
+    assert_eq!(fs::read_dir(files.join("8")).unwrap().count(), 2);
+    assert_eq!(png_size(&files.join("8/1_0.png")), (23, 100));
+    assert_eq!(png_size(&files.join("0/0_0.png")), (1, 1));
+
+    let empty_tiles = DeepZoom {
+        tile_size: 0,
+        overlap: 1,
+    };
+    assert!(empty_tiles
+        .write(&dir.join("empty"), &settings, &viewport, |_| {})
+        .is_err());
+    assert!(!dir.join("empty.dzi").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,54 @@
+use fractal_rs::export;
+use fractal_rs::fractal::{Fractal, RenderSettings};
+use fractal_rs::mandelbrot::Position;
+use fractal_rs::palette::Palette;
+
+mod common;
+use common::universe;
+
+#[test]
+pub fn test_png_restores_view() {
+    let original = universe(60, 40, Position::new(-17, 5, 1.3));
+    original.set_settings(RenderSettings::new(Fractal::Mandelbrot, 80, Palette::Fire));
+    original.update();
+    let png = original.encode_png().unwrap();
+
+    let metadata = export::read_view_png(&png).unwrap();
+    assert_eq!(metadata, original.view_metadata());
+
+    let restored = universe(60, 40, Position::new(0, 0, 1.0));
+    restored.restore_view(&metadata);
+    let position = restored.get_position();
+    assert_eq!((position.get_x(), position.get_y()), (-17, 5));
+    assert_eq!(position.get_zoom_factor(), 1.3);
+    assert_eq!(restored.get_settings(), original.get_settings());
+    assert_eq!(restored.rgba(), original.rgba());
+}
+
+#[test]
+pub fn test_missing_metadata() {
+    let png = export::encode_png(1, 1, &[0, 0, 0, 255]).unwrap();
+    let err = export::read_view_png(&png).unwrap_err();
+    assert_eq!(err.to_string(), "missing 'Fractal' metadata");
+}
+
+#[test]
+pub fn test_invalid_zoom_metadata() {
+    let text = |zoom: &str| -> Vec<(String, String)> {
+        [
+            ("Fractal", "mandelbrot"),
+            ("Centre", "-0.5,0"),
+            ("Zoom", zoom),
+            ("Iterations", "100"),
+            ("Palette", "fire"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+    };
+    assert!(export::ViewMetadata::from_text(&text("0.5"), 4, 3).is_ok());
+    for zoom in ["0", "-1", "NaN", "inf"] {
+        let err = export::ViewMetadata::from_text(&text(zoom), 4, 3).unwrap_err();
+        assert_eq!(err.to_string(), format!("invalid 'Zoom' metadata: '{}'", zoom));
+    }
+}
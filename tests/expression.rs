@@ -0,0 +1,125 @@
+use fractal_rs::expression::{BinaryOp, Dialect, Expr, Parser, Scope, Token, MAX_DEPTH};
+use num::complex::Complex;
+
+struct Names;
+
+impl Scope for Names {
+    fn is_variable(&self, name: &str) -> bool {
+        ["x", "y"].contains(&name)
+    }
+
+    fn arity(&self, name: &str) -> Option<usize> {
+        match name {
+            "f" => Some(1),
+            "g" => Some(2),
+            _ => None,
+        }
+    }
+}
+
+fn parse(source: &str, dialect: Dialect) -> Result<Expr, String> {
+    let mut parser = Parser::new(source, 1, dialect).map_err(|err| err.to_string())?;
+    let expr = parser.expression(&Names).map_err(|err| err.to_string())?;
+    match parser.peek() {
+        Token::Separator | Token::End => Ok(expr),
+        token => Err(format!("unexpected {}", token)),
+    }
+}
+
+fn number(value: f64) -> Box<Expr> {
+    Box::new(Expr::Number(Complex::new(value, 0.0)))
+}
+
+fn variable(name: &str) -> Box<Expr> {
+    Box::new(Expr::Variable(name.to_string()))
+}
+
+#[test]
+pub fn test_precedence() {
+    // Powers bind tighter than negation and to the right
+    assert_eq!(
+        parse("-x^2^y", Dialect::Standard),
+        Ok(Expr::Negate(Box::new(Expr::Binary(
+            BinaryOp::Pow,
+            variable("x"),
+            Box::new(Expr::Binary(BinaryOp::Pow, number(2.0), variable("y")))
+        ))))
+    );
+    assert_eq!(
+        parse("x + 1.5e-3 * y < 2 || !x", Dialect::Standard),
+        Ok(Expr::Binary(
+            BinaryOp::Or,
+            Box::new(Expr::Binary(
+                BinaryOp::Less,
+                Box::new(Expr::Binary(
+                    BinaryOp::Add,
+                    variable("x"),
+                    Box::new(Expr::Binary(BinaryOp::Mul, number(1.5e-3), variable("y")))
+                )),
+                number(2.0)
+            )),
+            Box::new(Expr::Not(variable("x")))
+        ))
+    );
+    assert_eq!(
+        parse("g(f(x), 2)", Dialect::Standard),
+        Ok(Expr::Call(
+            "g".to_string(),
+            vec![
+                Expr::Call("f".to_string(), vec![*variable("x")]),
+                *number(2.0)
+            ]
+        ))
+    );
+}
+
+#[test]
+pub fn test_fractint_dialect() {
+    assert_eq!(
+        parse("|x - y| <= (-1, 0.5)", Dialect::Fractint),
+        Ok(Expr::Binary(
+            BinaryOp::LessEqual,
+            Box::new(Expr::Modulus(Box::new(Expr::Binary(
+                BinaryOp::Sub,
+                variable("x"),
+                variable("y")
+            )))),
+            Box::new(Expr::Number(Complex::new(-1.0, 0.5)))
+        ))
+    );
+    assert!(parse("|x|", Dialect::Standard).is_err());
+    assert!(parse("(1, 2)", Dialect::Standard).is_err());
+}
+
+#[test]
+pub fn test_expression_errors() {
+    let error = |source: &str| parse(source, Dialect::Standard).unwrap_err();
+    assert_eq!(error("x + z"), "line 1, column 5: unknown variable 'z'");
+    assert_eq!(error("h(x)"), "line 1, column 1: unknown function 'h'");
+    assert_eq!(
+        error("f(x, y)"),
+        "line 1, column 4: 'f' takes 1 argument(s)"
+    );
+    assert_eq!(
+        error("x *\ny"),
+        "line 1, column 4: expected a number, variable or function, found end of statement"
+    );
+    assert_eq!(error("1.2.3"), "line 1, column 1: invalid number '1.2.3'");
+}
+
+#[test]
+pub fn test_nesting_depth() {
+    // The innermost `x` is a level of its own
+    let nested = |open: &str, close: &str, depth: usize| {
+        format!("{}x{}", open.repeat(depth), close.repeat(depth))
+    };
+    for (open, close) in [("(", ")"), ("f(", ")"), ("-", ""), ("!", ""), ("x^", "")] {
+        assert!(parse(&nested(open, close, MAX_DEPTH - 1), Dialect::Standard).is_ok());
+        let error = parse(&nested(open, close, MAX_DEPTH), Dialect::Standard).unwrap_err();
+        assert!(error.ends_with("expression nested deeper than 64 levels"));
+        // Far deeper input fails the same way instead of overflowing the stack
+        assert!(parse(&nested(open, close, 2000), Dialect::Standard).is_err());
+    }
+    let error = parse(&nested("|(", ")|", MAX_DEPTH), Dialect::Fractint).unwrap_err();
+    assert!(error.ends_with("expression nested deeper than 64 levels"));
+}
@@ -0,0 +1,239 @@
+use fractal_rs::density::{DensityRenderer, Random, Screen};
+use fractal_rs::flame::{Flame, FlameError, FlameRenderer, Transform, Variation};
+use fractal_rs::ifs::AffineMap;
+use fractal_rs::mandelbrot::Position;
+use fractal_rs::palette::Palette;
+use fractal_rs::Universe;
+use rayon::ThreadPoolBuilder;
+
+const SIERPINSKI_FLAME: &str = r#"<?xml version="1.0"?>
+<flames>
+<!-- three linear maps and a bubble as final transform -->
+<flame name="first" size="64 48" center="0.5 0.25" scale="32" brightness="8" gamma="2.5"
+       vibrancy="0.5" supersample="2">
+   <xform weight="0.5" color="0" symmetry="0.5" coefs="0.5 0 0 0.5 0 0" linear="1"/>
+   <xform weight="0.25" color="1" color_speed="1" coefs="0.5 0 0 0.5 0.5 0"
+          linear="0.75" swirl="0.25" julian_power="2" opacity="1"/>
+   <xform weight="0.25" color="0.5" coefs="0.5 0 0 0.5 0.25 0.5" post="1 0 0 -1 0 0"/>
+   <finalxform color="0" color_speed="0" bubble="1"/>
+   <color index="0" rgb="255 0 0"/>
+   <color index="255" rgb="0 0 255"/>
+</flame>
+<flame name="second">
+   <xform weight="1" coefs="1 0 0 1 0 0" linear="1"/>
+   <palette count="2" format="RGB">
+      FF0000 00FF00
+   </palette>
+</flame>
+</flames>
+"#;
+
+#[test]
+pub fn test_variations() {
+    let mut random = Random(0);
+    let mut apply = |variation: Variation, point: (f64, f64)| variation.apply(point, &mut random);
+    assert_eq!(apply(Variation::Linear, (0.5, -2.0)), (0.5, -2.0));
+    let (x, y) = apply(Variation::Spherical, (2.0, 0.0));
+    assert!((x - 0.5).abs() < 1e-9 && y == 0.0);
+    let (x, y) = apply(Variation::Swirl, (1.0, 0.0));
+    assert!((x - 1f64.sin()).abs() < 1e-12 && (y - 1f64.cos()).abs() < 1e-12);
+    assert_eq!(apply(Variation::Bent, (-1.0, -1.0)), (-2.0, -0.5));
+    assert_eq!(apply(Variation::Cylinder, (0.0, 3.0)), (0.0, 3.0));
+    // Square roots of the distance, halving the angle from the y axis
+    let (x, y) = apply(Variation::Julia, (0.0, 4.0));
+    assert!((x.abs() - 2.0).abs() < 1e-12 && y.abs() < 1e-12);
+    // The angle is measured from the y axis
+    let (x, y) = apply(Variation::Polar, (1.0, 0.0));
+    assert!((x - 0.5).abs() < 1e-12 && y.abs() < 1e-12);
+    // Spherical keeps the origin finite
+    let (x, y) = apply(Variation::Spherical, (0.0, 0.0));
+    assert!(x.is_finite() && y.is_finite());
+
+    for variation in Variation::ALL.iter() {
+        assert_eq!(Variation::from_name(variation.name()), Some(*variation));
+    }
+    assert_eq!(Variation::from_name("blob"), None);
+}
+
+#[test]
+pub fn test_parse_flame() {
+    let flame = Flame::parse(SIERPINSKI_FLAME, "").unwrap();
+    assert_eq!(flame.name, "first");
+    assert_eq!(flame.transforms.len(), 3);
+    assert_eq!(flame.brightness, 2.0);
+    assert_eq!(flame.gamma, 2.5);
+    assert_eq!(flame.vibrancy, 0.5);
+    assert_eq!(flame.supersample, 2);
+    let camera = flame.camera.unwrap();
+    assert_eq!(camera.centre, (0.5, 0.25));
+    assert_eq!((camera.width, camera.height), (2.0, 1.5));
+
+    let first = &flame.transforms[0];
+    assert_eq!(first.affine.weight, 0.5);
+    assert_eq!(first.variations, vec![(Variation::Linear, 1.0)]);
+    // Symmetry 0.5 moves the colour a quarter of the way
+    assert_eq!(first.color_speed, 0.25);
+    let second = &flame.transforms[1];
+    assert_eq!(second.affine.apply((1.0, 1.0)), (1.0, 0.5));
+    assert_eq!(
+        second.variations,
+        vec![(Variation::Linear, 0.75), (Variation::Swirl, 0.25)]
+    );
+    assert_eq!(second.color_speed, 1.0);
+    // Transforms without variations are linear, `post` is applied after them
+    let third = &flame.transforms[2];
+    assert_eq!(third.variations, vec![(Variation::Linear, 1.0)]);
+    let mut random = Random(0);
+    assert_eq!(third.apply((0.0, 0.0), &mut random), (0.25, -0.5));
+    let last = flame.final_transform.unwrap();
+    assert_eq!(last.variations, vec![(Variation::Bubble, 1.0)]);
+
+    assert_eq!(flame.palette.len(), 256);
+    assert_eq!(flame.palette[0], (255, 0, 0));
+    assert_eq!(flame.palette[255], (0, 0, 255));
+
+    let second = Flame::parse(SIERPINSKI_FLAME, "second").unwrap();
+    assert_eq!(second.palette, vec![(255, 0, 0), (0, 255, 0)]);
+    assert_eq!(second.camera, None);
+    assert_eq!(second.brightness, 1.0);
+}
+
+#[test]
+pub fn test_flame_errors() {
+    assert_eq!(
+        Flame::parse(SIERPINSKI_FLAME, "third"),
+        Err(FlameError::MissingFlame("third".to_string()))
+    );
+    assert_eq!(
+        Flame::parse("<flame><xform blob=\"1\"/></flame>", ""),
+        Err(FlameError::UnsupportedVariation("blob".to_string()))
+    );
+    assert_eq!(
+        Flame::parse("<flame></flame>", ""),
+        Err(FlameError::NoTransforms)
+    );
+    assert_eq!(
+        Flame::parse("<flame gamma=\"x\"><xform/></flame>", ""),
+        Err(FlameError::Invalid("gamma".to_string(), "x".to_string()))
+    );
+    assert_eq!(
+        Flame::parse("<flame estimator_radius=\"1e9\"><xform/></flame>", ""),
+        Err(FlameError::Invalid(
+            "estimator_radius".to_string(),
+            "1000000000".to_string()
+        ))
+    );
+    assert_eq!(
+        Flame::parse("<flame estimator_minimum=\"NaN\"><xform/></flame>", ""),
+        Err(FlameError::Invalid(
+            "estimator_minimum".to_string(),
+            "NaN".to_string()
+        ))
+    );
+    assert_eq!(
+        Flame::parse("<flame estimator_curve=\"-1\"><xform/></flame>", ""),
+        Err(FlameError::Invalid(
+            "estimator_curve".to_string(),
+            "-1".to_string()
+        ))
+    );
+    match Flame::parse("<flames>\n<flame name=first>", "") {
+        Err(FlameError::Xml { line: 2, .. }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    match Flame::parse("<flame><palette>12345</palette><xform/></flame>", "") {
+        Err(FlameError::InvalidPalette(_)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    // Colours must be within a shorter palette given before them
+    let short = "<flame><palette>FF0000 00FF00</palette><color index=\"1\" rgb=\"0 0 255\"/>\
+                 <color index=\"255\" rgb=\"0 0 255\"/><xform/></flame>";
+    match Flame::parse(short, "") {
+        Err(FlameError::InvalidPalette(message)) => assert_eq!(message, "invalid colour 255"),
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+fn sierpinski(palette: &Palette) -> Flame {
+    let transforms = vec![
+        Transform::new(
+            AffineMap::new(0.5, 0.0, 0.0, 0.5, 0.0, 0.0),
+            vec![(Variation::Linear, 1.0)],
+            0.0,
+        ),
+        Transform::new(
+            AffineMap::new(0.5, 0.0, 0.0, 0.5, 0.5, 0.0),
+            vec![(Variation::Linear, 1.0)],
+            0.5,
+        ),
+        Transform::new(
+            AffineMap::new(0.5, 0.0, 0.0, 0.5, 0.25, 0.5),
+            vec![(Variation::Linear, 1.0)],
+            0.9,
+        ),
+    ];
+    Flame::new(transforms, palette)
+}
+
+#[test]
+pub fn test_flame_rendering() {
+    let mut flame = sierpinski(&Palette::Fire);
+    flame.supersample = 2;
+    let position = flame.fitting_position(32, 32).unwrap();
+    let screen = Screen::new(position, 32, 32);
+    let mut one_pass = FlameRenderer::new(screen.clone(), flame.clone()).unwrap();
+    one_pass.accumulate(60_000);
+    assert_eq!(one_pass.samples(), 60_000);
+    assert_eq!(one_pass.histogram().len(), 64 * 64);
+    let total: f32 = one_pass.histogram().iter().map(|cell| cell[3]).sum();
+    assert!(total > 59_000.0);
+
+    let thread_pool = ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+    let mut two_passes = FlameRenderer::new(screen.clone(), flame.clone()).unwrap();
+    thread_pool.install(|| {
+        two_passes.accumulate(16384);
+        two_passes.accumulate(60_000 - 16384);
+    });
+    let difference: f32 = one_pass
+        .histogram()
+        .iter()
+        .zip(two_passes.histogram())
+        .map(|(a, b)| (a[3] - b[3]).abs())
+        .sum();
+    assert!(difference / total < 0.1);
+
+    let (r, g, b) = one_pass.to_cells();
+    assert_eq!(r.len(), 32 * 32);
+    assert!(r.iter().any(|value| *value > 200));
+    // Colours come from the transforms, the fire palette has no blue below its last third
+    assert!(r.iter().zip(&b).all(|(r, b)| r >= b));
+    // The triangle points downwards, the bottom corners of the view stay black
+    let corner = 31 * 32;
+    assert_eq!((r[corner], g[corner], b[corner]), (0, 0, 0));
+
+    // Without vibrancy gamma applies to every channel, which desaturates the colours
+    flame.vibrancy = 0.0;
+    let mut desaturated = FlameRenderer::new(screen, flame).unwrap();
+    desaturated.accumulate(60_000);
+    let (_, g_desaturated, _) = desaturated.to_cells();
+    let green = |cells: &[u8]| -> u64 { cells.iter().map(|value| *value as u64).sum() };
+    assert!(green(&g_desaturated) > green(&g));
+}
+
+#[test]
+pub fn test_universe_flame() {
+    let thread_pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let universe = Universe::with_thread_pool(32, 32, Position::new(0, 0, 0.3), thread_pool);
+    let flame = Flame::parse(SIERPINSKI_FLAME, "first").unwrap();
+    universe.start_fractal_flame(flame).unwrap();
+    // The camera shows 2 x 1.5 units around (0.5, 0.25), with y along the rows
+    let zoom_factor = Position::zoom_factor_fitting(1.5, 2.0, 32, 32);
+    assert_eq!(universe.get_position().get_zoom_factor(), zoom_factor);
+    universe.draw_density(20_000).unwrap();
+    assert_eq!(universe.density().unwrap().samples(), 20_000);
+    assert!(universe.rgba().chunks(4).any(|p| p[0] > 0));
+
+    let mut invalid = sierpinski(&Palette::Classic);
+    invalid.gamma = 0.0;
+    assert!(universe.start_fractal_flame(invalid).is_err());
+}
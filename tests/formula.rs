@@ -0,0 +1,192 @@
+use fractal_rs::dual::Dual;
+use fractal_rs::formula::Formula;
+use fractal_rs::fractal::Fractal;
+use fractal_rs::mandelbrot;
+use num::complex::Complex;
+use std::sync::Arc;
+
+#[test]
+pub fn test_mandelbrot_formula() {
+    let points = [
+        Complex::new(-0.75, 0.1),
+        Complex::new(0.3, 0.5),
+        Complex::new(-2.1, 0.0),
+        Complex::new(-0.1, 0.2),
+    ];
+    for source in &[
+        "z^2 + c",
+        "z = z*z + pixel",
+        "# Mandelbrot\nw = sqr(z)\nw + c",
+    ] {
+        let formula = Formula::compile(source).unwrap();
+        for point in points.iter() {
+            assert_eq!(
+                formula.escape(*point, 100),
+                mandelbrot::mandelbrot_escape(*point, 100),
+                "{} at {}",
+                source,
+                point
+            );
+        }
+    }
+}
+
+#[test]
+pub fn test_conditionals() {
+    // Burning ship like fold of the imaginary part on odd iterations only
+    let formula = Formula::compile("w = if(n >= 1 && im(z) < 0, conj(z), z); w^2 + c").unwrap();
+    let mut variables = vec![Dual::default(); 5];
+    variables[0] = Dual::constant(Complex::new(1.0, -1.0));
+    variables[1] = Dual::variable(Complex::new(0.5, 0.0));
+    variables[3] = Dual::constant(Complex::new(1.0, 0.0));
+    formula.run(&mut variables, &mut Vec::new());
+    assert_eq!(variables[0].value, Complex::new(0.5, 2.0));
+    variables[0] = Dual::constant(Complex::new(1.0, -1.0));
+    variables[3] = Dual::constant(Complex::new(0.0, 0.0));
+    formula.run(&mut variables, &mut Vec::new());
+    assert_eq!(variables[0].value, Complex::new(0.5, -2.0));
+}
+
+#[test]
+pub fn test_comparisons_of_real_parts() {
+    // Values with the same real part are equal, whatever their imaginary parts
+    let compare = |source: &str, z: Complex<f64>, c: Complex<f64>| {
+        let formula = Formula::compile(source).unwrap();
+        let mut variables = vec![Dual::default(); 4];
+        variables[0] = Dual::constant(z);
+        variables[1] = Dual::constant(c);
+        formula.run(&mut variables, &mut Vec::new());
+        variables[0].value
+    };
+    let (a, b) = (Complex::new(1.0, 1.0), Complex::new(1.0, -1.0));
+    assert_eq!(compare("z == c", a, b), Complex::new(1.0, 0.0));
+    assert_eq!(compare("z != c", a, b), Complex::new(0.0, 0.0));
+    assert_eq!(compare("z <= c && z >= c", a, b), Complex::new(1.0, 0.0));
+    assert_eq!(
+        compare("z == c", a, Complex::new(2.0, 1.0)),
+        Complex::new(0.0, 0.0)
+    );
+}
+
+#[test]
+pub fn test_formula_errors() {
+    let message = |source: &str| Formula::compile(source).unwrap_err().to_string();
+    assert_eq!(message("z^2 + q"), "line 1, column 7: unknown variable 'q'");
+    assert_eq!(
+        message("w = 2 * z\nw + foo(c)"),
+        "line 2, column 5: unknown function 'foo'"
+    );
+    // New lines end statements, also after an operator
+    assert_eq!(
+        message("z^2 +\n  c"),
+        "line 1, column 6: expected a number, variable or function, found end of statement"
+    );
+    assert_eq!(
+        message("z^2 *"),
+        "line 1, column 6: expected a number, variable or function, found end of statement"
+    );
+    assert_eq!(
+        message("pow(z)"),
+        "line 1, column 6: 'pow' takes 2 arguments, expected ',' but found ')'"
+    );
+    assert_eq!(
+        message("z^2 + (c"),
+        "line 1, column 9: expected ')' to close the parenthesis, found end of statement"
+    );
+    // Deep nesting is an error instead of a stack overflow
+    let nested = format!("z^2 + {}c{}", "(".repeat(2000), ")".repeat(2000));
+    assert_eq!(
+        message(&nested),
+        "line 1, column 71: expression nested deeper than 64 levels"
+    );
+    assert_eq!(
+        message("c = 1; z"),
+        "line 1, column 1: 'c' is predefined and cannot be assigned"
+    );
+    assert_eq!(
+        message("z $ c"),
+        "line 1, column 3: unexpected character '$'"
+    );
+    assert_eq!(
+        message("# nothing"),
+        "line 1, column 1: the formula is empty"
+    );
+}
+
+#[test]
+pub fn test_formula_spec() {
+    let fractal = Fractal::Formula(Arc::new(Formula::compile("z^3 + c").unwrap()));
+    assert_eq!(fractal.spec(), "formula:z^3 + c");
+    assert_eq!(Fractal::from_spec(&fractal.spec()), Some(fractal));
+    assert_eq!(Fractal::from_spec("mandelbrot"), Some(Fractal::Mandelbrot));
+    assert_eq!(Fractal::from_spec("formula:z^"), None);
+}
+
+#[test]
+pub fn test_derivatives() {
+    // f(c) = c^3 sin(c) / exp(c) with the derivative computed by hand
+    let c = Complex::new(0.3, -0.7);
+    let x = Dual::variable(c);
+    let f = x.powi(3) * x.sin() / x.exp();
+    let expected = (c * c * 3.0 * c.sin() + c.powi(3) * c.cos() - c.powi(3) * c.sin()) / c.exp();
+    assert!((f.derivative - expected).norm() < 1e-12);
+
+    // Non-integer powers agree with repeated multiplication
+    let powc = x.powc(Dual::constant(Complex::new(2.5, 0.0)));
+    let expected = c.powf(1.5) * 2.5;
+    assert!((powc.derivative - expected).norm() < 1e-12);
+    assert!(Dual::default()
+        .powc(Dual::constant(Complex::new(2.5, 0.0)))
+        .derivative
+        .is_finite());
+}
+
+#[test]
+pub fn test_formula_distance_estimate() {
+    // z^3 + c has the derivative 3 z^2 z' + 1 like the hand written kernel below
+    let formula = Formula::compile("z^3 + c").unwrap();
+    let point = Complex::new(0.6, 0.6);
+    let (mut z, mut dz, mut iter) = (Complex::new(0.0, 0.0), Complex::new(0.0, 0.0), 0);
+    while z.norm_sqr() < 4.0 && iter < 100 {
+        dz = z * z * dz * 3.0 + 1.0;
+        z = z * z * z + point;
+        iter += 1;
+    }
+    let expected = mandelbrot::escape_from(iter, z.norm_sqr(), dz.norm_sqr());
+    let escape = formula.escape(point, 100).unwrap();
+    assert_eq!(escape.smoothed_iterations, expected.smoothed_iterations);
+    assert!((escape.distance - expected.distance).abs() < 1e-12);
+    assert!(escape.distance.is_finite() && escape.distance > 0.0);
+}
+
+#[test]
+pub fn test_burning_ship_distance_estimate() {
+    // Folding both parts starts at z = 0, where abs has no derivative of its own
+    let formula = Formula::compile("w = abs(re(z)) + i*abs(im(z)); w^2 + c").unwrap();
+    let point = Complex::new(-1.6, -0.05);
+    let (mut z, mut dz, mut iter) = (Complex::new(0.0, 0.0), Complex::new(0.0, 0.0), 0);
+    while z.norm_sqr() < 4.0 && iter < 100 {
+        let fold = |x: f64, dx: f64| match x {
+            x if x < 0.0 => (-x, -dx),
+            x if x > 0.0 => (x, dx),
+            _ => (0.0, dx.abs()),
+        };
+        let (re, dre) = fold(z.re, dz.re);
+        let (im, dim) = fold(z.im, dz.im);
+        let (w, dw) = (Complex::new(re, im), Complex::new(dre, dim));
+        dz = w * dw * 2.0 + 1.0;
+        z = w * w + point;
+        iter += 1;
+    }
+    let expected = mandelbrot::escape_from(iter, z.norm_sqr(), dz.norm_sqr());
+    let escape = formula.escape(point, 100).unwrap();
+    assert_eq!(escape.smoothed_iterations, expected.smoothed_iterations);
+    assert!((escape.distance - expected.distance).abs() < 1e-12);
+    assert!(escape.distance.is_finite() && escape.distance > 0.0);
+
+    let zero = Dual::default();
+    assert_eq!(zero.abs(), Dual::default());
+    assert_eq!(zero.arg(), Dual::default());
+    let moving = Dual::new(Complex::new(0.0, 0.0), Complex::new(3.0, -4.0));
+    assert_eq!(moving.abs().derivative, Complex::new(5.0, 0.0));
+}
@@ -0,0 +1,128 @@
+use fractal_rs::fractal::Fractal;
+use fractal_rs::fractint::{self, Expr, FormulaFile, FractintError, Statement};
+use fractal_rs::palette::Palette;
+use num::complex::Complex;
+
+fn formulas() -> FormulaFile {
+    FormulaFile::parse(include_str!("data/sample.frm")).unwrap()
+}
+
+#[test]
+pub fn test_parse_par_entries() {
+    let entries = fractint::parse_par(include_str!("data/locations.par")).unwrap();
+    let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "Seahorse",
+            "Mag_Example",
+            "Julia_Example",
+            "Rotated",
+            "Cubic"
+        ]
+    );
+
+    let seahorse = entries[0].location(&formulas()).unwrap();
+    assert!((seahorse.centre - Complex::new(-0.745, 0.105)).norm() < 1e-12);
+    assert!((seahorse.extent - 0.03).abs() < 1e-12);
+    assert_eq!(seahorse.settings.fractal, Fractal::Mandelbrot);
+    assert_eq!(seahorse.settings.max_iterations, 500);
+
+    let magnified = entries[1].location(&formulas()).unwrap();
+    assert_eq!(magnified.centre, Complex::new(-0.7453, 0.1127));
+    assert_eq!(magnified.extent, 2.0 / 150.0);
+    assert_eq!(magnified.settings.max_iterations, 1000);
+    assert_eq!(magnified.settings.palette, Palette::default());
+}
+
+#[test]
+pub fn test_unsupported_par_entries() {
+    let entries = fractint::parse_par(include_str!("data/locations.par")).unwrap();
+    let unsupported = |idx: usize| match entries[idx].location(&formulas()) {
+        Err(FractintError::Unsupported(what)) => what,
+        other => panic!("expected an unsupported error, got {:?}", other),
+    };
+    assert_eq!(unsupported(2), "type=julia");
+    assert_eq!(unsupported(3), "center-mag=-0.5/0/1/1/30/0");
+    assert!(unsupported(4).starts_with("formula 'Cubic'"));
+}
+
+#[test]
+pub fn test_frm_formulas() {
+    let formulas = formulas();
+    assert_eq!(
+        formulas.names(),
+        ["MandelClone", "MandelStart", "Cubic", "Conditional"]
+    );
+    assert_eq!(
+        formulas.get("mandelclone").unwrap().fractal(),
+        Ok(Fractal::Mandelbrot)
+    );
+    assert_eq!(
+        formulas.get("MandelStart").unwrap().fractal(),
+        Ok(Fractal::Mandelbrot)
+    );
+    assert!(matches!(
+        formulas.get("Conditional"),
+        Err(FractintError::Unsupported(_))
+    ));
+    assert_eq!(
+        formulas.get("Missing"),
+        Err(FractintError::UnknownFormula("Missing".to_string()))
+    );
+
+    let broken = FormulaFile::parse("Broken {\n  z = 0:\n  z = (z + \n  |z| <= 4\n}").unwrap();
+    assert!(matches!(
+        broken.get("Broken"),
+        Err(FractintError::Syntax { line: 3, .. })
+    ));
+}
+
+#[test]
+pub fn test_decode_colors() {
+    let colors = fractint::decode_colors("000<2>0z0zzz").unwrap();
+    assert_eq!(
+        colors,
+        [(0, 85, 0), (0, 170, 0), (0, 255, 0), (255, 255, 255)]
+    );
+    assert!(fractint::decode_colors("@default.map").is_err());
+
+    // Tables hold at most 256 colours, the inside included
+    assert_eq!(fractint::decode_colors("000<254>zzz").unwrap().len(), 255);
+    assert!(fractint::decode_colors("000<255>zzz").is_err());
+    assert!(fractint::decode_colors("000<4294967295>zzz").is_err());
+    assert!(fractint::decode_colors("000<18446744073709551615>FFF").is_err());
+    assert!(fractint::decode_colors("000<99999999999999999999>zzz").is_err());
+    assert!(fractint::decode_colors(&"zzz".repeat(257)).is_err());
+    assert_eq!(
+        fractint::decode_colors(&"zzz".repeat(256)).unwrap().len(),
+        255
+    );
+}
+
+#[test]
+pub fn test_negative_complex_constants() {
+    let formulas = FormulaFile::parse(
+        "Constant {\n  z = (-0.5, -0.3), c = (0.25, -1e-3):\n  z = sqr(z) + pixel\n  |z| <= 4\n}\n\
+         MandelZero {\n  z = (-0, 0):\n  z = sqr(z) + pixel\n  |z| <= 4\n}",
+    )
+    .unwrap();
+    let constant = formulas.get("Constant").unwrap();
+    assert_eq!(
+        constant.init,
+        [
+            Statement::Assign("z".to_string(), Expr::Number(Complex::new(-0.5, -0.3))),
+            Statement::Assign("c".to_string(), Expr::Number(Complex::new(0.25, -1e-3))),
+        ]
+    );
+    assert_eq!(
+        formulas.get("MandelZero").unwrap().fractal(),
+        Ok(Fractal::Mandelbrot)
+    );
+
+    let variable = FormulaFile::parse("Variable {\n  z = (-pixel, 0):\n  |z| <= 4\n}").unwrap();
+    assert!(matches!(
+        variable.get("Variable"),
+        Err(FractintError::Syntax { line: 2, .. })
+    ));
+}
@@ -0,0 +1,83 @@
+use fractal_rs::mandelbrot::Position;
+use fractal_rs::Universe;
+
+mod common;
+
+fn universe() -> Universe {
+    common::universe(40, 30, Position::new(-10, 0, 1.0))
+}
+
+#[test]
+pub fn test_undo_redo() {
+    let universe = universe();
+    let start = universe.rgba();
+    universe.zoom_in();
+    universe.update();
+    let zoomed = universe.rgba();
+    universe.move_horizontal(5);
+    assert_eq!(universe.history_length(), 3);
+
+    assert!(universe.undo());
+    assert_eq!(universe.get_position(), Position::new(-10, 0, 1.1));
+    assert_eq!(universe.rgba(), zoomed);
+    assert!(universe.undo());
+    assert_eq!(universe.rgba(), start);
+    assert!(!universe.undo());
+
+    assert!(universe.redo());
+    assert_eq!(universe.rgba(), zoomed);
+
+    // Navigating after undo drops the views that could have been redone
+    universe.zoom_out();
+    assert_eq!(universe.history_length(), 3);
+    assert!(!universe.redo());
+
+    assert!(universe.jump_to_history(0));
+    assert_eq!(universe.history_index(), 0);
+    assert_eq!(universe.rgba(), start);
+    assert!(!universe.jump_to_history(3));
+}
+
+#[test]
+pub fn test_history_is_bounded() {
+    let universe = universe();
+    universe.set_history_capacity(4, 1);
+    for _ in 0..10 {
+        universe.move_horizontal(1);
+    }
+    assert_eq!(universe.history_length(), 4);
+    assert_eq!(universe.history_index(), 3);
+
+    // Views without a kept frame are rendered again
+    let latest = universe.rgba();
+    universe.undo();
+    universe.undo();
+    assert!(universe.redo());
+    assert!(universe.redo());
+    assert_eq!(universe.rgba(), latest);
+}
+
+#[test]
+pub fn test_resize_is_not_recorded() {
+    let mut universe = universe();
+    universe.zoom_in();
+    for size in [(50, 40), (60, 45), (80, 60)] {
+        universe.resize(size.0, size.1);
+    }
+    assert_eq!(universe.history_length(), 2);
+    assert_eq!(universe.history_index(), 1);
+    // The current view is that of the resized screen
+    assert_eq!(
+        universe.history_state(1),
+        Some(universe.view_state().encode())
+    );
+
+    // Undo shows the first view on the resized screen
+    assert!(universe.undo());
+    assert_eq!((universe.width(), universe.height()), (80, 60));
+    assert!(universe.redo());
+    assert_eq!(
+        universe.history_state(1),
+        Some(universe.view_state().encode())
+    );
+}
@@ -0,0 +1,132 @@
+use fractal_rs::density::{DensityRenderer, Screen};
+use fractal_rs::ifs::{self, AffineMap, ChaosGame, Ifs, IfsError};
+use fractal_rs::mandelbrot::Position;
+use fractal_rs::palette::Palette;
+use fractal_rs::Universe;
+use rayon::ThreadPoolBuilder;
+
+#[test]
+pub fn test_parse_maps() {
+    let text = "# Sierpinski triangle\n\
+                0.5 0 0 0.5 0 0\n\
+                \n\
+                0.5, 0, 0, 0.5, 0.5, 0, 2 # heavier\n";
+    let ifs = Ifs::parse(text).unwrap();
+    assert_eq!(ifs.maps().len(), 2);
+    // Without a weight a map is chosen by its area
+    assert_eq!(ifs.maps()[0].weight, 0.25);
+    assert_eq!(ifs.maps()[1].weight, 2.0);
+    assert_eq!(ifs.maps()[1].apply((1.0, 2.0)), (1.0, 1.0));
+    assert_eq!(ifs.choose(0.0), 0);
+    assert_eq!(ifs.choose(0.2), 1);
+    assert_eq!(ifs.choose(0.999), 1);
+
+    assert_eq!(Ifs::parse(&ifs.to_text()).unwrap(), ifs);
+    assert_eq!(Ifs::from_json(&ifs.to_json().unwrap()).unwrap(), ifs);
+
+    match Ifs::parse("0.5 0 0 0.5 0 0\n1 2 3") {
+        Err(IfsError::Syntax { line: 2, .. }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    match Ifs::parse("0.5 0 0 0.5 0 x") {
+        Err(IfsError::Syntax { line: 1, message }) => assert!(message.contains("'x'")),
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert!(matches!(Ifs::parse("# nothing"), Err(IfsError::Empty)));
+    assert!(matches!(
+        Ifs::parse("0.5 0 0 0.5 0 0 -1"),
+        Err(IfsError::InvalidWeight(0))
+    ));
+    assert!(matches!(
+        Ifs::new(vec![AffineMap::new(f64::NAN, 0.0, 0.0, 0.5, 0.0, 0.0)]),
+        Err(IfsError::InvalidCoefficient(0))
+    ));
+}
+
+#[test]
+pub fn test_json_maps() {
+    let json = r#"{"maps": [
+        {"a": 0.5, "b": 0, "c": 0, "d": 0.5, "e": 0, "f": 0},
+        {"a": 0.5, "b": 0, "c": 0, "d": 0.5, "e": 0.5, "f": 0, "weight": 0.75}
+    ]}"#;
+    let ifs = Ifs::from_json(json).unwrap();
+    assert_eq!(ifs.maps()[0].weight, 0.25);
+    assert_eq!(ifs.maps()[1].weight, 0.75);
+    assert!(matches!(Ifs::from_json("{}"), Err(IfsError::Json(_))));
+    assert!(matches!(
+        Ifs::from_json(r#"{"maps": []}"#),
+        Err(IfsError::Empty)
+    ));
+}
+
+#[test]
+pub fn test_presets() {
+    for name in ifs::PRESETS.iter() {
+        assert!(Ifs::preset(name).is_ok());
+    }
+    assert!(matches!(
+        Ifs::preset("mandelbrot"),
+        Err(IfsError::UnknownPreset(_))
+    ));
+
+    // The fern grows upwards from the origin to a height of about 10
+    let ((x_min, x_max), (y_min, y_max)) = Ifs::barnsley_fern().bounds();
+    assert!(x_min > -3.0 && x_max < 3.0);
+    assert!(y_min >= 0.0 && y_max > 9.0 && y_max < 10.1);
+
+    let ((x_min, x_max), (y_min, y_max)) = Ifs::sierpinski().bounds();
+    assert!(x_min >= 0.0 && x_max <= 1.0 && x_max > 0.99);
+    assert!(y_min >= 0.0 && y_max <= 0.87);
+}
+
+#[test]
+pub fn test_chaos_game_is_deterministic() {
+    let position = Ifs::sierpinski().fitting_position(32, 32);
+    let screen = Screen::new(position, 32, 32);
+    let mut one_pass = ChaosGame::new(screen.clone(), Ifs::sierpinski(), Palette::Grayscale);
+    one_pass.accumulate(50_000);
+    assert_eq!(one_pass.samples(), 50_000);
+    // Every point of the attractor is on the screen
+    let total: f32 = one_pass.counts().iter().sum();
+    assert_eq!(total, 50_000.0);
+
+    let thread_pool = ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+    let mut two_passes = ChaosGame::new(screen, Ifs::sierpinski(), Palette::Grayscale);
+    thread_pool.install(|| {
+        two_passes.accumulate(16384);
+        two_passes.accumulate(50_000 - 16384);
+    });
+    let difference: f32 = one_pass
+        .counts()
+        .iter()
+        .zip(two_passes.counts())
+        .map(|(a, b)| (a - b).abs())
+        .sum();
+    assert!(difference / total < 0.1);
+
+    // The upright triangle has its tip in the top row and its base in the bottom row
+    let row_total =
+        |row: usize| -> f32 { one_pass.counts()[row * 32..(row + 1) * 32].iter().sum() };
+    assert!(row_total(4) < row_total(27));
+    let (r, g, b) = one_pass.to_cells();
+    assert_eq!(r, g);
+    assert_eq!(g, b);
+    assert!(r.iter().any(|value| *value > 250));
+    // The hole in the middle of the triangle is never hit
+    assert_eq!(r[20 * 32 + 16], 0);
+}
+
+#[test]
+pub fn test_universe_chaos_game() {
+    let thread_pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let position = Position::new(0, 0, 0.3);
+    let universe = Universe::with_thread_pool(32, 32, position, thread_pool);
+    universe.start_chaos_game(Ifs::barnsley_fern());
+    assert_eq!(
+        universe.get_position(),
+        Ifs::barnsley_fern().fitting_position(32, 32)
+    );
+    universe.draw_density(20_000).unwrap();
+    assert_eq!(universe.density().unwrap().samples(), 20_000);
+    assert!(universe.rgba().chunks(4).any(|p| p[1] > 0));
+}
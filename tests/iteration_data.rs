@@ -0,0 +1,106 @@
+use fractal_rs::formula::Formula;
+use fractal_rs::fractal::Fractal;
+use fractal_rs::iteration_data::{FormatError, IterationData};
+use fractal_rs::palette::Palette;
+use fractal_rs::viewport::Viewport;
+use num::complex::Complex;
+use std::io;
+use std::sync::Arc;
+
+#[test]
+pub fn test_iteration_data_round_trip() {
+    let viewport = Viewport::with_extent(Complex::new(-0.75, 0.0), 3.5, 16, 12);
+    let data = IterationData::render(Fractal::Mandelbrot, viewport, 100, true);
+    assert!(data.iterations.iter().any(|value| value.is_infinite()));
+    assert!(data.iterations.iter().any(|value| value.is_finite()));
+
+    let mut bytes = Vec::new();
+    data.write_to(&mut bytes).unwrap();
+    let read = IterationData::read_from(&mut bytes.as_slice()).unwrap();
+    assert_eq!(read, data);
+    assert_eq!(read.to_rgba(&Palette::default(), true).len(), 16 * 12 * 4);
+}
+
+#[test]
+pub fn test_iteration_data_version_1() {
+    let viewport = Viewport::with_extent(Complex::new(-0.75, 0.0), 3.5, 4, 3);
+    let data = IterationData::render(Fractal::Mandelbrot, viewport, 20, false);
+    let mut bytes = Vec::new();
+    data.write_to(&mut bytes).unwrap();
+    // Version 1 stored the length of the fractal name after the header in one byte
+    let mut version_1 = b"FRACITER\x01\x00".to_vec();
+    version_1.extend_from_slice(&bytes[10..48]);
+    version_1.push(bytes[48]);
+    version_1.extend_from_slice(&bytes[50..]);
+    assert_eq!(bytes[49], 0);
+    let read = IterationData::read_from(&mut version_1.as_slice()).unwrap();
+    assert_eq!(read, data);
+
+    bytes[8] = 3;
+    let result = IterationData::read_from(&mut bytes.as_slice());
+    assert!(matches!(result, Err(FormatError::UnsupportedVersion(3))));
+}
+
+#[test]
+pub fn test_iteration_data_rejects_other_files() {
+    let result = IterationData::read_from(&mut &b"\x89PNG\r\n\x1a\n"[..]);
+    assert!(matches!(result, Err(FormatError::NotIterationData)));
+}
+
+fn file_with_size(width: u32, height: u32) -> Vec<u8> {
+    let viewport = Viewport::with_extent(Complex::new(-0.75, 0.0), 3.5, 4, 3);
+    let data = IterationData::render(Fractal::Mandelbrot, viewport, 20, false);
+    let mut bytes = Vec::new();
+    data.write_to(&mut bytes).unwrap();
+    // Width and height follow the magic bytes, version and flags
+    bytes[12..16].copy_from_slice(&width.to_le_bytes());
+    bytes[16..20].copy_from_slice(&height.to_le_bytes());
+    bytes
+}
+
+#[test]
+pub fn test_iteration_data_truncated() {
+    // The 12 values of a 4 x 3 file are too few for 4 x 4 pixels
+    let bytes = file_with_size(4, 4);
+    let result = IterationData::read_from(&mut bytes.as_slice());
+    assert!(
+        matches!(result, Err(FormatError::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof)
+    );
+
+    // Files ending within the header or the values
+    let bytes = file_with_size(4, 3);
+    for length in [10, 30, bytes.len() - 1] {
+        let result = IterationData::read_from(&mut &bytes[..length]);
+        assert!(matches!(result, Err(FormatError::Io(_))));
+    }
+
+    // A header promising almost a gigapixel fails as soon as the data ends
+    let bytes = file_with_size(30_000, 30_000);
+    let result = IterationData::read_from(&mut bytes.as_slice());
+    assert!(matches!(result, Err(FormatError::Io(_))));
+}
+
+#[test]
+pub fn test_iteration_data_too_large() {
+    for (width, height) in [(u32::MAX, u32::MAX), (1 << 16, 1 << 15), (u32::MAX, 2)] {
+        let bytes = file_with_size(width, height);
+        let result = IterationData::read_from(&mut bytes.as_slice());
+        assert!(matches!(
+            result,
+            Err(FormatError::TooLarge { width: w, height: h }) if (w, h) == (width, height)
+        ));
+    }
+}
+
+#[test]
+pub fn test_iteration_data_long_formula() {
+    // Specifications of formulas may be longer than 255 bytes
+    let source = format!("{}z^2 + c", "w = z + 0.125\n".repeat(20));
+    let fractal = Fractal::Formula(Arc::new(Formula::compile(&source).unwrap()));
+    assert!(fractal.spec().len() > 255);
+    let viewport = Viewport::with_extent(Complex::new(-0.75, 0.0), 3.5, 4, 3);
+    let data = IterationData::render(fractal, viewport, 20, false);
+    let mut bytes = Vec::new();
+    data.write_to(&mut bytes).unwrap();
+    assert_eq!(IterationData::read_from(&mut bytes.as_slice()).unwrap(), data);
+}
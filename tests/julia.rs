@@ -0,0 +1,118 @@
+use fractal_rs::fractal::{Fractal, RenderSettings};
+use fractal_rs::julia::{self, DEFAULT_PARAMETER};
+use fractal_rs::mandelbrot::{self, Position};
+use fractal_rs::palette::Palette;
+use fractal_rs::state::ViewState;
+use fractal_rs::Universe;
+use num::complex::Complex;
+use rayon::ThreadPoolBuilder;
+
+#[test]
+pub fn test_julia_escape() {
+    // The Julia set of 0 is the unit disc
+    let zero = Complex::new(0.0, 0.0);
+    assert!(mandelbrot::julia_escape(Complex::new(0.0, 0.99), zero, 200).is_none());
+    let escape = mandelbrot::julia_escape(Complex::new(1.01, 0.0), zero, 200).unwrap();
+    assert!(escape.smoothed_iterations > 5.0);
+    // Far outside the count stays positive
+    let escape = mandelbrot::julia_escape(Complex::new(1e6, 0.0), zero, 200).unwrap();
+    assert_eq!(escape.smoothed_iterations, 0.0);
+    assert_eq!(
+        mandelbrot::julia_iteration_quotient(Complex::new(0.5, 0.0), zero, 200),
+        1.0
+    );
+
+    // The Julia set of c contains the fixed points of z^2 + c
+    let c = Complex::new(-0.12, 0.75);
+    let fixed = (Complex::new(1.0, 0.0) - (Complex::new(1.0, 0.0) - c * 4.0).sqrt()) / 2.0;
+    assert!(Fractal::Julia(c).escape(fixed, 500).is_none());
+}
+
+#[test]
+pub fn test_julia_spec() {
+    let fractal = Fractal::Julia(Complex::new(-0.4, 0.6));
+    assert_eq!(fractal.spec(), "julia:-0.4,0.6");
+    assert_eq!(Fractal::from_spec(&fractal.spec()), Some(fractal));
+    assert_eq!(
+        Fractal::from_spec("julia"),
+        Some(Fractal::Julia(DEFAULT_PARAMETER))
+    );
+    assert_eq!(Fractal::from_spec("julia:3,0"), None);
+    assert_eq!(Fractal::from_spec("julia:0.1"), None);
+    assert_eq!(Fractal::from_spec("julia:x,0"), None);
+}
+
+#[test]
+pub fn test_julia_thumbnail() {
+    let settings = RenderSettings::new(Fractal::Mandelbrot, 20, Palette::Grayscale);
+    let rgba = julia::thumbnail(Complex::new(0.0, 0.0), &settings, 24, 16);
+    assert_eq!(rgba.len(), 24 * 16 * 4);
+    let pixel = |row: usize, col: usize| &rgba[(row * 24 + col) * 4..(row * 24 + col + 1) * 4];
+    // The unit disc is in the middle, the corners are outside
+    assert_eq!(pixel(8, 12), &[0, 0, 0, 255]);
+    assert_ne!(pixel(0, 0), &[0, 0, 0, 255]);
+    // A square of side 3.2 around the origin fits, pixels are centred on their coordinates
+    let position = julia::default_position(24, 16);
+    let top_left = position.pixel_to_complex(0.0, 0.0, 24, 16);
+    let bottom_right = position.pixel_to_complex(15.0, 23.0, 24, 16);
+    assert!((top_left.re + 1.6).abs() < 1e-9 && top_left.im < -1.6);
+    assert!((bottom_right.re - 1.4).abs() < 1e-9 && bottom_right.im > 1.6);
+}
+
+#[test]
+pub fn test_universe_julia() {
+    let thread_pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let position = Position::from_centre(Complex::new(-0.5, 0.0), 0.4, 24, 16);
+    let universe = Universe::with_thread_pool(24, 16, position, thread_pool);
+    let origin = universe.view_state();
+    assert!(!universe.leave_julia());
+    assert!(universe.julia_parameter().is_empty());
+
+    // The preview at a pixel shows the Julia set of the point at the pixel
+    let c = universe.get_position().pixel_to_complex(5.0, 7.0, 24, 16);
+    assert_eq!(
+        universe.julia_thumbnail_at(5.0, 7.0, 12, 8),
+        universe.julia_thumbnail(c, 12, 8)
+    );
+
+    universe.show_julia(5.0, 7.0).unwrap();
+    assert_eq!(universe.julia_parameter(), vec![c.re, c.im]);
+    assert_eq!(universe.get_position(), julia::default_position(24, 16));
+    assert_eq!(universe.julia_origin(), Some(origin.clone()));
+    let julia_view = universe.rgba();
+
+    // Entering another Julia set keeps the view to return to
+    universe.enter_julia(DEFAULT_PARAMETER);
+    assert_eq!(universe.julia_origin(), Some(origin.clone()));
+    let state = universe.view_state();
+    assert_eq!(ViewState::decode(&state.encode()).unwrap(), state);
+
+    assert!(universe.leave_julia());
+    assert_eq!(universe.view_state(), origin);
+    assert_eq!(universe.julia_origin(), None);
+    assert!(!universe.leave_julia());
+
+    // The Julia views are in the history
+    assert!(universe.undo());
+    assert_eq!(
+        universe.get_settings().fractal,
+        Fractal::Julia(DEFAULT_PARAMETER)
+    );
+    assert!(universe.undo());
+    assert_eq!(universe.rgba(), julia_view);
+
+    // Other fractals forget the view to return to, whether chosen or reached by undoing
+    universe.show_julia(5.0, 7.0).unwrap();
+    assert!(universe.julia_origin().is_some());
+    universe.set_settings(RenderSettings {
+        fractal: Fractal::Mandelbrot,
+        ..universe.get_settings()
+    });
+    assert_eq!(universe.julia_origin(), None);
+    assert!(!universe.leave_julia());
+
+    universe.show_julia(5.0, 7.0).unwrap();
+    assert!(universe.julia_origin().is_some());
+    assert!(universe.undo());
+    assert_eq!(universe.julia_origin(), None);
+}
@@ -0,0 +1,56 @@
+use fractal_rs::fractal::{Fractal, RenderSettings};
+use fractal_rs::kfr::{KfrError, KfrLocation};
+use fractal_rs::palette::Palette;
+use num::complex::Complex;
+
+#[test]
+pub fn test_parse_kfr_file() {
+    let location = KfrLocation::parse(include_str!("data/seahorse.kfr")).unwrap();
+    assert_eq!(
+        location.centre,
+        Complex::new(-0.7436438870371587, 0.13182590420531198)
+    );
+    assert_eq!(location.zoom, 2.5e5);
+    assert_eq!(location.settings.fractal, Fractal::Mandelbrot);
+    assert_eq!(location.settings.max_iterations, 2000);
+
+    // 4 / zoom across the 360 pixels of the height
+    let viewport = location.viewport(640, 360);
+    assert_eq!(viewport.get_pixel_size(), 4.0 / (2.5e5 * 360.0));
+}
+
+#[test]
+pub fn test_unsupported_kfr_fields() {
+    let result = KfrLocation::parse(include_str!("data/burning_ship.kfr"));
+    assert_eq!(
+        result,
+        Err(KfrError::Unsupported(
+            "FractalType".to_string(),
+            "1".to_string()
+        ))
+    );
+    let result = KfrLocation::parse("Re: 0\nIm: 0\nZoom: 1E20\nIterations: 100\n");
+    assert_eq!(result, Err(KfrError::ZoomTooDeep(1e20)));
+    let result = KfrLocation::parse("Re: 0\nIm: 0\nIterations: 100\n");
+    assert_eq!(result, Err(KfrError::Missing("Zoom")));
+}
+
+#[test]
+pub fn test_kfr_round_trip() {
+    for palette in Palette::ALL.iter() {
+        let location = KfrLocation {
+            centre: Complex::new(-0.10109636384562, 0.95628651080914),
+            zoom: 1.25e7,
+            settings: RenderSettings::new(Fractal::Mandelbrot, 750, palette.clone()),
+        };
+        assert_eq!(
+            KfrLocation::parse(&location.to_text()),
+            Ok(location.clone())
+        );
+
+        let metadata = location.view_metadata(800, 600);
+        let restored = KfrLocation::from_view_metadata(&metadata);
+        assert_eq!(restored.centre, location.centre);
+        assert!((restored.zoom / location.zoom - 1.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,225 @@
+use fractal_rs::density::{DensityRenderer, Screen};
+use fractal_rs::ifs;
+use fractal_rs::lsystem::{
+    self, LSystem, LSystemError, LSystemRenderer, Module, Segment, MAX_ITERATIONS,
+};
+use fractal_rs::mandelbrot::Position;
+use fractal_rs::palette::Palette;
+use fractal_rs::Universe;
+use rayon::ThreadPoolBuilder;
+
+#[test]
+pub fn test_expand() {
+    let algae = LSystem::parse("axiom: A\nA -> AB\nB -> A").unwrap();
+    let lengths: Vec<usize> = (0..8).map(|n| algae.expand(n).unwrap().len()).collect();
+    assert_eq!(lengths, vec![1, 2, 3, 5, 8, 13, 21, 34]);
+    assert_eq!(
+        lsystem::modules_to_string(&algae.expand(4).unwrap()),
+        "ABAABABA"
+    );
+
+    // Parameters are computed from the predecessor, rules apply where their condition holds
+    let parametric = LSystem::parse(
+        "axiom: A(1, 2)\n\
+         A(x, y) : x < 4 && !(y == 0) -> A(x + 1, y * 2) B(-x ^ 2)\n\
+         B(z) : z < -5 -> C",
+    )
+    .unwrap();
+    assert_eq!(
+        parametric.expand(2).unwrap(),
+        vec![
+            Module::new('A', vec![3.0, 8.0]),
+            Module::new('B', vec![-4.0]),
+            Module::new('B', vec![-1.0]),
+        ]
+    );
+    assert_eq!(
+        lsystem::modules_to_string(&parametric.expand(4).unwrap()),
+        "A(4,16)CB(-4)B(-1)"
+    );
+
+    // Numbers may have exponents
+    let scaled = LSystem::parse("axiom: A(5e-1)\nA(s) : s < 1e1 -> A(s * 2E1)").unwrap();
+    assert_eq!(
+        scaled.expand(3).unwrap(),
+        vec![Module::new('A', vec![10.0])]
+    );
+
+    // Stochastic choices follow the weights and only depend on the seed
+    let stochastic = LSystem::parse("axiom: X\nseed: 7\nX -> XA ; 3\nX -> XB").unwrap();
+    let modules = stochastic.expand(4000).err();
+    assert_eq!(modules, Some(LSystemError::TooManyIterations(4000)));
+    let text = lsystem::modules_to_string(&stochastic.expand(MAX_ITERATIONS).unwrap());
+    assert_eq!(
+        text,
+        lsystem::modules_to_string(&stochastic.expand(MAX_ITERATIONS).unwrap())
+    );
+    let a = text.matches('A').count();
+    assert!(a > 16 && a < 32);
+}
+
+#[test]
+pub fn test_errors() {
+    assert_eq!(LSystem::parse("F -> FF"), Err(LSystemError::MissingAxiom));
+    assert_eq!(
+        LSystem::preset("fern"),
+        Err(LSystemError::UnknownPreset("fern".to_string()))
+    );
+    match LSystem::parse("axiom: A\n\nA(x) -> A(y)") {
+        Err(LSystemError::Syntax { line: 3, message }) => assert!(message.contains("'y'")),
+        other => panic!("unexpected result {:?}", other),
+    }
+    match LSystem::parse("axiom: F\nangle: wide") {
+        Err(LSystemError::Syntax { line: 2, .. }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    match LSystem::parse("axiom: F\nF -> F(1 ; 2") {
+        Err(LSystemError::Syntax { line: 2, .. }) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert_eq!(
+        LSystem::parse("axiom: F\niterations: 40"),
+        Err(LSystemError::TooManyIterations(40))
+    );
+
+    // Every step quadruples the string, which soon gets too long
+    let growing = LSystem::parse("axiom: F\nF -> FFFF").unwrap();
+    assert!(growing.expand(9).is_ok());
+    assert_eq!(
+        growing.expand(11),
+        Err(LSystemError::TooLarge { iteration: 10 })
+    );
+}
+
+#[test]
+pub fn test_turtle() {
+    let system = LSystem::parse("axiom: F+F(2)[-F]f|G\nheading: 0").unwrap();
+    let segments = system.turtle(system.axiom());
+    let close =
+        |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).abs() < 1e-12 && (a.1 - b.1).abs() < 1e-12;
+    assert_eq!(segments.len(), 4);
+    assert!(close(segments[0].start, (0.0, 0.0)) && close(segments[0].end, (1.0, 0.0)));
+    assert!(close(segments[1].end, (1.0, 2.0)));
+    // The branch turns right and is nested once, then the turtle returns to its start
+    assert!(close(segments[2].end, (2.0, 2.0)));
+    assert_eq!(segments[2].depth, 1);
+    assert!(close(segments[3].start, (1.0, 3.0)) && close(segments[3].end, (1.0, 2.0)));
+    assert_eq!(segments[3].depth, 0);
+
+    // The Hilbert curve visits every point of a 2^n grid once
+    let hilbert = LSystem::preset("hilbert").unwrap();
+    let segments = hilbert.segments().unwrap();
+    assert_eq!(segments.len(), 32 * 32 - 1);
+    let ((x_min, x_max), (y_min, y_max)) = lsystem::bounds(&segments).unwrap();
+    assert!((x_max - x_min - 31.0).abs() < 1e-9 && (y_max - y_min - 31.0).abs() < 1e-9);
+
+    // The snowflake is closed
+    let koch = LSystem::preset("koch").unwrap().segments().unwrap();
+    assert_eq!(koch.len(), 3 * 4usize.pow(4));
+    assert!(close(koch[0].start, koch[koch.len() - 1].end));
+
+    for name in lsystem::lsystem_presets() {
+        assert!(!LSystem::preset(&name)
+            .unwrap()
+            .segments()
+            .unwrap()
+            .is_empty());
+    }
+}
+
+#[test]
+pub fn test_svg() {
+    let segments = vec![
+        Segment {
+            start: (0.0, 0.0),
+            end: (1.0, 0.0),
+            depth: 0,
+        },
+        Segment {
+            start: (1.0, 0.0),
+            end: (1.0, 1.0),
+            depth: 0,
+        },
+        Segment {
+            start: (0.0, 1.0),
+            end: (0.5, 0.5),
+            depth: 1,
+        },
+    ];
+    let svg = lsystem::to_svg(&segments, 100);
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\""));
+    // Connected lines share a path, y grows upwards
+    assert!(svg.contains("d=\"M2.50 97.50 L97.50 97.50 L97.50 2.50 M2.50 2.50 L50.00 50.00\""));
+    assert!(svg.trim_end().ends_with("</svg>"));
+}
+
+#[test]
+pub fn test_lsystem_rendering() {
+    // A square around the centre of the screen
+    let system = LSystem::parse("axiom: F+F+F+F\nheading: 0").unwrap();
+    let segments = system.segments().unwrap();
+    let position = lsystem::fitting_position(&segments, 32, 32);
+    let mut renderer = LSystemRenderer::new(
+        Screen::new(position.clone(), 32, 32),
+        segments,
+        Palette::Grayscale,
+    );
+    // Every sample draws one line
+    renderer.accumulate(1);
+    assert_eq!(renderer.samples(), 1);
+    let side: f32 = renderer.coverage().iter().sum();
+    renderer.accumulate(10);
+    assert_eq!(renderer.samples(), 4);
+    let coverage: f32 = renderer.coverage().iter().sum();
+    // Four sides of about 29 pixels
+    assert!(side > 25.0 && side < 33.0);
+    assert!(coverage > 100.0 && coverage < 130.0);
+    // Drawing again changes nothing
+    renderer.accumulate(1);
+    assert_eq!(renderer.samples(), 4);
+    assert_eq!(renderer.coverage().iter().sum::<f32>(), coverage);
+
+    let (r, _, _) = renderer.to_cells();
+    assert_eq!(r[16 * 32 + 16], 0);
+    assert!(
+        r[16 * 32..17 * 32]
+            .iter()
+            .filter(|value| **value > 0)
+            .count()
+            >= 2
+    );
+
+    // Zoomed into a corner the lines are clipped to the screen
+    let zoomed = Position::from_centre(
+        ifs::to_complex((0.0, 0.0)),
+        position.get_zoom_factor() * 1000.0,
+        32,
+        32,
+    );
+    let mut corner = renderer.restart(Screen::new(zoomed, 32, 32)).unwrap();
+    corner.accumulate(4);
+    let (r, _, _) = corner.to_cells();
+    assert!(r.iter().any(|value| *value > 0));
+}
+
+#[test]
+pub fn test_universe_lsystem() {
+    let thread_pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let universe = Universe::with_thread_pool(32, 32, Position::new(0, 0, 0.3), thread_pool);
+    let plant = LSystem::preset("plant").unwrap();
+    universe.start_l_system(&plant).unwrap();
+    let segments = plant.segments().unwrap();
+    assert_eq!(
+        universe.get_position(),
+        lsystem::fitting_position(&segments, 32, 32)
+    );
+    universe.draw_density(1).unwrap();
+    assert_eq!(universe.density().unwrap().samples(), 1);
+    universe.draw_density(segments.len() as u64).unwrap();
+    assert_eq!(universe.density().unwrap().samples(), segments.len() as u64);
+    assert!(universe.rgba().chunks(4).any(|p| p[1] > 0));
+    assert!(universe
+        .lsystem_svg("axiom: F", 64)
+        .unwrap()
+        .contains("<path"));
+}
@@ -0,0 +1,88 @@
+use fractal_rs::fractal::{Fractal, RenderSettings};
+use fractal_rs::lyapunov::{Lyapunov, LyapunovError, Region};
+use fractal_rs::mandelbrot::Position;
+use num::complex::Complex;
+use std::sync::Arc;
+
+mod common;
+
+#[test]
+pub fn test_lyapunov_exponent() {
+    let lyapunov = Lyapunov::new("a b").unwrap();
+    assert_eq!(lyapunov.sequence(), "AB");
+    // With a = b = 2 the fixed point 1/2 is superstable
+    assert!(lyapunov.exponent(Complex::new(2.0, 2.0), 200) < -5.0);
+    // The logistic map is chaotic at r = 3.9 with an exponent of about 0.5
+    let chaos = lyapunov.exponent(Complex::new(3.9, 3.9), 2000);
+    assert!((chaos - 0.5).abs() < 0.05);
+    assert_eq!(
+        lyapunov.iteration_quotient(Complex::new(3.9, 3.9), 200),
+        1.0
+    );
+    assert_eq!(lyapunov.escape(Complex::new(3.9, 3.9), 200), None);
+    // Rates outside of [0, 4] count as chaotic
+    assert_eq!(
+        lyapunov.iteration_quotient(Complex::new(5.0, 3.0), 200),
+        1.0
+    );
+
+    let stable = lyapunov.iteration_quotient(Complex::new(3.0, 2.5), 200);
+    assert!(stable > 0.0 && stable < 1.0);
+}
+
+#[test]
+pub fn test_lyapunov_validation() {
+    assert_eq!(Lyapunov::new(""), Err(LyapunovError::EmptySequence));
+    assert_eq!(Lyapunov::new("ABC"), Err(LyapunovError::InvalidSymbol('C')));
+    assert_eq!(
+        Lyapunov::new(&"AB".repeat(40)),
+        Err(LyapunovError::SequenceTooLong(80))
+    );
+    let region = |a_min, a_max, b_min, b_max| Region {
+        a_min,
+        a_max,
+        b_min,
+        b_max,
+    };
+    assert_eq!(Region::default().validate(), Ok(()));
+    assert!(region(3.0, 2.0, 2.0, 4.0).validate().is_err());
+    assert!(region(2.0, 4.5, 2.0, 4.0).validate().is_err());
+    assert!(region(2.0, 4.0, f64::NAN, 4.0).validate().is_err());
+
+    let fractal = Fractal::Lyapunov(Arc::new(Lyapunov::new("AABAB").unwrap()));
+    assert_eq!(fractal.spec(), "lyapunov:AABAB");
+    assert_eq!(Fractal::from_spec(&fractal.spec()), Some(fractal));
+    assert_eq!(Fractal::from_spec("lyapunov:ABX"), None);
+}
+
+#[test]
+pub fn test_lyapunov_region_view() {
+    let universe = common::universe(40, 20, Position::new(0, 0, 1.0));
+    let region = Region {
+        a_min: 3.0,
+        a_max: 4.0,
+        b_min: 0.5,
+        b_max: 4.0,
+    };
+    let fractal = Fractal::Lyapunov(Arc::new(Lyapunov::default()));
+    let settings = RenderSettings {
+        fractal,
+        ..RenderSettings::default()
+    };
+    let metadata = region.view_metadata(settings.clone(), 40, 20).unwrap();
+    universe.restore_view(&metadata);
+    assert_eq!(universe.get_settings(), settings);
+    assert_eq!(
+        universe.get_position(),
+        Position::from_centre(Complex::new(3.5, 2.25), metadata.zoom_factor, 40, 20)
+    );
+    // The region fills the 20 rows along a and fits into the 40 columns along b, up to the
+    // rounding of the position to whole pixels
+    assert!((metadata.zoom_factor - 0.5).abs() < 1e-12);
+    let position = universe.get_position();
+    let top_left = position.pixel_to_complex(-0.5, -0.5, 40, 20);
+    let bottom_right = position.pixel_to_complex(19.5, 39.5, 40, 20);
+    let pixel = 1.0 / (40.0 * metadata.zoom_factor);
+    assert!((top_left.re - 3.0).abs() <= pixel && (bottom_right.re - 4.0).abs() <= pixel);
+    assert!(top_left.im <= 0.5 + pixel && bottom_right.im >= 4.0 - pixel);
+}
@@ -0,0 +1,187 @@
+use fractal_rs::fractal::Fractal;
+use fractal_rs::iteration_data::IterationData;
+use fractal_rs::mesh::{HeightTransfer, Heightfield, Mesh, MeshError, MeshSettings};
+use fractal_rs::viewport::Viewport;
+use num::complex::Complex;
+use std::collections::HashSet;
+
+// Every edge is used once in each direction, so the surface is closed and consistently oriented
+fn assert_watertight(mesh: &Mesh) {
+    let mut edges = HashSet::new();
+    for [a, b, c] in &mesh.triangles {
+        for edge in [(*a, *b), (*b, *c), (*c, *a)] {
+            assert!(edges.insert(edge), "edge {:?} is used twice", edge);
+        }
+    }
+    for (a, b) in &edges {
+        assert!(edges.contains(&(*b, *a)), "edge {:?} is open", (a, b));
+    }
+}
+
+fn field(width: u32, height: u32, value: impl Fn(u32, u32) -> f64) -> Heightfield {
+    let values = (0..height)
+        .flat_map(|row| (0..width).map(move |col| (row, col)))
+        .map(|(row, col)| value(row, col))
+        .collect();
+    Heightfield {
+        width,
+        height,
+        values,
+    }
+}
+
+#[test]
+pub fn test_height_transfer() {
+    assert_eq!(HeightTransfer::Linear.apply(25.0, 100), 0.25);
+    assert_eq!(HeightTransfer::SquareRoot.apply(25.0, 100), 0.5);
+    assert_eq!(HeightTransfer::Power(2.0).apply(50.0, 100), 0.25);
+    assert_eq!(HeightTransfer::Logarithmic.apply(100.0, 100), 1.0);
+    assert!(HeightTransfer::Logarithmic.apply(10.0, 100) > 0.5);
+    // Points of the set are on top, counts beyond the limit are clamped
+    assert_eq!(HeightTransfer::Linear.apply(f32::INFINITY, 100), 1.0);
+    assert_eq!(HeightTransfer::Linear.apply(150.0, 100), 1.0);
+
+    let spike = field(
+        3,
+        3,
+        |row, col| if (row, col) == (1, 1) { 9.0 } else { 0.0 },
+    );
+    let smoothed = spike.smoothed(1);
+    assert!(smoothed.values.iter().all(|value| *value == 1.0));
+    assert_eq!(spike.smoothed(0), spike);
+}
+
+#[test]
+pub fn test_flat_mesh() {
+    let settings = MeshSettings {
+        size: 4.0,
+        base: 0.5,
+        ..MeshSettings::default()
+    };
+    let flat = field(5, 5, |_, _| 0.3);
+    let mesh = Mesh::from_heightfield(&flat, &settings).unwrap();
+    assert_watertight(&mesh);
+    assert!((mesh.volume() - 4.0 * 4.0 * 0.5).abs() < 1e-9);
+    // Two triangles per cell on top, three per border vertex for the walls and the bottom
+    assert_eq!(mesh.triangles.len(), 2 * 16 + 3 * 16);
+    assert!(mesh.vertices.iter().all(|v| v[2] == 0.0 || v[2] == 0.5));
+
+    // Decimated, the top is a single square
+    let decimated = MeshSettings {
+        decimation: Some(0.0),
+        ..settings
+    };
+    let mesh = Mesh::from_heightfield(&flat, &decimated).unwrap();
+    assert_watertight(&mesh);
+    assert!((mesh.volume() - 4.0 * 4.0 * 0.5).abs() < 1e-9);
+    assert_eq!(mesh.triangles.len(), 2 + 3 * 4);
+}
+
+#[test]
+pub fn test_decimated_mesh() {
+    // A plateau with a ramp on its right, on a grid whose size is no power of two
+    let plateau = field(13, 7, |_, col| (col.max(8) - 8) as f64);
+    let settings = MeshSettings {
+        size: 12.0,
+        relief: 4.0,
+        base: 1.0,
+        decimation: Some(1e-9),
+        ..MeshSettings::default()
+    };
+    let full = Mesh::from_heightfield(
+        &plateau,
+        &MeshSettings {
+            decimation: None,
+            ..settings.clone()
+        },
+    )
+    .unwrap();
+    let decimated = Mesh::from_heightfield(&plateau, &settings).unwrap();
+    assert_watertight(&full);
+    assert_watertight(&decimated);
+    assert!(decimated.triangles.len() < full.triangles.len() * 3 / 4);
+    // Flat regions are exact, the ramp is kept as is
+    assert!((decimated.volume() - full.volume()).abs() < 1e-9);
+    let expected = 12.0 * 6.0 * 1.0 + 6.0 * (4.0 * 4.0 / 2.0);
+    assert!((full.volume() - expected).abs() < 1e-9);
+    // Walls and the bottom point outwards
+    for triangle in &full.triangles {
+        let normal = full.normal(triangle);
+        let centre: Vec<f64> = (0..3)
+            .map(|axis| {
+                triangle
+                    .iter()
+                    .map(|idx| full.vertices[*idx][axis])
+                    .sum::<f64>()
+                    / 3.0
+            })
+            .collect();
+        if centre[2] == 0.0 {
+            assert_eq!(normal, [0.0, 0.0, -1.0]);
+        } else if centre[0] == 0.0 {
+            assert_eq!(normal, [-1.0, 0.0, 0.0]);
+        }
+    }
+}
+
+#[test]
+pub fn test_iteration_data_mesh() {
+    let viewport = Viewport::with_extent(Complex::new(-0.75, 0.0), 3.5, 40, 30);
+    let data = IterationData::render(Fractal::Mandelbrot, viewport, 64, false);
+    let settings = MeshSettings {
+        decimation: Some(0.01),
+        ..MeshSettings::default()
+    };
+    let mesh = Mesh::from_iteration_data(&data, &settings).unwrap();
+    assert_watertight(&mesh);
+    let full = Mesh::from_iteration_data(
+        &data,
+        &MeshSettings {
+            decimation: None,
+            ..settings
+        },
+    )
+    .unwrap();
+    assert!(mesh.triangles.len() < full.triangles.len());
+    assert!((mesh.volume() - full.volume()).abs() / full.volume() < 0.01);
+    let top = mesh.vertices.iter().map(|v| v[2]).fold(0.0, f64::max);
+    assert!((top - 12.0).abs() < 1e-9);
+
+    let mut obj = Vec::new();
+    mesh.write_obj(&mut obj).unwrap();
+    let obj = String::from_utf8(obj).unwrap();
+    assert_eq!(
+        obj.lines().filter(|line| line.starts_with("v ")).count(),
+        mesh.vertices.len()
+    );
+    assert_eq!(
+        obj.lines().filter(|line| line.starts_with("f ")).count(),
+        mesh.triangles.len()
+    );
+
+    let mut stl = Vec::new();
+    mesh.write_stl(&mut stl).unwrap();
+    assert_eq!(stl.len(), 84 + 50 * mesh.triangles.len());
+    let count = u32::from_le_bytes([stl[80], stl[81], stl[82], stl[83]]);
+    assert_eq!(count as usize, mesh.triangles.len());
+}
+
+#[test]
+pub fn test_mesh_errors() {
+    let settings = MeshSettings::default();
+    assert_eq!(
+        Mesh::from_heightfield(&field(1, 5, |_, _| 0.0), &settings),
+        Err(MeshError::TooSmall {
+            width: 1,
+            height: 5
+        })
+    );
+    let no_base = MeshSettings {
+        base: 0.0,
+        ..MeshSettings::default()
+    };
+    assert_eq!(
+        Mesh::from_heightfield(&field(2, 2, |_, _| 0.0), &no_base),
+        Err(MeshError::InvalidSetting("base"))
+    );
+}
@@ -0,0 +1,114 @@
+use fractal_rs::fractal::{Fractal, RenderSettings};
+use fractal_rs::newton::{self, Newton, NewtonError};
+use fractal_rs::palette::Palette;
+use num::complex::Complex;
+use std::sync::Arc;
+
+#[test]
+pub fn test_basins_of_cubic() {
+    let newton = Newton::from_coefficients(&[
+        Complex::new(-1.0, 0.0),
+        Complex::new(0.0, 0.0),
+        Complex::new(0.0, 0.0),
+        Complex::new(1.0, 0.0),
+    ])
+    .unwrap();
+    assert_eq!(newton.roots().len(), 3);
+    for root in newton.roots() {
+        assert!((root.powi(3) - 1.0).norm() < 1e-9);
+    }
+
+    // Points close to a root converge to it, and quicker than points further away
+    for (idx, root) in newton.roots().iter().enumerate() {
+        let near = newton.converge(root * 1.1, 100).unwrap();
+        let far = newton.converge(root * 3.0, 100).unwrap();
+        assert_eq!(near.root, Some(idx));
+        assert_eq!(far.root, Some(idx));
+        assert!(near.smoothed_iterations < far.smoothed_iterations);
+    }
+    // The origin is a critical point where Newton's method is undefined
+    assert_eq!(newton.converge(Complex::new(0.0, 0.0), 100), None);
+
+    let settings = RenderSettings::new(Fractal::Newton(Arc::new(newton)), 100, Palette::Classic);
+    assert_eq!(settings.rgb_value_at(Complex::new(0.0, 0.0)), (0, 0, 0));
+    assert_ne!(
+        settings.rgb_value_at(Complex::new(1.1, 0.0)),
+        settings.rgb_value_at(Complex::new(-0.55, 0.95))
+    );
+}
+
+#[test]
+pub fn test_roots_and_coefficients() {
+    let roots = [Complex::new(2.0, 0.0), Complex::new(0.0, 1.0)];
+    let newton = Newton::from_roots(&roots).unwrap();
+    // (z - 2)(z - i) = z^2 - (2 + i) z + 2i
+    assert_eq!(
+        newton.coefficients(),
+        &[
+            Complex::new(0.0, 2.0),
+            Complex::new(-2.0, -1.0),
+            Complex::new(1.0, 0.0)
+        ]
+    );
+    assert_eq!(newton.roots(), &roots);
+    assert_eq!(
+        newton.converge(Complex::new(0.1, 0.9), 50).unwrap().root,
+        Some(1)
+    );
+}
+
+#[test]
+pub fn test_relaxed_and_nova() {
+    let newton = Newton::default();
+    let plain = newton.converge(Complex::new(1.5, 0.5), 200).unwrap();
+    let relaxed = newton
+        .clone()
+        .with_relaxation(Complex::new(0.5, 0.0))
+        .converge(Complex::new(1.5, 0.5), 200)
+        .unwrap();
+    // Halved steps converge linearly to the same root
+    assert_eq!(relaxed.root, plain.root);
+    assert!(relaxed.smoothed_iterations > plain.smoothed_iterations);
+
+    let nova = newton.with_nova(true);
+    let convergence = nova.converge(Complex::new(-0.2, 0.0), 200).unwrap();
+    assert_eq!(convergence.root, None);
+}
+
+#[test]
+pub fn test_newton_spec() {
+    let newton = Newton::parse("roots=1, -0.5+0.25i, -1e-3-2i; relaxation=0.75; nova").unwrap();
+    assert_eq!(
+        newton.roots(),
+        &[
+            Complex::new(1.0, 0.0),
+            Complex::new(-0.5, 0.25),
+            Complex::new(-1e-3, -2.0)
+        ]
+    );
+    assert_eq!(newton.relaxation(), Complex::new(0.75, 0.0));
+    assert!(newton.is_nova());
+
+    let fractal = Fractal::Newton(Arc::new(newton));
+    assert_eq!(Fractal::from_spec(&fractal.spec()), Some(fractal));
+    assert_eq!(
+        Fractal::from_spec("newton"),
+        Some(Fractal::Newton(Arc::new(Newton::default())))
+    );
+
+    assert_eq!(newton::parse_complex("i"), Ok(Complex::new(0.0, 1.0)));
+    assert_eq!(newton::parse_complex("-i"), Ok(Complex::new(0.0, -1.0)));
+    assert_eq!(newton::parse_complex("1e-2+i"), Ok(Complex::new(0.01, 1.0)));
+    assert_eq!(
+        Newton::parse("roots=1,x"),
+        Err(NewtonError::InvalidNumber("x".to_string()))
+    );
+    assert_eq!(
+        Newton::parse("coefficients=3,0"),
+        Err(NewtonError::ConstantPolynomial)
+    );
+    assert_eq!(
+        Newton::parse("relaxation=2"),
+        Err(NewtonError::MissingPolynomial)
+    );
+}
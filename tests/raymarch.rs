@@ -0,0 +1,188 @@
+use fractal_rs::mandelbrot::Position;
+use fractal_rs::palette::Palette;
+use fractal_rs::raymarch::{self, Camera, RayMarchError, RayMarcher, Solid, SolidKind};
+use fractal_rs::Universe;
+use rayon::ThreadPoolBuilder;
+
+#[test]
+pub fn test_solid_parameters() {
+    for kind in SolidKind::ALL.iter() {
+        assert_eq!(SolidKind::from_name(kind.name()), Some(*kind));
+        assert_eq!(
+            kind.parameter_names().len(),
+            kind.default_parameters().len()
+        );
+        assert!(Solid::new(*kind, kind.default_parameters()).is_ok());
+    }
+    assert_eq!(raymarch::solid_types().len(), 2);
+    assert_eq!(
+        raymarch::solid_default_parameters("mandelbulb"),
+        vec![8.0, 12.0]
+    );
+    assert!(raymarch::solid_parameter_names("menger").is_empty());
+
+    assert_eq!(
+        Solid::from_name("menger", &[]),
+        Err(RayMarchError::UnknownSolid("menger".to_string()))
+    );
+    assert_eq!(
+        Solid::from_name("mandelbulb", &[8.0]),
+        Err(RayMarchError::ParameterCount {
+            expected: 2,
+            found: 1
+        })
+    );
+    assert_eq!(
+        Solid::from_name("mandelbulb", &[8.0, 2.5]),
+        Err(RayMarchError::InvalidParameter("iterations", 2.5))
+    );
+    assert_eq!(
+        Solid::from_name("mandelbox", &[0.5, 0.5, 1.0, 10.0]),
+        Err(RayMarchError::InvalidParameter("scale", 0.5))
+    );
+    // The minimum radius must be smaller than the fixed one
+    assert_eq!(
+        Solid::from_name("mandelbox", &[2.0, 1.5, 1.0, 10.0]),
+        Err(RayMarchError::InvalidParameter("min_radius", 1.5))
+    );
+    assert!(Solid::from_name("mandelbox", &[-1.5, 0.5, 1.0, 10.0]).is_ok());
+}
+
+#[test]
+pub fn test_distance_estimates() {
+    let bulb = Solid::from_name("mandelbulb", &[]).unwrap();
+    assert_eq!(bulb.distance([0.0, 0.0, 0.0]), 0.0);
+    // The bulb reaches out to about 1.1 along the axes, the estimate is a lower bound
+    let distance = bulb.distance([3.0, 0.0, 0.0]);
+    assert!(distance > 0.5 && distance < 2.0);
+    assert!(bulb.distance([1.5, 0.0, 0.0]) < distance);
+
+    let mandelbox = Solid::from_name("mandelbox", &[]).unwrap();
+    assert_eq!(mandelbox.distance([0.0, 0.0, 0.0]), 0.0);
+    let distance = mandelbox.distance([20.0, 0.0, 0.0]);
+    assert!(distance > 1.0 && distance < 14.0);
+    assert!((mandelbox.bounding_radius() - 6.0 * 3f64.sqrt()).abs() < 1e-12);
+}
+
+#[test]
+pub fn test_march() {
+    let bulb = Solid::from_name("mandelbulb", &[]).unwrap();
+    let t = bulb.march([0.0, -5.0, 0.0], [0.0, 1.0, 0.0], 1e-3).unwrap();
+    assert!(t > 3.5 && t < 4.5);
+    // Rays starting inside the bounding sphere march from their origin
+    let t = bulb.march([0.0, -1.5, 0.0], [0.0, 1.0, 0.0], 1e-3).unwrap();
+    assert!(t > 0.0 && t < 1.0);
+    // Rays passing the bounding sphere or pointing away miss
+    assert_eq!(bulb.march([0.0, -5.0, 3.0], [0.0, 1.0, 0.0], 1e-3), None);
+    assert_eq!(bulb.march([0.0, -5.0, 0.0], [0.0, -1.0, 0.0], 1e-3), None);
+}
+
+#[test]
+pub fn test_camera() {
+    assert!(Camera::new([0.0, 0.0, 1.0], [0.0, 0.0, 1.0], 45.0).is_err());
+    assert!(Camera::new([0.0, -3.0, 0.0], [0.0, 0.0, 0.0], 180.0).is_err());
+    assert!(Camera::new([f64::NAN, -3.0, 0.0], [0.0, 0.0, 0.0], 45.0).is_err());
+
+    let camera = Camera::new([0.0, -3.0, 0.0], [0.0, 0.0, 0.0], 90.0).unwrap();
+    let close = |a: [f64; 3], b: [f64; 3]| a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-12);
+    assert!(close(camera.ray(1, 1, 3, 3), [0.0, 1.0, 0.0]));
+    // The z axis points up in the image and x to the right, 90 degrees span the height
+    let norm = (1.0f64 + 4.0 / 9.0).sqrt();
+    assert!(close(
+        camera.ray(0, 1, 3, 3),
+        [0.0, 1.0 / norm, 2.0 / 3.0 / norm]
+    ));
+    assert!(close(
+        camera.ray(1, 2, 3, 3),
+        [2.0 / 3.0 / norm, 1.0 / norm, 0.0]
+    ));
+
+    // Looking straight down still has an up direction
+    let down = Camera::new([0.0, 0.0, 3.0], [0.0, 0.0, 0.0], 45.0).unwrap();
+    assert!(down.ray(0, 0, 4, 4).iter().all(|value| value.is_finite()));
+    assert_eq!(down.ray_in(down.basis(), 0, 0, 4, 4), down.ray(0, 0, 4, 4));
+
+    assert_eq!(
+        Camera::from_values(&[0.0, 0.0, 3.0, 0.0, 0.0, 0.0, 45.0]),
+        Ok(down)
+    );
+    assert!(matches!(
+        Camera::from_values(&[0.0, 0.0, 3.0]),
+        Err(RayMarchError::InvalidCamera(_))
+    ));
+}
+
+#[test]
+pub fn test_ray_marching() {
+    let bulb = Solid::from_name("mandelbulb", &[]).unwrap();
+    let mut renderer = RayMarcher::new(
+        24,
+        16,
+        bulb.clone(),
+        bulb.default_camera(),
+        Palette::Grayscale,
+    );
+    renderer.accumulate(100);
+    assert_eq!(renderer.samples(), 100);
+    let (r, _, _) = renderer.to_cells();
+    assert!(r[100..].iter().all(|value| *value == 0));
+    renderer.accumulate(1000);
+    assert_eq!(renderer.samples(), 24 * 16);
+
+    let (r, g, b) = renderer.to_cells();
+    assert_eq!(r, g);
+    assert_eq!(g, b);
+    // The bulb is in the middle of the image, the corners show the background
+    assert!(r[8 * 24 + 12] > 0);
+    assert_eq!(r[0], 0);
+    assert_eq!(r[16 * 24 - 1], 0);
+
+    // Traced in parallel in several steps the image is the same
+    let thread_pool = ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+    let mut steps = renderer.restart(24, 16, bulb.default_camera());
+    thread_pool.install(|| {
+        steps.accumulate(50);
+        steps.accumulate(u64::MAX);
+    });
+    assert_eq!(steps.to_cells(), renderer.to_cells());
+}
+
+#[test]
+pub fn test_universe_ray_marching() {
+    let thread_pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let position = Position::new(0, 0, 0.3);
+    let universe = Universe::with_thread_pool(16, 16, position.clone(), thread_pool);
+    let mandelbox = Solid::from_name("mandelbox", &[]).unwrap();
+    universe.start_ray_marching(mandelbox.clone(), mandelbox.default_camera());
+    assert_eq!(universe.get_position(), position);
+    assert!(universe.density().is_none());
+    universe.draw_ray_marching(16 * 8);
+    assert_eq!(universe.ray_marcher().unwrap().samples(), 16 * 8);
+
+    // Moving the view in the plane keeps tracing the same image
+    universe.move_horizontal(4);
+    universe.draw_ray_marching(16 * 8);
+    assert_eq!(universe.ray_marcher().unwrap().samples(), 16 * 16);
+    assert!(universe.rgba().chunks(4).any(|p| p[1] > 0));
+
+    // Another camera starts over
+    let camera = Camera::new([0.0, -5.0, 0.0], [0.0, 0.0, 0.0], 60.0).unwrap();
+    universe.move_camera(camera.clone());
+    assert_eq!(universe.ray_marcher().unwrap().samples(), 0);
+    assert_eq!(universe.ray_marcher().unwrap().camera(), &camera);
+
+    universe
+        .start_solid(
+            "mandelbulb",
+            &[4.0, 8.0],
+            &[0.0, -3.0, 0.0, 0.0, 0.0, 0.0, 60.0],
+        )
+        .unwrap();
+    universe.draw_ray_marching(16 * 16);
+    assert!(universe.rgba().chunks(4).any(|p| p[1] > 0));
+    assert_eq!(universe.traced_pixels(), 256.0);
+
+    // Density images replace the fractal in space
+    universe.start_nebulabrot();
+    assert!(universe.ray_marcher().is_none());
+}
@@ -0,0 +1,88 @@
+use fractal_rs::resample::{resample, Interpolation};
+
+// Two rows of three cells
+const SOURCE: [u8; 6] = [0, 10, 20, 30, 40, 50];
+
+// Samples the source at fractional (row, column) positions into a single row
+fn sample_at(points: &[(f64, f64)], interpolation: Interpolation) -> Vec<u8> {
+    let mut target = vec![0; points.len()];
+    resample(
+        &SOURCE,
+        (3, 2),
+        &mut target,
+        (points.len() as u32, 1),
+        interpolation,
+        |_, col| points[col as usize],
+    );
+    target
+}
+
+#[test]
+pub fn test_resample_identity() {
+    for interpolation in [Interpolation::Nearest, Interpolation::Bilinear] {
+        let mut target = vec![0; 6];
+        resample(
+            &SOURCE,
+            (3, 2),
+            &mut target,
+            (3, 2),
+            interpolation,
+            |row, col| (row as f64, col as f64),
+        );
+        assert_eq!(target, SOURCE);
+    }
+}
+
+#[test]
+pub fn test_resample_nearest() {
+    let points = [(0.4, 1.6), (0.6, 0.4), (1.0, 2.0)];
+    assert_eq!(sample_at(&points, Interpolation::Nearest), vec![20, 30, 50]);
+
+    // Positions outside of the source take the nearest edge or corner
+    let points = [
+        (-3.0, 1.0),
+        (5.0, 1.2),
+        (0.0, -0.7),
+        (1.0, 9.0),
+        (-1.0, 7.0),
+    ];
+    assert_eq!(
+        sample_at(&points, Interpolation::Nearest),
+        vec![10, 40, 0, 50, 20]
+    );
+
+    // Enlarging repeats every cell
+    let mut target = vec![0; 24];
+    resample(
+        &SOURCE,
+        (3, 2),
+        &mut target,
+        (6, 4),
+        Interpolation::Nearest,
+        |row, col| ((row / 2) as f64, (col / 2) as f64),
+    );
+    assert_eq!(&target[..6], &[0, 0, 10, 10, 20, 20]);
+    assert_eq!(&target[18..], &[30, 30, 40, 40, 50, 50]);
+}
+
+#[test]
+pub fn test_resample_bilinear() {
+    let points = [(0.0, 0.5), (0.5, 0.5), (0.25, 1.0), (0.5, 1.75)];
+    assert_eq!(
+        sample_at(&points, Interpolation::Bilinear),
+        vec![5, 20, 18, 33]
+    );
+
+    // Beyond the edges the edge cells are blended only with each other
+    let points = [
+        (0.5, 2.0),
+        (1.0, 2.5),
+        (-0.5, -0.5),
+        (3.0, 0.5),
+        (0.5, -4.0),
+    ];
+    assert_eq!(
+        sample_at(&points, Interpolation::Bilinear),
+        vec![35, 50, 0, 35, 15]
+    );
+}
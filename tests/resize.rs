@@ -0,0 +1,71 @@
+use fractal_rs::mandelbrot::Position;
+
+mod common;
+
+fn coordinates(position: &Position) -> (i64, i64, f64) {
+    (
+        position.get_x(),
+        position.get_y(),
+        position.get_zoom_factor(),
+    )
+}
+
+#[test]
+pub fn test_resize_keeps_view() {
+    let mut position = Position::new(-100, 50, 0.8);
+    let centre = position.pixel_to_complex(24.0, 32.0, 64, 48);
+    position.resize(64, 48, 128, 72);
+
+    // Twice as many pixels along the real axis show the same extent
+    assert!((position.get_zoom_factor() - 0.4).abs() < 1e-12);
+    // The centre moves by less than a pixel, as the position is rounded to whole pixels
+    let pixel = 1.0 / (128.0 * position.get_zoom_factor());
+    let new_centre = position.pixel_to_complex(36.0, 64.0, 128, 72);
+    assert!((new_centre.re - centre.re).abs() <= pixel);
+    assert!((new_centre.im - centre.im).abs() <= pixel);
+}
+
+#[test]
+pub fn test_resize_unchanged_or_empty() {
+    let mut position = Position::new(-100, 50, 0.8);
+    let original = coordinates(&position);
+    position.resize(64, 48, 64, 48);
+    assert_eq!(coordinates(&position), original);
+
+    // A zero sized screen would need an infinite zoom factor
+    position.resize(64, 48, 0, 0);
+    assert_eq!(coordinates(&position), original);
+    position.resize(64, 48, 64, 0);
+    assert_eq!(coordinates(&position), original);
+    position.resize(0, 0, 64, 48);
+    assert_eq!(coordinates(&position), original);
+}
+
+#[test]
+pub fn test_universe_resize() {
+    let position = Position::new(-10, 0, 1.0);
+    let mut universe = common::universe(40, 30, position.clone());
+    let cells = universe.rgba();
+
+    // Resizing to the same size keeps the view and its cells
+    universe.resize(40, 30);
+    assert_eq!(coordinates(&universe.get_position()), coordinates(&position));
+    assert_eq!(universe.rgba(), cells);
+
+    // Empty screens are ignored
+    universe.resize(0, 0);
+    universe.resize(40, 0);
+    assert_eq!((universe.width(), universe.height()), (40, 30));
+    assert_eq!(coordinates(&universe.get_position()), coordinates(&position));
+    assert_eq!(universe.rgba(), cells);
+
+    // Growing the screen keeps the centre and draws the whole new screen
+    let centre = position.centre(40, 30);
+    universe.resize(80, 60);
+    assert_eq!(universe.rgba().len(), 80 * 60 * 4);
+    let new_centre = universe.get_position().centre(80, 60);
+    let pixel = 1.0 / (80.0 * universe.get_position().get_zoom_factor());
+    assert!((new_centre - centre).norm() <= 2.0 * pixel);
+    let resized = common::universe(80, 60, universe.get_position());
+    assert_eq!(universe.rgba(), resized.rgba());
+}
@@ -0,0 +1,86 @@
+use fractal_rs::fractal::Fractal;
+use fractal_rs::mandelbrot::portable_ln;
+use fractal_rs::slippy::{self, TileError, TileRequest, TILE_SIZE};
+
+#[test]
+pub fn test_portable_ln_matches_ln() {
+    for value in [1e-300, 0.001, 0.5, 1.0, 2.0, 3.7, 1e10, 1e300] {
+        assert!((portable_ln(value) - value.ln()).abs() <= 1e-12 * value.ln().abs().max(1.0));
+    }
+}
+
+#[test]
+pub fn test_tile_bounds() {
+    let (corner, pixel_size) = slippy::tile_bounds(1, 1, 1).unwrap();
+    assert_eq!((corner.re, corner.im), (-0.5, 0.0));
+    assert_eq!(pixel_size, 2.0 / TILE_SIZE as f64);
+    assert_eq!(
+        slippy::tile_bounds(1, 2, 0),
+        Err(TileError::OutOfRange { z: 1, x: 2, y: 0 })
+    );
+}
+
+#[test]
+pub fn test_render_tile() {
+    let rgba = slippy::render_tile(&Fractal::Mandelbrot, 0, 0, 0, 51).unwrap();
+    assert_eq!(rgba.len(), (TILE_SIZE * TILE_SIZE * 4) as usize);
+    // The centre of the plane at -0.5 + 0i lies inside the set
+    let idx = ((TILE_SIZE / 2 * TILE_SIZE + TILE_SIZE / 2) * 4) as usize;
+    assert_eq!(&rgba[idx..idx + 4], &[0, 0, 0, 255]);
+}
+
+#[test]
+pub fn test_tile_request() {
+    let tile = TileRequest::parse("/tiles/mandelbrot/3/2/5.png").unwrap();
+    assert_eq!(
+        tile,
+        TileRequest {
+            fractal: Fractal::Mandelbrot,
+            z: 3,
+            x: 2,
+            y: 5,
+            max_iterations: slippy::default_max_iterations(3),
+        }
+    );
+
+    // Every query parameter is looked at, unknown ones are ignored
+    let tile = TileRequest::parse("/tiles/mandelbrot/3/2/5.png?v=2&iterations=300").unwrap();
+    assert_eq!(tile.max_iterations, 300);
+    let tile = TileRequest::parse("/tiles/mandelbrot/0/0/0.png?iterations=300&v=2").unwrap();
+    assert_eq!(tile.max_iterations, 300);
+    // Iteration limits are clamped
+    let tile = TileRequest::parse("/tiles/mandelbrot/0/0/0.png?iterations=99999999999").unwrap();
+    assert_eq!(tile.max_iterations, slippy::MAX_TILE_ITERATIONS);
+    let tile = TileRequest::parse("/tiles/mandelbrot/0/0/0.png?iterations=0").unwrap();
+    assert_eq!(tile.max_iterations, 1);
+    assert_eq!(
+        TileRequest::parse("/tiles/mandelbrot/0/0/0.png?iterations=many"),
+        Err(TileError::InvalidQuery("iterations=many".to_string()))
+    );
+}
+
+#[test]
+pub fn test_tile_request_not_found() {
+    assert_eq!(
+        TileRequest::parse("/tiles/mandelbrot/1/2/0.png"),
+        Err(TileError::OutOfRange { z: 1, x: 2, y: 0 })
+    );
+    assert_eq!(
+        TileRequest::parse("/tiles/mandelbrot/41/0/0.png"),
+        Err(TileError::ZoomTooDeep(41))
+    );
+    assert_eq!(
+        TileRequest::parse("/tiles/menger/0/0/0.png"),
+        Err(TileError::UnknownFractal("menger".to_string()))
+    );
+    for url in [
+        "/tiles/mandelbrot/0/0/0",
+        "/tiles/mandelbrot/0/0/x.png",
+        "/tiles/mandelbrot/0/0.png",
+        "/other/mandelbrot/0/0/0.png",
+        "/tiles/mandelbrot/0/-1/0.png?iterations=5",
+    ] {
+        let path = url.split('?').next().unwrap().to_string();
+        assert_eq!(TileRequest::parse(url), Err(TileError::NotFound(path)));
+    }
+}
@@ -0,0 +1,55 @@
+use fractal_rs::fractal::{Fractal, RenderSettings};
+use fractal_rs::mandelbrot::Position;
+use fractal_rs::palette::Palette;
+use fractal_rs::state::{StateError, ViewState};
+
+mod common;
+use common::universe_with_settings;
+
+#[test]
+pub fn test_state_round_trip() {
+    let settings = RenderSettings::new(Fractal::Mandelbrot, 120, Palette::Ocean);
+    let original = universe_with_settings(48, 32, Position::new(-9, 4, 2.7), settings);
+
+    let state = original.view_state().encode();
+    assert!(state
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    let decoded = ViewState::decode(&state).unwrap();
+    assert_eq!(decoded, original.view_state());
+
+    let restored = universe_with_settings(
+        decoded.width,
+        decoded.height,
+        decoded.position,
+        decoded.settings,
+    );
+    assert_eq!(restored.rgba(), original.rgba());
+}
+
+#[test]
+pub fn test_invalid_states() {
+    assert_eq!(
+        ViewState::decode("not base64!"),
+        Err(StateError::InvalidEncoding)
+    );
+    assert_eq!(
+        ViewState::decode("AwAA"),
+        Err(StateError::UnsupportedVersion(3))
+    );
+    assert_eq!(ViewState::decode("AQAA"), Err(StateError::InvalidLength(3)));
+
+    // Zoom factors that would break the mapping of pixels to the complex plane
+    for zoom_factor in [0.0, -2.7, f64::NAN, f64::INFINITY] {
+        let view_state = ViewState {
+            settings: RenderSettings::new(Fractal::Mandelbrot, 120, Palette::Ocean),
+            position: Position::new(-9, 4, zoom_factor),
+            width: 48,
+            height: 32,
+        };
+        assert_eq!(
+            ViewState::decode(&view_state.encode()),
+            Err(StateError::InvalidZoom)
+        );
+    }
+}
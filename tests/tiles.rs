@@ -0,0 +1,53 @@
+use fractal_rs::fractal::RenderSettings;
+use fractal_rs::mandelbrot::{mandelbrot_rgb_value, Position};
+use fractal_rs::tiles::{self, TileCache, TILE_SIZE};
+
+fn draw(position: &Position, cache: &mut TileCache, width: u32, height: u32) -> Vec<u8> {
+    let size = (width * height) as usize;
+    let (mut cells_r, mut cells_g, mut cells_b) = (vec![0; size], vec![0; size], vec![0; size]);
+    tiles::draw_view(
+        position,
+        &RenderSettings::default(),
+        cache,
+        (&mut cells_r, &mut cells_g, &mut cells_b),
+        width,
+        height,
+        tiles::render_tiles,
+    );
+    cells_g
+}
+
+#[test]
+pub fn test_tiles_match_pixels() {
+    let (width, height) = (100, 70);
+    let position = Position::new(-37, 13, 1.0);
+    let mut cache = TileCache::new(tiles::DEFAULT_CACHE_CAPACITY);
+    let cells_g = draw(&position, &mut cache, width, height);
+    for row in 0..height {
+        for col in 0..width {
+            let (_, g, _) = mandelbrot_rgb_value(row, col, width, height, &position);
+            assert_eq!(cells_g[(row * width + col) as usize], g);
+        }
+    }
+}
+
+#[test]
+pub fn test_visible_tiles_centre_first() {
+    let position = Position::new(0, 0, 1.0);
+    let keys = tiles::visible_tiles(&position, 5 * TILE_SIZE, 3 * TILE_SIZE);
+    assert_eq!(keys.len(), 15);
+    assert_eq!((keys[0].get_row(), keys[0].get_col()), (1, 2));
+}
+
+#[test]
+pub fn test_cache_evicts_least_recently_used() {
+    let (width, height) = (2 * TILE_SIZE, TILE_SIZE);
+    let mut position = Position::new(0, 0, 1.0);
+    let mut cache = TileCache::new(2);
+    draw(&position, &mut cache, width, height);
+    let first = tiles::visible_tiles(&position, width, height);
+    position.move_horizontal(TILE_SIZE as i64);
+    draw(&position, &mut cache, width, height);
+    assert_eq!(cache.len(), 2);
+    assert!(!cache.contains(first.iter().find(|key| key.get_col() == 0).unwrap()));
+}
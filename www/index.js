@@ -4,20 +4,36 @@ import { Universe, WorkerPool } from "wasm-game-of-life";
 
 let universe;
 const canvas = document.getElementById("game-of-life-canvas");
-let zoomFactor = 1.0;
 let width = window.innerWidth;
 let height = window.innerHeight;
-let x = BigInt(-Math.floor(width / 4));
-let y = BigInt(0);
 let relativeMoveFactor = 50;
 let threads = 1;
 let pool = new WorkerPool(threads);
 
+// The view is kept in the URL fragment so that it survives reloads and links can be shared
+const stateFromUrl = () => window.location.hash.slice(1);
+
+const saveState = () => {
+    history.replaceState(null, "", "#" + universe.to_state());
+};
+
 const generateUniverse = () => {
     width = window.innerWidth;
     height = window.innerHeight;
-    console.log("Generating new universe", { width, height, x, y, zoomFactor, zoomFactor, threads });
-    universe = Universe.new(width, height, y, x, zoomFactor, pool, threads);
+    const state = stateFromUrl();
+    if (state) {
+        try {
+            console.log("Restoring universe", { width, height, state, threads });
+            universe = Universe.from_state(state, width, height, pool, threads);
+        } catch (error) {
+            console.error("Could not restore view from URL", error);
+        }
+    }
+    if (!universe) {
+        console.log("Generating new universe", { width, height, threads });
+        universe = Universe.new(width, height, BigInt(0), BigInt(-Math.floor(width / 4)), 1.0, pool, threads);
+    }
+    saveState();
     console.log("Generated universe");
 
     canvas.height = height;
@@ -67,24 +83,49 @@ const render = () => {
     });
 };
 
+// Draws the resampled preview produced by zooming first and the recomputed cells afterwards
+const drawPreviewAndRefine = () => {
+    requestAnimationFrame(() => {
+        drawCells();
+        requestAnimationFrame(() => {
+            universe.update();
+            drawCells();
+        });
+    });
+};
+
 render();
 
-// addEventListener("resize", render);
+addEventListener("resize", () => {
+    width = window.innerWidth;
+    height = window.innerHeight;
+    universe.resize(width, height);
+    canvas.height = height;
+    canvas.width = width;
+    saveState();
+    // Resizing renders the new screen already
+    requestAnimationFrame(drawCells);
+});
 
-addEventListener("keyup", (event) => {
+addEventListener("keyup", async (event) => {
     if (event.key === "+") {
         console.log("Zoom in");
-        zoomFactor = universe.zoom_in();
+        const zoomFactor = universe.zoom_in();
         console.log({ zoomFactor });
-        universe.update();
+        saveState();
+        drawPreviewAndRefine();
+        return;
     } else if (event.key === "-") {
         console.log("Zoom out");
-        zoomFactor = universe.zoom_out();
+        const zoomFactor = universe.zoom_out();
         console.log({ zoomFactor });
-        universe.update();
+        saveState();
+        drawPreviewAndRefine();
+        return;
     } else if (event.key == "w") {
         console.log("Move Up");
-        y = universe.move_vertical(
+        // The move renders the uncovered rows asynchronously, save the state once it is done
+        const y = await universe.move_vertical(
             BigInt(-Math.floor(height / relativeMoveFactor)),
             pool
         );
@@ -92,7 +133,7 @@ addEventListener("keyup", (event) => {
     } else if (event.key == "s") {
         // down arrow
         console.log("Move Down");
-        y = universe.move_vertical(
+        const y = await universe.move_vertical(
             BigInt(Math.floor(height / relativeMoveFactor)),
             pool
         );
@@ -100,21 +141,166 @@ addEventListener("keyup", (event) => {
     } else if (event.key == "a") {
         // left arrow
         console.log("Move Left");
-        x = universe.move_horizontal(
+        const x = universe.move_horizontal(
             BigInt(-Math.floor(width / relativeMoveFactor))
         );
         console.log({ x });
     } else if (event.key == "d") {
         // right arrow
         console.log("Move Right");
-        x = universe.move_horizontal(
+        const x = universe.move_horizontal(
             BigInt(Math.floor(width / relativeMoveFactor))
         );
         console.log({ x });
+    } else if (event.key == "z") {
+        console.log("Undo");
+        universe.undo();
+    } else if (event.key == "y") {
+        console.log("Redo");
+        universe.redo();
+    } else if (event.key == "p") {
+        console.log("Save PNG");
+        savePng();
+        return;
+    } else if (event.key == "b") {
+        console.log("Buddhabrot");
+        universe.start_buddhabrot(buddhabrotIterations);
+        drawDensity();
+        return;
+    } else if (event.key == "n") {
+        console.log("Nebulabrot");
+        universe.start_nebulabrot();
+        drawDensity();
+        return;
+    } else if (event.key == "j") {
+        console.log("Julia set");
+        if (!pointer) {
+            return;
+        }
+        try {
+            universe.show_julia(pointer.row, pointer.col);
+        } catch (error) {
+            console.error("Could not show Julia set", error);
+            return;
+        }
+        hideJuliaPreview();
+    } else if (event.key == "m") {
+        console.log("Back from Julia set");
+        if (!universe.leave_julia()) {
+            return;
+        }
+    } else if (event.key == "Escape") {
+        console.log("Escape time rendering");
+        drawingDensity = false;
+        universe.stop_density();
     } else {
         return;
     }
+    saveState();
     requestAnimationFrame(() => {
         drawCells();
     });
 });
+
+// Density images are refined with more samples every frame until they are stopped
+let drawingDensity = false;
+const buddhabrotIterations = 500;
+const densitySamplesPerFrame = 20000;
+
+const drawDensity = () => {
+    if (drawingDensity) {
+        return;
+    }
+    drawingDensity = true;
+    const step = async () => {
+        if (!drawingDensity) {
+            return;
+        }
+        await universe.accumulate_density(pool, densitySamplesPerFrame);
+        drawCells();
+        console.log("Density samples", universe.density_samples());
+        requestAnimationFrame(step);
+    };
+    requestAnimationFrame(step);
+};
+
+// Downloads the current view as PNG, the view itself is recorded in the file's metadata
+const savePng = () => {
+    const bytes = universe.to_png();
+    const url = URL.createObjectURL(new Blob([bytes], { type: "image/png" }));
+    const link = document.createElement("a");
+    link.href = url;
+    link.download = "fractal.png";
+    link.click();
+    URL.revokeObjectURL(url);
+};
+
+// Dropping a PNG saved before onto the canvas restores its view
+canvas.addEventListener("dragover", (event) => event.preventDefault());
+canvas.addEventListener("drop", async (event) => {
+    event.preventDefault();
+    const file = event.dataTransfer.files[0];
+    if (!file) {
+        return;
+    }
+    try {
+        universe.load_png(new Uint8Array(await file.arrayBuffer()));
+    } catch (error) {
+        console.error("Could not restore view", error);
+        return;
+    }
+    saveState();
+    requestAnimationFrame(() => {
+        drawCells();
+    });
+});
+
+// Hovering over the Mandelbrot set previews the Julia set of the point under the pointer, "j"
+// switches to it. Only one preview is rendered at a time, the latest pointer position wins
+const juliaPreview = document.getElementById("julia-preview");
+let pointer = null;
+let renderingPreview = false;
+
+// Julia sets are only previewed over the escape time rendering of the Mandelbrot set
+const showsMandelbrot = () => universe.fractal() === "mandelbrot" && !drawingDensity;
+
+const hideJuliaPreview = () => {
+    juliaPreview.style.visibility = "hidden";
+};
+
+const drawJuliaPreview = async () => {
+    if (renderingPreview || !pointer) {
+        return;
+    }
+    renderingPreview = true;
+    const { row, col } = pointer;
+    const { width, height } = juliaPreview;
+    try {
+        const rgba = await universe.julia_preview(pool, row, col, width, height);
+        // The pointer may have left or the view switched to another fractal meanwhile
+        if (pointer && showsMandelbrot()) {
+            const image = new ImageData(new Uint8ClampedArray(rgba.buffer), width, height);
+            juliaPreview.getContext("2d").putImageData(image, 0, 0);
+            juliaPreview.style.visibility = "visible";
+        }
+    } catch (error) {
+        console.error("Could not preview Julia set", error);
+    }
+    renderingPreview = false;
+    if (pointer && (pointer.row !== row || pointer.col !== col)) {
+        drawJuliaPreview();
+    }
+};
+
+canvas.addEventListener("mousemove", (event) => {
+    pointer = { row: event.offsetY, col: event.offsetX };
+    if (!showsMandelbrot()) {
+        hideJuliaPreview();
+        return;
+    }
+    drawJuliaPreview();
+});
+canvas.addEventListener("mouseleave", () => {
+    pointer = null;
+    hideJuliaPreview();
+});